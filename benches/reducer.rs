@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::hint::black_box;
+use std::rc::Rc;
+
+use chrono::{DateTime, Utc};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use proxima_backend::database::ProxDatabase;
+use proxima_backend::database::chats::ChatID;
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_frontend_ui::app::{DatabaseAction, DatabaseState};
+use proxima_frontend_ui::db_sync::UserCursors;
+use proxima_frontend_ui::tokenizer::TokenizerKind;
+use yew::Reducible;
+
+const PARTS_PER_CHAT:usize = 50;
+
+fn synthetic_database(chats:usize) -> ProxDatabase {
+    let mut db = ProxDatabase::new_just_data(String::from("bench"), String::from("bench"));
+    let text = "Some text of about the length of a short chat message, repeated to make it longer. ".repeat(8);
+    for _ in 0..chats {
+        let parts = (0..PARTS_PER_CHAT).map(|i| {
+            ContextPart::new(vec![ContextData::Text(text.clone())], if i % 2 == 0 {ContextPosition::User} else {ContextPosition::AI})
+        }).collect();
+        let chat = db.chats.create_possible_chat(WholeContext::new(parts), None, 0, None);
+        db.chats.add_chat_raw(chat);
+    }
+    db
+}
+
+#[allow(dead_code)]
+struct OldStreamingData {
+    token_ids:Vec<(u64, DateTime<Utc>)>,
+    all_ids:HashSet<u64>,
+    last_update:DateTime<Utc>
+}
+
+/// The state as it was before the chats were shared, every action cloned all of it
+#[allow(dead_code)]
+struct OldDatabaseState {
+    db:ProxDatabase,
+    cursors:UserCursors,
+    update_flipper:bool,
+    token_streams:HashMap<ChatID, OldStreamingData>,
+    received_updates:HashSet<u64>,
+    ongoing_chats:HashSet<ChatID>
+}
+
+impl OldDatabaseState {
+    /// The streamed token arm of the old reducer
+    fn add_data_to_last_part(self:Rc<Self>, chat_id:ChatID, token_id:u64, data:ContextData) -> Rc<Self> {
+        let mut database = self.db.clone();
+        let cursors = self.cursors.clone();
+        let mut update_flipper = self.update_flipper;
+        let mut token_streams = self.token_streams.iter().map(|(id, stream)| {
+            (*id, OldStreamingData { token_ids: stream.token_ids.clone(), all_ids: stream.all_ids.clone(), last_update: stream.last_update })
+        }).collect::<HashMap<ChatID, OldStreamingData>>();
+        let received_updates = self.received_updates.clone();
+        let ongoing_chats = self.ongoing_chats.clone();
+        update_flipper = !update_flipper;
+        if let Some(chat) = database.chats.get_chats_mut().get_mut(&chat_id) && let Some(stream) = token_streams.get_mut(&chat_id) && !stream.all_ids.contains(&token_id) {
+            let last_part = chat.context.get_parts_mut().last_mut().unwrap();
+            match last_part.get_data_mut().last_mut().unwrap() {
+                ContextData::Text(text) => {
+                    match data {
+                        ContextData::Text(new_text) => *text += &new_text,
+                        ContextData::Media(image) => last_part.add_data(ContextData::Media(image)),
+                    }
+                },
+                ContextData::Media(_) => last_part.add_data(data)
+            }
+            stream.all_ids.insert(token_id);
+            stream.last_update = Utc::now();
+            stream.token_ids.push((token_id, stream.last_update));
+        }
+        Rc::new(OldDatabaseState { db:database, cursors, update_flipper, token_streams, received_updates, ongoing_chats })
+    }
+}
+
+/// The last chats of a synthetic database with a response started in each of them, returns the last one
+fn streaming_state(chats:usize, streams:usize) -> (Rc<DatabaseState>, ChatID) {
+    let db = synthetic_database(chats);
+    let last_chat = db.chats.latest_id - 1;
+    let mut state = Rc::new(DatabaseState::default());
+    state = state.reduce(DatabaseAction::SetDB(db));
+    for chat_id in (last_chat + 1 - streams)..=last_chat {
+        state = state.reduce(DatabaseAction::AddToOngoingChats { chat: chat_id, config: String::from("bench"), tokenizer: TokenizerKind::default() });
+        state = state.reduce(DatabaseAction::AddPartToChat { chat_id, token_id: 0, part: ContextPart::new(vec![ContextData::Text(String::new())], ContextPosition::AI) });
+        for token_id in 1..200 {
+            state = state.reduce(DatabaseAction::AddDataToLastPartOfChat { chat_id, token_id, data: ContextData::Text(String::from(" token")) });
+        }
+    }
+    (state, last_chat)
+}
+
+fn old_streaming_state(chats:usize) -> (Rc<OldDatabaseState>, ChatID) {
+    let mut db = synthetic_database(chats);
+    let chat_id = db.chats.latest_id - 1;
+    if let Some(chat) = db.chats.get_chats_mut().get_mut(&chat_id) {
+        chat.context.add_part(ContextPart::new(vec![ContextData::Text(String::new())], ContextPosition::AI));
+    }
+    let token_streams = HashMap::from([(chat_id, OldStreamingData { token_ids: vec![(0, Utc::now())], all_ids: HashSet::from([0]), last_update: Utc::now() })]);
+    (Rc::new(OldDatabaseState { db, cursors: UserCursors::zero(), update_flipper: false, token_streams, received_updates: HashSet::new(), ongoing_chats: HashSet::from([chat_id]) }), chat_id)
+}
+
+/// Cost of a streamed token, every token starts from the same state which stays alive like the state the
+/// components last rendered with
+fn streamed_token(c:&mut Criterion) {
+    let mut group = c.benchmark_group("streamed_token");
+    for chats in [10, 200, 1000] {
+        group.bench_with_input(BenchmarkId::new("copying_database", chats), &chats, |b, &chats| {
+            let (rendered, chat_id) = old_streaming_state(chats);
+            b.iter(|| {
+                black_box(rendered.clone().add_data_to_last_part(chat_id, 1, ContextData::Text(String::from(" token"))))
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("shared_chats", chats), &chats, |b, &chats| {
+            let (rendered, chat_id) = streaming_state(chats, 1);
+            b.iter(|| {
+                black_box(rendered.clone().reduce(DatabaseAction::AddDataToLastPartOfChat { chat_id, token_id: 200, data: ContextData::Text(String::from(" token")) }))
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Cost of a streamed token while other chats are streaming too, their streams and live responses are shared
+/// with the rendered state instead of copied
+fn concurrent_streams(c:&mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_streams");
+    for streams in [1, 8, 32] {
+        group.bench_with_input(BenchmarkId::new("streamed_token", streams), &streams, |b, &streams| {
+            let (rendered, chat_id) = streaming_state(200, streams);
+            b.iter(|| {
+                black_box(rendered.clone().reduce(DatabaseAction::AddDataToLastPartOfChat { chat_id, token_id: 200, data: ContextData::Text(String::from(" token")) }))
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("new_part", streams), &streams, |b, &streams| {
+            let (rendered, chat_id) = streaming_state(200, streams);
+            b.iter(|| {
+                black_box(rendered.clone().reduce(DatabaseAction::AddPartToChat { chat_id, token_id: 200, part: ContextPart::new(vec![ContextData::Text(String::from("Next"))], ContextPosition::AI) }))
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, streamed_token, concurrent_streams);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum DatasetFormat {
+    OpenAIJsonl,
+    ShareGPT,
+    Alpaca
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DatasetToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DatasetMessage {
+    pub role: String,
+    pub content: String,
+    /// Calls made by an assistant turn, each answered by the tool turn carrying its id
+    pub tool_calls: Vec<DatasetToolCall>,
+    pub tool_call_id: Option<String>,
+}
+
+impl DatasetMessage {
+    pub fn new(role: &str, content: String) -> Self {
+        Self { role: role.to_string(), content, tool_calls: Vec::new(), tool_call_id: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DatasetConversation {
+    pub messages: Vec<DatasetMessage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DatasetWritten {
+    pub records: usize,
+    /// Prompts the format could not hold, like Alpaca prompts answered through tool calls
+    pub skipped: usize,
+}
@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum ImportedRole {
+    User,
+    AI,
+    System,
+    Tool
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImportedMessage {
+    pub role: ImportedRole,
+    pub text: String,
+    pub date: Option<DateTime<Utc>>,
+    /// Name of the tool that gave a tool message, when the file has it
+    pub tool_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImportedChat {
+    pub title: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub messages: Vec<ImportedMessage>,
+}
@@ -0,0 +1,163 @@
+use std::{fs, path::PathBuf};
+
+use proxima_frontend_shared::dataset::{DatasetConversation, DatasetFormat, DatasetMessage, DatasetWritten};
+use serde_json::json;
+
+fn sharegpt_role(role: &str) -> &str {
+    match role {
+        "user" => "human",
+        "assistant" => "gpt",
+        "tool" => "observation",
+        other => other
+    }
+}
+
+fn openai_message(message: &DatasetMessage) -> serde_json::Value {
+    let mut record = json!({ "role": message.role, "content": message.content });
+    if !message.tool_calls.is_empty() {
+        record["tool_calls"] = message.tool_calls.iter().map(|call| json!({ "id": call.id, "type": "function", "function": { "name": call.name, "arguments": call.arguments } })).collect();
+    }
+    if let Some(id) = &message.tool_call_id {
+        record["tool_call_id"] = json!(id);
+    }
+    record
+}
+
+/// Calls become function_call turns after the text of their turn, their outputs are observation turns
+fn sharegpt_turns(message: &DatasetMessage) -> Vec<serde_json::Value> {
+    let mut turns = Vec::with_capacity(1 + message.tool_calls.len());
+    if !message.content.is_empty() || message.tool_calls.is_empty() {
+        turns.push(json!({ "from": sharegpt_role(&message.role), "value": message.content }));
+    }
+    for call in &message.tool_calls {
+        turns.push(json!({ "from": "function_call", "value": json!({ "name": call.name, "arguments": call.arguments }).to_string() }));
+    }
+    turns
+}
+
+/// Every user turn directly answered by the assistant becomes one Alpaca record, answers that call tools cannot be written as one
+/// and are counted as skipped instead
+fn alpaca_records(conversation: &DatasetConversation) -> (Vec<serde_json::Value>, usize) {
+    let system = conversation.messages.iter().find(|message| message.role == "system").map(|message| message.content.clone());
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    for pair in conversation.messages.windows(2).filter(|pair| pair[0].role == "user") {
+        if pair[1].role != "assistant" || !pair[1].tool_calls.is_empty() {
+            skipped += 1;
+            continue
+        }
+        let mut record = json!({ "instruction": pair[0].content, "input": "", "output": pair[1].content });
+        if let Some(system) = &system {
+            record["system"] = json!(system);
+        }
+        records.push(record);
+    }
+    (records, skipped)
+}
+
+/// Returns how many records were written and how many prompts were left out
+pub fn write_dataset(conversations: Vec<DatasetConversation>, format: DatasetFormat, path: PathBuf) -> Result<DatasetWritten, String> {
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    let (content, records, skipped) = match format {
+        DatasetFormat::OpenAIJsonl => {
+            let lines = conversations.iter().map(|conversation| {
+                json!({ "messages": conversation.messages.iter().map(openai_message).collect::<Vec<_>>() }).to_string()
+            }).collect::<Vec<String>>();
+            (lines.join("\n") + "\n", lines.len(), 0)
+        },
+        DatasetFormat::ShareGPT => {
+            let lines = conversations.iter().map(|conversation| {
+                json!({ "conversations": conversation.messages.iter().flat_map(sharegpt_turns).collect::<Vec<_>>() }).to_string()
+            }).collect::<Vec<String>>();
+            (lines.join("\n") + "\n", lines.len(), 0)
+        },
+        DatasetFormat::Alpaca => {
+            let (records, skipped) = conversations.iter().map(alpaca_records).fold((Vec::new(), 0), |(mut records, skipped), (conversation_records, conversation_skipped)| {
+                records.extend(conversation_records);
+                (records, skipped + conversation_skipped)
+            });
+            let number = records.len();
+            (serde_json::to_string_pretty(&records).map_err(|error| format!("{error}"))?, number, skipped)
+        }
+    };
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|error| format!("{error}"))?;
+        }
+    }
+    fs::write(&path, content).map_err(|error| format!("{error}"))?;
+    Ok(DatasetWritten { records, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_frontend_shared::dataset::DatasetToolCall;
+
+    use super::*;
+
+    fn message(role: &str, content: &str) -> DatasetMessage {
+        DatasetMessage::new(role, content.to_string())
+    }
+
+    fn calling(content: &str, id: &str) -> DatasetMessage {
+        DatasetMessage { tool_calls: vec![DatasetToolCall { id: id.to_string(), name: String::from("search"), arguments: String::from("{\"q\":\"rust\"}") }], ..message("assistant", content) }
+    }
+
+    fn tool_round_trip() -> DatasetConversation {
+        DatasetConversation { messages: vec![
+            message("system", "Be brief"),
+            message("user", "Hi"),
+            message("assistant", "Hello"),
+            message("user", "Search rust"),
+            calling("", "call_1"),
+            DatasetMessage { tool_call_id: Some(String::from("call_1")), ..message("tool", "results") },
+            message("assistant", "Rust is a language"),
+        ] }
+    }
+
+    #[test]
+    fn openai_messages_carry_calls_and_their_ids() {
+        assert_eq!(openai_message(&message("user", "Hi")), json!({ "role": "user", "content": "Hi" }));
+        assert_eq!(openai_message(&calling("Looking", "call_1")), json!({
+            "role": "assistant",
+            "content": "Looking",
+            "tool_calls": [{ "id": "call_1", "type": "function", "function": { "name": "search", "arguments": "{\"q\":\"rust\"}" } }]
+        }));
+        let output = DatasetMessage { tool_call_id: Some(String::from("call_1")), ..message("tool", "results") };
+        assert_eq!(openai_message(&output), json!({ "role": "tool", "content": "results", "tool_call_id": "call_1" }));
+    }
+
+    #[test]
+    fn sharegpt_calls_follow_the_text_of_their_turn() {
+        assert_eq!(sharegpt_turns(&message("user", "Hi")), vec![json!({ "from": "human", "value": "Hi" })]);
+        assert_eq!(sharegpt_turns(&message("tool", "results")), vec![json!({ "from": "observation", "value": "results" })]);
+        let turns = sharegpt_turns(&calling("Looking", "call_1"));
+        assert_eq!(turns[0], json!({ "from": "gpt", "value": "Looking" }));
+        assert_eq!(turns[1]["from"], "function_call");
+        let call: serde_json::Value = serde_json::from_str(turns[1]["value"].as_str().unwrap()).unwrap();
+        assert_eq!(call, json!({ "name": "search", "arguments": "{\"q\":\"rust\"}" }));
+        // a turn made only of a call has no empty text turn before it
+        assert_eq!(sharegpt_turns(&calling("", "call_1")).len(), 1);
+    }
+
+    #[test]
+    fn alpaca_counts_prompts_answered_through_tools_as_skipped() {
+        let (records, skipped) = alpaca_records(&tool_round_trip());
+        assert_eq!(records, vec![json!({ "instruction": "Hi", "input": "", "output": "Hello", "system": "Be brief" })]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn written_datasets_report_records_and_skipped_prompts() {
+        let path = std::env::temp_dir().join(format!("proxima_dataset_test_{}.json", std::process::id()));
+        let written = write_dataset(vec![tool_round_trip()], DatasetFormat::Alpaca, path.clone()).unwrap();
+        assert_eq!(written, DatasetWritten { records: 1, skipped: 1 });
+        let written = write_dataset(vec![tool_round_trip(), tool_round_trip()], DatasetFormat::OpenAIJsonl, path.clone()).unwrap();
+        assert_eq!(written, DatasetWritten { records: 2, skipped: 0 });
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        fs::remove_file(path).unwrap();
+        assert!(write_dataset(Vec::new(), DatasetFormat::ShareGPT, PathBuf::from("relative.jsonl")).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportedChat {
+    pub id: usize,
+    pub title: Option<String>,
+    pub config: Option<String>,
+    pub parts: Vec<ExportedPart>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportedPart {
+    pub index: usize,
+    pub position: String,
+    pub date: Option<String>,
+    pub sections: Vec<ExportedSection>,
+    pub media: Vec<ExportedMedia>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind")]
+pub enum ExportedSection {
+    Text { text: String },
+    Thinking { text: String },
+    ToolCall { tool: String, text: String },
+    ToolOutputs { text: String },
+    Memory { text: String },
+    CurrentTime { text: String },
+    Other { name: String, text: String },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExportedMedia {
+    pub hash: String,
+    pub file_name: Option<String>,
+    pub media_type: Option<String>,
+}
+
+const HTML_STYLE: &str = "body { font-family: Inter, Avenir, Helvetica, Arial, sans-serif; max-width: 60em; margin: auto; background-color: #f6f6f6; }
+.part { background-color: #ffffff; border: 3px outset #000000; padding: 4px; margin-bottom: 8px; }
+.AI { background-color: #e0e0e0; }
+.Tool { background-color: #c0c0c0; }
+.part-header { display: flex; justify-content: space-between; font-weight: bold; }
+pre { overflow-x: auto; background-color: #f0f0f0; padding: 4px; }
+img { max-width: 100%; }";
+
+fn chat_title(chat: &ExportedChat) -> String {
+    chat.title.clone().unwrap_or(format!("Untitled Chat {}", chat.id))
+}
+
+fn file_name_for(chat: &ExportedChat, format: &ExportFormat) -> String {
+    let slug: String = chat_title(chat)
+        .chars()
+        .map(|character| if character.is_alphanumeric() { character.to_ascii_lowercase() } else { '_' })
+        .take(40)
+        .collect();
+    let extension = match format {
+        ExportFormat::Markdown => "md",
+        ExportFormat::Json => "json",
+        ExportFormat::Html => "html",
+    };
+    format!("chat_{}_{}.{}", chat.id, slug, extension)
+}
+
+/// The fence is longer than any run of backticks in the text so that the text can not close it
+fn code_fence(text: &str) -> String {
+    let longest_run = text.split(|character| character != '`').map(|run| run.len()).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    format!("{}\n{}\n{}", fence, text, fence)
+}
+
+pub fn chat_to_markdown(chat: &ExportedChat, url: &str) -> String {
+    let mut out = format!("# {}\n\n", chat_title(chat));
+    if let Some(config) = &chat.config {
+        out += &format!("*Chat configuration : {}*\n\n", config);
+    }
+    for part in &chat.parts {
+        out += &format!("## {} (part {})", part.position, part.index);
+        if let Some(date) = &part.date {
+            out += &format!(" - {}", date);
+        }
+        out += "\n\n";
+        for section in &part.sections {
+            let section_text = match section {
+                ExportedSection::Text { text } => text.clone(),
+                ExportedSection::Thinking { text } => format!("<details>\n<summary>Thought process</summary>\n\n{}\n\n</details>", text),
+                ExportedSection::ToolCall { tool, text } => format!("**Tool call : {}**\n\n{}", tool, code_fence(text)),
+                ExportedSection::ToolOutputs { text } => format!("**Tool call outputs**\n\n{}", code_fence(text)),
+                ExportedSection::Memory { text } => format!("**Automatic memory**\n\n> {}", text.lines().collect::<Vec<&str>>().join("\n> ")),
+                ExportedSection::CurrentTime { text } => format!("*Current time : {}*", text),
+                ExportedSection::Other { name, text } => format!("**{}**\n\n{}", name, text),
+            };
+            out += &section_text;
+            out += "\n\n";
+        }
+        for media in &part.media {
+            let name = media.file_name.clone().unwrap_or(media.hash.clone());
+            match media.media_type.as_deref() {
+                Some("Image") => out += &format!("![{}]({}/media/{})\n\n", name, url, media.hash),
+                _ => out += &format!("*Attached file : {}*\n\n", name),
+            }
+        }
+    }
+    out
+}
+
+async fn fetch_image_data_uri(url: &str, hash: &str) -> Option<String> {
+    let response = reqwest::Client::new().get(format!("{}/media/{}", url, hash)).send().await.ok()?;
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("image/png")
+        .to_string();
+    let bytes = response.bytes().await.ok()?;
+    Some(format!("data:{};base64,{}", mime, BASE64_STANDARD.encode(bytes)))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub async fn chat_to_html(chat: &ExportedChat, url: &str) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(&chat_title(chat)));
+    if let Some(config) = &chat.config {
+        body += &format!("<p><em>Chat configuration : {}</em></p>\n", escape_html(config));
+    }
+    for part in &chat.parts {
+        body += &format!(
+            "<div class=\"part {}\">\n<div class=\"part-header\"><span>{}</span><span>{}</span></div>\n",
+            part.position,
+            part.position,
+            part.date.clone().unwrap_or_default()
+        );
+        for section in &part.sections {
+            body += &match section {
+                ExportedSection::Text { text } => markdown::to_html(text),
+                ExportedSection::Thinking { text } => format!("<details><summary>Thought process</summary>{}</details>", markdown::to_html(text)),
+                ExportedSection::ToolCall { tool, text } => format!("<details><summary>Tool call : {}</summary><pre>{}</pre></details>", escape_html(tool), escape_html(text)),
+                ExportedSection::ToolOutputs { text } => format!("<details><summary>Tool call outputs</summary><pre>{}</pre></details>", escape_html(text)),
+                ExportedSection::Memory { text } => format!("<details><summary>Automatic memory</summary>{}</details>", markdown::to_html(text)),
+                ExportedSection::CurrentTime { text } => format!("<p><em>Current time : {}</em></p>", escape_html(text)),
+                ExportedSection::Other { name, text } => format!("<p><strong>{}</strong></p>{}", escape_html(name), markdown::to_html(text)),
+            };
+            body += "\n";
+        }
+        for media in &part.media {
+            let name = escape_html(&media.file_name.clone().unwrap_or(media.hash.clone()));
+            match media.media_type.as_deref() {
+                Some("Image") => match fetch_image_data_uri(url, &media.hash).await {
+                    Some(data_uri) => body += &format!("<img alt=\"{}\" src=\"{}\"/>\n", name, data_uri),
+                    None => body += &format!("<p><em>Image {} could not be embedded</em></p>\n", name),
+                },
+                _ => body += &format!("<p><em>Attached file : {}</em></p>\n", name),
+            }
+        }
+        body += "</div>\n";
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&chat_title(chat)),
+        HTML_STYLE,
+        body
+    )
+}
+
+pub async fn render_chat(chat: &ExportedChat, format: &ExportFormat, url: &str) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => Ok(chat_to_markdown(chat, url)),
+        ExportFormat::Json => serde_json::to_string_pretty(chat).map_err(|error| format!("{error}")),
+        ExportFormat::Html => Ok(chat_to_html(chat, url).await),
+    }
+}
+
+/// A single chat goes to the given file unless it is a folder, several chats get one file each inside the given folder
+/// Files already in the folder are never replaced, the new ones get a numbered name instead
+pub async fn write_export(chats: Vec<ExportedChat>, format: ExportFormat, path: PathBuf, url: String) -> Result<usize, String> {
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    let into_folder = chats.len() > 1 || path.is_dir();
+    if into_folder {
+        fs::create_dir_all(&path).map_err(|error| format!("{error}"))?;
+    }
+    let mut written = 0;
+    for chat in &chats {
+        let rendered = render_chat(chat, &format, &url).await?;
+        let file_path = if into_folder { free_path(path.join(file_name_for(chat, &format))) } else { path.clone() };
+        write_file(&file_path, rendered)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn free_path(path: PathBuf) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.clone();
+    let mut number = 2;
+    while candidate.exists() {
+        candidate = path.with_file_name(format!("{stem}_{number}{extension}"));
+        number += 1;
+    }
+    candidate
+}
+
+fn write_file(path: &Path, content: String) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|error| format!("{error}"))?;
+        }
+    }
+    fs::write(path, content).map_err(|error| format!("{error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(index: usize, position: &str, sections: Vec<ExportedSection>) -> ExportedPart {
+        ExportedPart { index, position: position.to_string(), date: None, sections, media: Vec::new() }
+    }
+
+    fn chat(title: Option<&str>, parts: Vec<ExportedPart>) -> ExportedChat {
+        ExportedChat { id: 7, title: title.map(|title| title.to_string()), config: Some(String::from("Local")), parts }
+    }
+
+    fn test_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("proxima_export_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn fences_outgrow_backticks_in_the_text() {
+        assert_eq!(code_fence("plain"), "```\nplain\n```");
+        assert_eq!(code_fence("a ``` b"), "````\na ``` b\n````");
+        assert_eq!(code_fence("`````"), "``````\n`````\n``````");
+    }
+
+    #[test]
+    fn markdown_has_a_section_per_part() {
+        let chat = chat(Some("Rust"), vec![
+            part(0, "User", vec![ExportedSection::Text { text: String::from("Hello") }]),
+            part(1, "AI", vec![
+                ExportedSection::Thinking { text: String::from("hmm") },
+                ExportedSection::ToolCall { tool: String::from("Python"), text: String::from("print(1)") },
+                ExportedSection::Memory { text: String::from("line 1\nline 2") },
+            ]),
+        ]);
+        let markdown = chat_to_markdown(&chat, "http://server");
+        assert!(markdown.starts_with("# Rust\n\n*Chat configuration : Local*"));
+        assert!(markdown.contains("## User (part 0)\n\nHello"));
+        assert!(markdown.contains("<summary>Thought process</summary>\n\nhmm"));
+        assert!(markdown.contains("**Tool call : Python**\n\n```\nprint(1)\n```"));
+        assert!(markdown.contains("> line 1\n> line 2"));
+    }
+
+    #[test]
+    fn markdown_links_images_and_names_other_files() {
+        let mut with_media = part(0, "User", Vec::new());
+        with_media.media = vec![
+            ExportedMedia { hash: String::from("abc"), file_name: Some(String::from("cat.png")), media_type: Some(String::from("Image")) },
+            ExportedMedia { hash: String::from("def"), file_name: None, media_type: Some(String::from("Pdf")) },
+        ];
+        let markdown = chat_to_markdown(&chat(None, vec![with_media]), "http://server");
+        assert!(markdown.starts_with("# Untitled Chat 7"));
+        assert!(markdown.contains("![cat.png](http://server/media/abc)"));
+        assert!(markdown.contains("*Attached file : def*"));
+    }
+
+    #[test]
+    fn html_escapes_titles_and_tool_text() {
+        let chat = chat(Some("<b>Title</b>"), vec![
+            part(0, "AI", vec![ExportedSection::ToolOutputs { text: String::from("<script>x</script>") }]),
+        ]);
+        let html = tauri::async_runtime::block_on(chat_to_html(&chat, "http://server"));
+        assert!(html.contains("<title>&lt;b&gt;Title&lt;/b&gt;</title>"));
+        assert!(html.contains("<pre>&lt;script&gt;x&lt;/script&gt;</pre>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<div class=\"part AI\">"));
+    }
+
+    #[test]
+    fn file_names_are_slugs_of_the_title() {
+        let chat = chat(Some("Hello, World!"), Vec::new());
+        assert_eq!(file_name_for(&chat, &ExportFormat::Markdown), "chat_7_hello__world_.md");
+        assert_eq!(file_name_for(&chat, &ExportFormat::Html), "chat_7_hello__world_.html");
+        let long = ExportedChat { title: Some("a".repeat(100)), ..chat };
+        assert_eq!(file_name_for(&long, &ExportFormat::Json), format!("chat_7_{}.json", "a".repeat(40)));
+    }
+
+    #[test]
+    fn existing_files_get_a_numbered_name() {
+        let folder = test_folder("free_path");
+        let path = folder.join("chat.md");
+        assert_eq!(free_path(path.clone()), path);
+        fs::write(&path, "taken").unwrap();
+        assert_eq!(free_path(path.clone()), folder.join("chat_2.md"));
+        fs::write(folder.join("chat_2.md"), "taken").unwrap();
+        assert_eq!(free_path(path), folder.join("chat_3.md"));
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use std::{fs, path::Path};
+
+use chrono::{DateTime, Utc};
+use proxima_frontend_shared::import::{ImportedChat, ImportedMessage, ImportedRole};
+use serde_json::Value;
+
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    let seconds = value.as_f64()?;
+    DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1_000_000_000.0) as u32)
+}
+
+fn role_from_name(name: &str) -> Option<ImportedRole> {
+    match name.trim().to_lowercase().as_str() {
+        "user" | "human" => Some(ImportedRole::User),
+        "assistant" | "gpt" | "model" | "bot" | "chatgpt" => Some(ImportedRole::AI),
+        "system" => Some(ImportedRole::System),
+        "tool" | "function" | "function_call" | "observation" => Some(ImportedRole::Tool),
+        _ => None
+    }
+}
+
+/// ChatGPT stores every branch of a conversation, only the one leading to `current_node` is kept
+fn chatgpt_conversation(conversation: &Value) -> Option<ImportedChat> {
+    let mapping = conversation.get("mapping")?.as_object()?;
+    let mut node_id = conversation.get("current_node").and_then(|node| node.as_str()).map(|node| node.to_string());
+    let mut messages = Vec::with_capacity(mapping.len());
+    while let Some(id) = node_id {
+        let node = match mapping.get(&id) {
+            Some(node) => node,
+            None => break
+        };
+        if let Some(message) = node.get("message").filter(|message| !message.is_null()) {
+            let role = message.get("author").and_then(|author| author.get("role")).and_then(|role| role.as_str()).and_then(role_from_name);
+            let text = message
+                .get("content")
+                .and_then(|content| content.get("parts"))
+                .and_then(|parts| parts.as_array())
+                .map(|parts| parts.iter().filter_map(|part| part.as_str()).collect::<Vec<&str>>().join("\n"))
+                .or(message.get("content").and_then(|content| content.get("text")).and_then(|text| text.as_str()).map(|text| text.to_string()))
+                .unwrap_or_default();
+            let tool_name = message.get("author").and_then(|author| author.get("name")).and_then(|name| name.as_str()).map(|name| name.to_string());
+            if let Some(role) = role {
+                if !text.trim().is_empty() {
+                    messages.push(ImportedMessage { role, text, date: message.get("create_time").and_then(timestamp), tool_name });
+                }
+            }
+        }
+        node_id = node.get("parent").and_then(|parent| parent.as_str()).map(|parent| parent.to_string());
+    }
+    messages.reverse();
+    Some(ImportedChat {
+        title: conversation.get("title").and_then(|title| title.as_str()).map(|title| title.to_string()),
+        created_at: conversation.get("create_time").and_then(timestamp),
+        updated_at: conversation.get("update_time").and_then(timestamp),
+        messages
+    })
+}
+
+fn sharegpt_conversation(conversation: &Value) -> Option<ImportedChat> {
+    let turns = conversation.get("conversations").or(conversation.get("messages"))?.as_array()?;
+    let messages = turns.iter().filter_map(|turn| {
+        let role = turn.get("from").or(turn.get("role"))?.as_str().and_then(role_from_name)?;
+        let text = turn.get("value").or(turn.get("content"))?.as_str()?.to_string();
+        let tool_name = turn.get("name").and_then(|name| name.as_str()).map(|name| name.to_string());
+        Some(ImportedMessage { role, text, date: None, tool_name })
+    }).collect();
+    Some(ImportedChat {
+        title: conversation.get("title").or(conversation.get("id")).and_then(|title| title.as_str()).map(|title| title.to_string()),
+        created_at: None,
+        updated_at: None,
+        messages
+    })
+}
+
+fn conversation_from_value(conversation: &Value) -> Option<ImportedChat> {
+    if conversation.get("mapping").is_some() {
+        chatgpt_conversation(conversation)
+    }
+    else {
+        sharegpt_conversation(conversation)
+    }
+}
+
+/// Reads a ChatGPT `conversations.json`, a ShareGPT JSON array or a ShareGPT JSONL file
+pub fn parse_import(path: &Path) -> Result<Vec<ImportedChat>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("{error}"))?;
+    let conversations = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Array(conversations)) => conversations,
+        Ok(conversation) => vec![conversation],
+        Err(_) => {
+            let mut conversations = Vec::with_capacity(text.lines().count());
+            for (line_number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                conversations.push(serde_json::from_str::<Value>(line).map_err(|error| format!("line {} : {error}", line_number + 1))?);
+            }
+            conversations
+        }
+    };
+    let chats: Vec<ImportedChat> = conversations
+        .iter()
+        .filter_map(conversation_from_value)
+        .filter(|chat| !chat.messages.is_empty())
+        .collect();
+    if chats.is_empty() && !conversations.is_empty() {
+        return Err(String::from("no ChatGPT or ShareGPT conversation found in this file"))
+    }
+    Ok(chats)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("import").join(name)
+    }
+
+    fn texts(chat: &ImportedChat) -> Vec<&str> {
+        chat.messages.iter().map(|message| message.text.as_str()).collect()
+    }
+
+    #[test]
+    fn chatgpt_keeps_the_branch_of_the_current_node() {
+        let chats = parse_import(&fixture("chatgpt_branched.json")).unwrap();
+        assert_eq!(chats.len(), 1);
+        let chat = &chats[0];
+        assert_eq!(chat.title.as_deref(), Some("Branched chat"));
+        // the empty system message and the abandoned answer are left out
+        assert_eq!(texts(chat), vec!["What is 2 + 2 ?", "4", "Four"]);
+        assert_eq!(chat.messages.iter().map(|message| message.role.clone()).collect::<Vec<ImportedRole>>(), vec![ImportedRole::User, ImportedRole::Tool, ImportedRole::AI]);
+        assert_eq!(chat.messages[1].tool_name.as_deref(), Some("python"));
+        assert_eq!(chat.created_at, DateTime::from_timestamp(1700000000, 0));
+        assert_eq!(chat.updated_at, DateTime::from_timestamp(1700000100, 0));
+    }
+
+    #[test]
+    fn chatgpt_messages_without_create_time_have_no_date() {
+        let chat = &parse_import(&fixture("chatgpt_branched.json")).unwrap()[0];
+        assert_eq!(chat.messages[0].date, DateTime::from_timestamp(1700000010, 500_000_000));
+        assert_eq!(chat.messages[2].date, None);
+    }
+
+    #[test]
+    fn sharegpt_json_skips_empty_conversations_and_unknown_roles() {
+        let chats = parse_import(&fixture("sharegpt.json")).unwrap();
+        assert_eq!(chats.len(), 2);
+        assert_eq!(chats[0].title.as_deref(), Some("first"));
+        assert_eq!(texts(&chats[0]), vec!["Hello", "Hi, how can I help ?"]);
+        assert_eq!(chats[1].title, None);
+        assert_eq!(chats[1].messages.iter().map(|message| message.role.clone()).collect::<Vec<ImportedRole>>(), vec![ImportedRole::System, ImportedRole::User, ImportedRole::Tool, ImportedRole::Tool, ImportedRole::AI]);
+        assert_eq!(chats[1].messages[3].tool_name.as_deref(), Some("weather"));
+        assert!(chats.iter().flat_map(|chat| chat.messages.iter()).all(|message| message.date.is_none()));
+    }
+
+    #[test]
+    fn sharegpt_jsonl_reads_one_conversation_per_line() {
+        let chats = parse_import(&fixture("sharegpt.jsonl")).unwrap();
+        assert_eq!(chats.iter().map(|chat| chat.title.as_deref()).collect::<Vec<Option<&str>>>(), vec![Some("one"), Some("two")]);
+        assert_eq!(texts(&chats[1]), vec!["Second"]);
+    }
+
+    #[test]
+    fn bad_jsonl_lines_are_reported_with_their_number() {
+        let error = parse_import(&fixture("bad_line.jsonl")).err().unwrap();
+        assert!(error.starts_with("line 2 : "), "{error}");
+    }
+
+    #[test]
+    fn files_without_conversations_are_refused() {
+        assert!(parse_import(&fixture("not_a_conversation.json")).is_err());
+        assert!(parse_import(&fixture("missing.json")).is_err());
+    }
+}
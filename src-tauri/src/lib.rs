@@ -1,6 +1,11 @@
 #![feature(mpmc_channel)]
 #![feature(string_from_utf8_lossy_owned)]
 
+mod dataset;
+mod export;
+mod import;
+mod local_store;
+
 use std::{
     collections::HashSet, fs::File, io::Read, path::PathBuf, sync::{
         Arc, RwLock, atomic::{AtomicBool, Ordering}, mpmc::{Receiver, Sender}
@@ -12,6 +17,7 @@ use chrono::Utc;
 use futures_util::{StreamExt, TryFutureExt};
 use openai::Credentials;
 use pdfium_render::prelude::Pdfium;
+use proxima_frontend_shared::{dataset::{DatasetConversation, DatasetFormat, DatasetWritten}, import::ImportedChat};
 use proxima_backend::{
     ai_interaction::endpoint_api::{EndpointRequestVariant, EndpointResponseVariant},
     database::{
@@ -23,8 +29,14 @@ use reqwest::Response;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
 use tauri::{DragDropEvent, Emitter, Manager, PhysicalPosition, async_runtime::spawn};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 use tauri_plugin_notification::NotificationExt;
 
+use crate::dataset::write_dataset;
+use crate::export::{ExportFormat, ExportedChat, write_export};
+use crate::import::parse_import;
+use crate::local_store::{load_store, save_store};
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -394,6 +406,111 @@ async fn add_media_from_file_if_exists(state: tauri::State<'_, ProximaState>, te
     Ok((hash, file_name, media_type))
 }
 
+#[tauri::command(async)]
+async fn export_chats(
+    state: tauri::State<'_, ProximaState>,
+    chats: Vec<ExportedChat>,
+    format: ExportFormat,
+    path: PathBuf,
+    url: String,
+) -> Result<usize, String> {
+    println!("[backend] exporting {} chat(s) to {:?}", chats.len(), path);
+    write_export(chats, format, path, url).await.map_err(|error| {
+        println!("[backend] export error : {error}");
+        error
+    })
+}
+
+#[tauri::command(async)]
+async fn export_dataset(
+    state: tauri::State<'_, ProximaState>,
+    conversations: Vec<DatasetConversation>,
+    format: DatasetFormat,
+    path: PathBuf,
+) -> Result<DatasetWritten, String> {
+    println!("[backend] writing dataset of {} conversation(s) to {:?}", conversations.len(), path);
+    write_dataset(conversations, format, path).map_err(|error| {
+        println!("[backend] dataset error : {error}");
+        error
+    })
+}
+
+#[tauri::command(async)]
+async fn parse_chat_import(state: tauri::State<'_, ProximaState>, path: PathBuf) -> Result<Vec<ImportedChat>, String> {
+    println!("[backend] parsing import file {:?}", path);
+    parse_import(&path).map_err(|error| {
+        println!("[backend] import error : {error}");
+        error
+    })
+}
+
+#[tauri::command]
+fn load_local_store(app: tauri::AppHandle, name: String) -> Result<Option<String>, String> {
+    load_store(&app, &name).map_err(|error| {
+        println!("[backend] could not load local store {name} : {error}");
+        error
+    })
+}
+
+#[tauri::command]
+fn save_local_store(app: tauri::AppHandle, name: String, content: String) -> Result<(), String> {
+    save_store(&app, &name, content).map_err(|error| {
+        println!("[backend] could not save local store {name} : {error}");
+        error
+    })
+}
+
+#[tauri::command(async)]
+async fn pick_save_path(app: tauri::AppHandle, name: String, extensions: Vec<String>) -> Result<Option<PathBuf>, String> {
+    let extensions = extensions.iter().map(|extension| extension.as_str()).collect::<Vec<&str>>();
+    let mut dialog = app.dialog().file().set_file_name(name);
+    if !extensions.is_empty() {
+        dialog = dialog.add_filter(extensions.join(", "), &extensions);
+    }
+    // the system dialog already asks before replacing an existing file
+    dialog.blocking_save_file().map(|path| path.into_path().map_err(|error| error.to_string())).transpose()
+}
+
+#[tauri::command(async)]
+async fn pick_file(app: tauri::AppHandle, extensions: Vec<String>) -> Result<Option<PathBuf>, String> {
+    let extensions = extensions.iter().map(|extension| extension.as_str()).collect::<Vec<&str>>();
+    let mut dialog = app.dialog().file();
+    if !extensions.is_empty() {
+        dialog = dialog.add_filter(extensions.join(", "), &extensions);
+    }
+    dialog.blocking_pick_file().map(|path| path.into_path().map_err(|error| error.to_string())).transpose()
+}
+
+#[tauri::command(async)]
+async fn confirm_dialog(app: tauri::AppHandle, title: String, message: String, button: String) -> bool {
+    app.dialog()
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(button, "Cancel".to_string()))
+        .blocking_show()
+}
+
+#[tauri::command(async)]
+async fn pick_folder(app: tauri::AppHandle) -> Result<Option<PathBuf>, String> {
+    app.dialog().file().blocking_pick_folder().map(|path| path.into_path().map_err(|error| error.to_string())).transpose()
+}
+
+#[tauri::command(async)]
+async fn save_text_file(path: PathBuf, content: String) -> Result<(), String> {
+    println!("[backend] saving text file to {:?}", path);
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    std::fs::write(&path, content).map_err(|error| {
+        println!("[backend] could not save text file : {error}");
+        error.to_string()
+    })
+}
+
 #[derive(Serialize, Clone)]
 pub struct SpecialDragDrop {
     paths:Vec<PathBuf>,
@@ -413,6 +530,7 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             app.manage(ProximaState {
                 initialized: AtomicBool::new(false),
@@ -442,7 +560,17 @@ pub fn run() {
             auth_post_request,
             streaming_update_task,
             show_notification,
-            add_media_from_file_if_exists
+            add_media_from_file_if_exists,
+            export_chats,
+            export_dataset,
+            parse_chat_import,
+            load_local_store,
+            save_local_store,
+            pick_save_path,
+            pick_file,
+            pick_folder,
+            confirm_dialog,
+            save_text_file
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
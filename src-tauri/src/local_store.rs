@@ -0,0 +1,33 @@
+use std::{fs, path::PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+/// Frontend-only data the Proxima database has no place for yet is kept as JSON files in the app's local data folder
+fn store_path(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let file_name: String = name
+        .chars()
+        .filter(|character| character.is_alphanumeric() || *character == '_' || *character == '-')
+        .collect();
+    if file_name.is_empty() {
+        return Err(String::from("invalid store name"))
+    }
+    let folder = app.path().app_local_data_dir().map_err(|error| format!("{error}"))?;
+    fs::create_dir_all(&folder).map_err(|error| format!("{error}"))?;
+    Ok(folder.join(format!("{file_name}.json")))
+}
+
+pub fn load_store(app: &AppHandle, name: &str) -> Result<Option<String>, String> {
+    let path = store_path(app, name)?;
+    if !path.exists() {
+        return Ok(None)
+    }
+    fs::read_to_string(path).map(Some).map_err(|error| format!("{error}"))
+}
+
+pub fn save_store(app: &AppHandle, name: &str, content: String) -> Result<(), String> {
+    let path = store_path(app, name)?;
+    // written next to the target first so a crash never leaves half a file behind
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, content).map_err(|error| format!("{error}"))?;
+    fs::rename(temporary, path).map_err(|error| format!("{error}"))
+}
@@ -1,4 +1,4 @@
-use std::{collections::{HashMap, HashSet}, path::PathBuf, thread, time::Duration, u64};
+use std::{collections::HashSet, ops::Range, path::PathBuf, rc::Rc, thread, time::Duration, u64};
 
 use chrono::{DateTime, TimeDelta, Utc};
 use gloo_events::EventListener;
@@ -13,14 +13,18 @@ use yew::prelude::*;
 use selectrs::yew::{Select, Group};
 use markdown::to_html;
 use web_sys::{EventTarget, HtmlElement};
-use futures::StreamExt;
+use futures::{StreamExt, future::Either};
+use yew::platform::time::sleep;
 
-use crate::{alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction, AlertsShow}, db_sync::{UserCursors, apply_server_updates, get_delta_for_add, get_next_id_for_category, handle_add_reducible}, tabs::{access_modes_tab::AccessModesTab, chat_configs_tab::ChatConfigsTab, chat_tab::ChatTab, home_tab::HomeTab, notification_tab::{NotificationTab, generate_title_and_desc_for}, tags_tab::TagsTab}};
+use crate::{alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction, AlertsShow}, chat_store::ChatStore, compare::{CompareSession, Promotion, promote_answer}, compaction::{COMPACTION_STORE, CompactionSettings}, drafts::{DRAFTS_STORE, Draft, Drafts}, local_store::{Stored, load_local, save_local, save_stored}, html_parsing::ParsedHtml, live_responses::{LiveResponse, ShownPart}, prompt_queue::{PromptQueues, PromptSettings, send_queued_prompt}, context_parts::part_key, search::SearchIndex, stream_metrics::{METRICS_STORE, MetricsStore, ResponseMetrics, TagTimes}, titles::{TITLES_STORE, TitleSettings}, tokenizer::{TOKENIZERS_STORE, TokenizerKind, TokenizerSettings}, token_reorder::ChatTokenBuffers, db_sync::{UserCursors, apply_server_updates, get_delta_for_add, get_next_id_for_category, handle_add_reducible}, tabs::{access_modes_tab::AccessModesTab, chat_configs_tab::ChatConfigsTab, chat_tab::ChatTab, compare_tab::CompareTab, home_tab::HomeTab, memory_tab::MemoryTab, notification_tab::{NotificationTab, generate_title_and_desc_for}, search_tab::SearchTab, tags_tab::TagsTab}};
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
     pub async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+    /// Same as invoke, for commands returning a Result, whose Err rejects the promise
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke, catch)]
+    pub async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
 }
 
 #[derive(Serialize, Deserialize)]
@@ -205,6 +209,36 @@ pub async fn make_db_request(payload:DBPayload, backend_url:String) -> Result<DB
     value.map_err(|error| {})
 }
 
+pub async fn fetch_chat(proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID) -> Option<Chat> {
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Get(DatabaseItemID::Chat(chat_id)) };
+    match make_db_request(json_request, proxima_state.chat_url.clone()).await {
+        Ok(DBResponse { reply:DatabaseReplyVariant::ReturnedItem(DatabaseItem::Chat(chat)) }) => Some(chat),
+        _ => None
+    }
+}
+
+/// Chats still generating are left alone as their parts are being written, see save_chat_change, and templates
+/// only change through the template panel
+pub async fn update_chat<F:FnOnce(&mut Chat)>(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID, change:F) -> Result<Chat, String> {
+    if db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats) {
+        return Err("the chat is still generating".to_string())
+    }
+    if db_state.chats.template(chat_id).is_some() {
+        return Err("templates are edited in the template panel".to_string())
+    }
+    save_chat_change(db_state, proxima_state, chat_id, change).await
+}
+
+/// The server only takes whole chats, so the chat is fetched right before the change to keep what happened to it
+/// meanwhile, only for the steps of sending a prompt, the rest goes through update_chat
+pub async fn save_chat_change<F:FnOnce(&mut Chat)>(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID, change:F) -> Result<Chat, String> {
+    let mut chat = fetch_chat(proxima_state, chat_id).await.ok_or("could not get the chat from the server".to_string())?;
+    change(&mut chat);
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Update(DatabaseItem::Chat(chat.clone())) };
+    make_db_request(json_request, proxima_state.chat_url.clone()).await.map_err(|_| {"could not save the chat".to_string()})?;
+    db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat.clone()))]));
+    Ok(chat)
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct SecondArgument {
@@ -237,20 +271,35 @@ pub fn loading_page() -> Html {
     }
 }
 
+/// Every field is shared with the previous state, an action only copies what it changes
 #[derive(PartialEq)]
 pub struct DatabaseState {
-    pub db:ProxDatabase,
-    pub cursors:UserCursors,
+    /// Everything but the chats, copied only by actions changing those items
+    pub db:Rc<ProxDatabase>,
+    /// Saved chats, a change copies only the chat it is about and streamed tokens go to the live responses
+    pub chats:ChatStore,
+    pub cursors:Rc<UserCursors>,
     pub update_flipper:bool,
-    pub token_streams:HashMap<ChatID, StreamingData>,
-    pub received_updates:HashSet<u64>,
-    pub ongoing_chats:HashSet<ChatID>
+    pub token_streams:im_rc::HashMap<ChatID, StreamingData>,
+    pub live_responses:im_rc::HashMap<ChatID, LiveResponse>,
+    pub received_updates:im_rc::HashSet<u64>,
+    pub ongoing_chats:im_rc::HashSet<ChatID>,
+    pub search_index:SearchIndex,
+    pub title_settings:Rc<Stored<TitleSettings>>,
+    pub tokenizer_settings:Rc<Stored<TokenizerSettings>>,
+    pub compaction_settings:Rc<Stored<CompactionSettings>>,
+    /// When each ongoing request started, for which configuration and with which tokenizer to count its tokens
+    pub request_starts:im_rc::HashMap<ChatID, (DateTime<Utc>, String, TokenizerKind)>,
+    pub stream_metrics:Rc<Stored<MetricsStore>>,
+    pub prompt_queues:Rc<PromptQueues>,
+    pub drafts:Rc<Stored<Drafts>>,
+    pub compare:Rc<CompareSession>
 }
 
 #[derive(Clone, PartialEq)]
 struct StreamingData {
-    token_ids:Vec<(u64, DateTime<Utc>)>,
-    all_ids:HashSet<u64>,
+    token_ids:im_rc::Vector<(u64, DateTime<Utc>)>,
+    all_ids:im_rc::HashSet<u64>,
     last_update:DateTime<Utc>,
     last_part_pos:ContextPosition
 }
@@ -258,12 +307,23 @@ struct StreamingData {
 impl Default for DatabaseState {
     fn default() -> Self {
         Self {
-            db:ProxDatabase::new_just_data(String::from("a"), String::from("a")),
-            cursors:UserCursors::zero(),
+            db:Rc::new(ProxDatabase::new_just_data(String::from("a"), String::from("a"))),
+            chats:ChatStore::default(),
+            cursors:Rc::new(UserCursors::zero()),
             update_flipper:false,
-            token_streams:HashMap::with_capacity(16),
-            received_updates:HashSet::with_capacity(128),
-            ongoing_chats:HashSet::with_capacity(16),
+            token_streams:im_rc::HashMap::new(),
+            live_responses:im_rc::HashMap::new(),
+            received_updates:im_rc::HashSet::new(),
+            ongoing_chats:im_rc::HashSet::new(),
+            search_index:SearchIndex::default(),
+            title_settings:Rc::new(Stored::default()),
+            tokenizer_settings:Rc::new(Stored::default()),
+            compaction_settings:Rc::new(Stored::default()),
+            request_starts:im_rc::HashMap::new(),
+            stream_metrics:Rc::new(Stored::default()),
+            prompt_queues:Rc::new(PromptQueues::default()),
+            drafts:Rc::new(Stored::default()),
+            compare:Rc::new(CompareSession::default()),
         }
     }
 }
@@ -302,9 +362,128 @@ pub enum DatabaseAction {
     },
     AddToOngoingChats {
         chat:ChatID,
+        config:String,
+        tokenizer:TokenizerKind
     },
     RemoveFromOngoingChats {
         chat:ChatID
+    },
+    SetFocusedPart(Option<usize>),
+    IndexMediaText {
+        hash:String,
+        text:String,
+        location:Option<(ChatID, usize)>
+    },
+    SkipMediaIndexing(String),
+    /// Drops the metrics of parts the chat lost
+    PrunePartData(ChatID),
+    LoadTitleSettings(TitleSettings),
+    SetTitleLock {
+        chat:ChatID,
+        locked:bool
+    },
+    SetAutoTitle {
+        auto:bool,
+        config:Option<usize>
+    },
+    LoadTokenizerSettings(TokenizerSettings),
+    SetTokenizer {
+        config:usize,
+        kind:TokenizerKind
+    },
+    LoadCompactionSettings(CompactionSettings),
+    SetAutoCompaction(bool),
+    /// Percent of the max context length a chat can use before it is compacted
+    SetCompactionThreshold(usize),
+    /// Parts left as is by compaction
+    SetCompactionKeepRecent(usize),
+    LoadStreamMetrics(MetricsStore),
+    QueuePrompt {
+        chat:ChatID,
+        text:String,
+        settings:PromptSettings,
+        files:Vec<PathBuf>
+    },
+    EditQueuedPrompt {
+        chat:ChatID,
+        id:u64,
+        text:String
+    },
+    RemoveQueuedPrompt {
+        chat:ChatID,
+        id:u64
+    },
+    TakeQueuedPrompt(ChatID),
+    StartSending(ChatID),
+    FinishSending(ChatID),
+    LoadDrafts(Drafts),
+    /// The draft of a chat, or of the next new chat, an empty draft removes it
+    SetDraft {
+        chat:Option<ChatID>,
+        draft:Draft
+    },
+    SetFocusedMemory(Option<u64>),
+    SetCompare(CompareSession),
+    SetCompareChat {
+        config:usize,
+        chat:ChatID
+    },
+    /// Refused when one of its chats is busy in the current state, not only when the button was shown
+    PromoteAnswer(Promotion),
+    FinishPromotion
+}
+
+impl DatabaseState {
+    /// Metrics of the response being streamed in this chat, up to now
+    pub fn live_metrics(&self, chat_id:ChatID) -> Option<ResponseMetrics> {
+        let (started_at, config, _) = self.request_starts.get(&chat_id)?;
+        let token_times = self.token_streams.get(&chat_id).map(|stream| {stream.token_ids.iter().map(|(_, time)| {*time}).collect::<Vec<DateTime<Utc>>>()}).unwrap_or_default();
+        let tokens = self.live_responses.get(&chat_id).map(|live| {live.tokens()}).unwrap_or(0);
+        Some(ResponseMetrics::from_token_times(config.clone(), *started_at, &token_times, tokens, Utc::now()))
+    }
+    /// When the tags of a part arrived, while it streams or once it was saved with the metrics
+    pub fn part_tag_times(&self, chat_id:ChatID, part_index:usize, part:&ContextPart) -> Option<TagTimes> {
+        if let Some(live) = self.live_responses.get(&chat_id) && let Some(saved_parts) = self.chats.get(chat_id).map(|chat| {chat.context.get_parts().len()}) {
+            let first_part = live.first_part_index(saved_parts);
+            if part_index >= first_part {
+                return live.tag_times().nth(part_index - first_part).cloned()
+            }
+        }
+        self.stream_metrics.get_tag_times(chat_id, &part_key(part)).cloned()
+    }
+    pub fn part_count(&self, chat_id:ChatID) -> Option<usize> {
+        let parts = self.chats.get(chat_id)?.context.get_parts().len();
+        Some(parts + self.live_responses.get(&chat_id).map(|live| {live.added_parts()}).unwrap_or(0))
+    }
+    /// A chat as saved, without what is being streamed into it
+    pub fn chat(&self, chat_id:ChatID) -> Option<&Chat> {
+        self.chats.get(chat_id).map(|chat| {chat.as_ref()})
+    }
+    /// A copy of a chat with what was streamed into it, for actions working on the whole chat
+    pub fn chat_with_live(&self, chat_id:ChatID) -> Option<Chat> {
+        let mut chat = self.chat(chat_id)?.clone();
+        if let Some(live) = self.live_responses.get(&chat_id) {
+            live.apply_to(&mut chat);
+        }
+        Some(chat)
+    }
+    /// A part of a chat as shown, only the parts being streamed are built
+    pub fn part(&self, chat_id:ChatID, index:usize) -> Option<ShownPart> {
+        let chat = self.chats.get(chat_id)?;
+        let saved = chat.context.get_parts().len();
+        match self.live_responses.get(&chat_id) {
+            Some(live) if index >= live.first_part_index(saved) => live.part(index - live.first_part_index(saved)).map(|part| {ShownPart::Live(Rc::new(part))}),
+            _ => (index < saved).then(|| {ShownPart::Saved(chat.clone(), index)})
+        }
+    }
+    /// Tags of a part being streamed, as parsed while it arrived
+    pub fn live_parsed(&self, chat_id:ChatID, index:usize) -> Option<ParsedHtml> {
+        let saved = self.chat(chat_id)?.context.get_parts().len();
+        let live = self.live_responses.get(&chat_id)?;
+        index.checked_sub(live.first_part_index(saved)).and_then(|index| {live.parsed(index)})
+    }
+    pub fn shown_parts(&self, chat_id:ChatID, range:Range<usize>) -> Vec<(usize, ShownPart)> {
+        range.filter_map(|index| {self.part(chat_id, index).map(|part| {(index, part)})}).collect()
     }
 }
 
@@ -312,14 +491,25 @@ impl Reducible for DatabaseState {
     type Action = DatabaseAction;
     fn reduce(self: std::rc::Rc<Self>, action: Self::Action) -> std::rc::Rc<Self> {
         let mut database = self.db.clone();
+        let mut chats = self.chats.clone();
         let mut cursors = self.cursors.clone();
         let mut update_flipper = self.update_flipper;
         let mut token_streams = self.token_streams.clone();
+        let mut live_responses = self.live_responses.clone();
         let mut received_updates = self.received_updates.clone();
         let mut ongoing_chats = self.ongoing_chats.clone();
+        let mut search_index = self.search_index.clone();
+        let mut title_settings = self.title_settings.clone();
+        let mut tokenizer_settings = self.tokenizer_settings.clone();
+        let mut compaction_settings = self.compaction_settings.clone();
+        let mut request_starts = self.request_starts.clone();
+        let mut stream_metrics = self.stream_metrics.clone();
+        let mut prompt_queues = self.prompt_queues.clone();
+        let mut drafts = self.drafts.clone();
+        let mut compare = self.compare.clone();
         let now = Utc::now();
         let mut to_remove = Vec::with_capacity(2);
-        for (chat_id, stream) in &mut token_streams {
+        for (chat_id, stream) in &token_streams {
             if stream.last_update.signed_duration_since(now).abs() > TimeDelta::minutes(3) {
                 to_remove.push(*chat_id);
             }
@@ -328,37 +518,63 @@ impl Reducible for DatabaseState {
             token_streams.remove(&rem);
         }
         match action {
-            DatabaseAction::SetDB(db) => database = db,
-            DatabaseAction::ApplyUpdates(updates) => {cursors = apply_server_updates(&mut database, updates, cursors);},
+            DatabaseAction::SetDB(mut db) => {
+                chats = ChatStore::take_from(&mut db);
+                search_index = SearchIndex::from_database(&db, &chats);
+                live_responses.clear();
+                database = Rc::new(db);
+            },
+            DatabaseAction::ApplyUpdates(updates) => {
+                for (id, item) in &updates {
+                    search_index.apply_item(id, item);
+                    drop_live_response(&mut live_responses, id);
+                }
+                cursors = Rc::new(apply_server_updates(&mut database, &mut chats, updates, (*cursors).clone()));
+            },
             DatabaseAction::AddItem(delta, remote_id, item) => {
+                for (id, delta_item) in &delta {
+                    search_index.apply_item(id, delta_item);
+                }
+                search_index.apply_item(&remote_id, &item);
                 let local_id = get_next_id_for_category(&database, &item);
+                for (id, _) in &delta {
+                    drop_live_response(&mut live_responses, id);
+                }
+                drop_live_response(&mut live_responses, &remote_id);
                 // idea : make the add action have 2 parts :
                 // make the add request in an async scope, and get everything from the local id to the given id in an array
                 // send the array of items between local and remote id as well as the new item as an action
                 // rewrite handle_add_reducible to handle that gracefully
                 let new_cursors = handle_add_reducible(
                     &mut database,
+                    &mut chats,
                     local_id,
                     remote_id,
                     item,
-                    cursors.clone(),
+                    (*cursors).clone(),
                     delta
                 );
-                cursors = new_cursors;
+                cursors = Rc::new(new_cursors);
             },
             DatabaseAction::RemoveItem(rem) => {
-                database.remove_request(rem);
+                search_index.remove_item(&rem);
+                drop_live_response(&mut live_responses, &rem);
+                remove_item(&mut database, &mut chats, &mut stream_metrics, rem);
             }
             DatabaseAction::ApplyClientUpdate { update, event_id } => {
-                if received_updates.insert(event_id) {
+                if received_updates.insert(event_id).is_none() {
                     match update {
                         ClientUpdate::ItemRemoval(rem) => {
-                            mark_updated(&mut cursors, rem.clone());
-                            database.remove_request(rem);
+                            mark_updated(Rc::make_mut(&mut cursors), rem.clone());
+                            search_index.remove_item(&rem);
+                            drop_live_response(&mut live_responses, &rem);
+                            remove_item(&mut database, &mut chats, &mut stream_metrics, rem);
                         },
                         ClientUpdate::ItemUpdate(item_id, item) => {
-                            mark_updated(&mut cursors, item_id.clone());
-                            cursors = apply_server_updates(&mut database, vec![(item_id, item)], cursors);
+                            mark_updated(Rc::make_mut(&mut cursors), item_id.clone());
+                            search_index.apply_item(&item_id, &item);
+                            drop_live_response(&mut live_responses, &item_id);
+                            cursors = Rc::new(apply_server_updates(&mut database, &mut chats, vec![(item_id, item)], (*cursors).clone()));
                         }
                     }
                     if received_updates.len() > 60 {
@@ -372,35 +588,101 @@ impl Reducible for DatabaseState {
                     }
                 }
             }
-            DatabaseAction::SetChat(chat) => cursors.chosen_chat = chat,
-            DatabaseAction::SetTab(tab) => {cursors.chosen_tab = tab; cursors.anything_new_for[tab] = false;},
-            DatabaseAction::SetGlobalAM(am) => cursors.chosen_access_mode = am,
-            DatabaseAction::SetModifiedAM(am) => cursors.access_mode_for_modification = am,
-            DatabaseAction::SetModifiedTag(tag) => cursors.chosen_tag = tag,
-            DatabaseAction::SetParentTag(par) => cursors.chosen_parent_tag = par,
-            DatabaseAction::ChangeUsedChatConfig(config) => cursors.chosen_config = config,
-            DatabaseAction::AddToTagsForAM(tag) => {cursors.chosen_access_mode_tags.insert(tag);},
-            DatabaseAction::RemoveFromTagsForAM(tag) => {cursors.chosen_access_mode_tags.remove(&tag);},
-            DatabaseAction::SetTagsForAM(tags) => cursors.chosen_access_mode_tags = tags,
-            DatabaseAction::SetConfigSettingID(id) => cursors.chosen_setting = id,
-            DatabaseAction::SetCurrentSetting(setting) => cursors.setting_for_modification = setting,
-            DatabaseAction::SetModifiedConfig(config) => cursors.config_for_modification = config,
+            // templates are stored as chats but are not opened as one, so nothing is sent to them
+            DatabaseAction::SetChat(Some(chat)) if chats.template(chat).is_some() => (),
+            DatabaseAction::SetChat(chat) => {
+                let cursors = Rc::make_mut(&mut cursors);
+                cursors.chosen_chat = chat;
+                cursors.focused_part = None;
+            },
+            DatabaseAction::SetFocusedPart(part) => Rc::make_mut(&mut cursors).focused_part = part,
+            DatabaseAction::IndexMediaText { hash, text, location } => {
+                if let Some(media) = database.media.get_media(&hash) {
+                    search_index.index_media_text(media, text, location);
+                }
+            },
+            DatabaseAction::SkipMediaIndexing(hash) => search_index.skip_media(&hash),
+            DatabaseAction::PrunePartData(chat) => prune_part_data(&mut stream_metrics, &chats, chat),
+            DatabaseAction::LoadTitleSettings(saved) => Rc::make_mut(&mut title_settings).load(saved),
+            DatabaseAction::SetTitleLock { chat, locked } => {
+                let settings = Rc::make_mut(&mut title_settings);
+                if locked {
+                    settings.locked_chats.insert(chat);
+                }
+                else {
+                    settings.locked_chats.remove(&chat);
+                }
+            },
+            DatabaseAction::SetAutoTitle { auto, config } => {
+                let settings = Rc::make_mut(&mut title_settings);
+                settings.auto_title = auto;
+                settings.titling_config = config;
+            },
+            DatabaseAction::LoadTokenizerSettings(saved) => Rc::make_mut(&mut tokenizer_settings).load(saved),
+            DatabaseAction::SetTokenizer { config, kind } => {Rc::make_mut(&mut tokenizer_settings).per_config.insert(config, kind);},
+            DatabaseAction::LoadCompactionSettings(saved) => Rc::make_mut(&mut compaction_settings).load(saved),
+            DatabaseAction::SetAutoCompaction(auto) => Rc::make_mut(&mut compaction_settings).auto = auto,
+            DatabaseAction::SetCompactionThreshold(threshold) => Rc::make_mut(&mut compaction_settings).threshold_percent = threshold,
+            DatabaseAction::SetCompactionKeepRecent(keep) => Rc::make_mut(&mut compaction_settings).keep_recent = keep,
+            DatabaseAction::LoadStreamMetrics(saved) => Rc::make_mut(&mut stream_metrics).load(saved),
+            DatabaseAction::QueuePrompt { chat, text, settings, files } => Rc::make_mut(&mut prompt_queues).push(chat, text, settings, files),
+            DatabaseAction::EditQueuedPrompt { chat, id, text } => Rc::make_mut(&mut prompt_queues).edit(chat, id, text),
+            DatabaseAction::RemoveQueuedPrompt { chat, id } => Rc::make_mut(&mut prompt_queues).remove(chat, id),
+            DatabaseAction::TakeQueuedPrompt(chat) => {Rc::make_mut(&mut prompt_queues).take_next(chat);},
+            DatabaseAction::StartSending(chat) => Rc::make_mut(&mut prompt_queues).start_sending(chat),
+            DatabaseAction::FinishSending(chat) => Rc::make_mut(&mut prompt_queues).finish_sending(chat),
+            DatabaseAction::LoadDrafts(saved) => Rc::make_mut(&mut drafts).load(saved),
+            DatabaseAction::SetDraft { chat, draft } => if drafts.get(chat) != draft {
+                Rc::make_mut(&mut drafts).set(chat, draft);
+            },
+            // a promotion going on stays until it is finished, so that its chats do not stay busy
+            DatabaseAction::SetCompare(session) => compare = Rc::new(CompareSession { promoting: compare.promoting.clone(), ..session }),
+            DatabaseAction::SetCompareChat { config, chat } => Rc::make_mut(&mut compare).set_chat(config, chat),
+            DatabaseAction::PromoteAnswer(promotion) => if compare.can_promote(&promotion, |chat_id| {prompt_queues.is_busy(chat_id, &ongoing_chats)}) {
+                let queues = Rc::make_mut(&mut prompt_queues);
+                for chat_id in promotion.chats() {
+                    queues.start_sending(chat_id);
+                }
+                Rc::make_mut(&mut compare).promoting = Some(promotion);
+            },
+            DatabaseAction::FinishPromotion => if let Some(promotion) = Rc::make_mut(&mut compare).promoting.take() {
+                let queues = Rc::make_mut(&mut prompt_queues);
+                for chat_id in promotion.chats() {
+                    queues.finish_sending(chat_id);
+                }
+            },
+            DatabaseAction::SetFocusedMemory(memory) => Rc::make_mut(&mut cursors).focused_memory = memory,
+            DatabaseAction::SetTab(tab) => {let cursors = Rc::make_mut(&mut cursors); cursors.chosen_tab = tab; cursors.anything_new_for[tab] = false;},
+            DatabaseAction::SetGlobalAM(am) => Rc::make_mut(&mut cursors).chosen_access_mode = am,
+            DatabaseAction::SetModifiedAM(am) => Rc::make_mut(&mut cursors).access_mode_for_modification = am,
+            DatabaseAction::SetModifiedTag(tag) => Rc::make_mut(&mut cursors).chosen_tag = tag,
+            DatabaseAction::SetParentTag(par) => Rc::make_mut(&mut cursors).chosen_parent_tag = par,
+            DatabaseAction::ChangeUsedChatConfig(config) => Rc::make_mut(&mut cursors).chosen_config = config,
+            DatabaseAction::AddToTagsForAM(tag) => {Rc::make_mut(&mut cursors).chosen_access_mode_tags.insert(tag);},
+            DatabaseAction::RemoveFromTagsForAM(tag) => {Rc::make_mut(&mut cursors).chosen_access_mode_tags.remove(&tag);},
+            DatabaseAction::SetTagsForAM(tags) => Rc::make_mut(&mut cursors).chosen_access_mode_tags = tags,
+            DatabaseAction::SetConfigSettingID(id) => Rc::make_mut(&mut cursors).chosen_setting = id,
+            DatabaseAction::SetCurrentSetting(setting) => Rc::make_mut(&mut cursors).setting_for_modification = setting,
+            DatabaseAction::SetModifiedConfig(config) => Rc::make_mut(&mut cursors).config_for_modification = config,
             DatabaseAction::AddPartToChat { chat_id, token_id, part } => {
                 update_flipper = !update_flipper;
-                database.chats.get_chats_mut().get_mut(&chat_id).map(|chat| {
+                let tokenizer = request_starts.get(&chat_id).map(|(_, _, tokenizer)| {*tokenizer}).unwrap_or_default();
+                if chats.contains(chat_id) {
                     match token_streams.get_mut(&chat_id) {
                         Some(stream) => {
-                            if now.signed_duration_since(stream.last_update).abs() > TimeDelta::seconds(1) || stream.last_part_pos != part.get_position().clone() {
-                                token_streams.insert(chat_id, StreamingData { token_ids: vec![(token_id, Utc::now())], all_ids:HashSet::from([token_id]), last_update:now, last_part_pos:part.get_position().clone() });
-                                chat.context.add_part(part);
+                            if stream.all_ids.insert(token_id).is_none() {
+                                stream.last_update = now;
+                                stream.token_ids.push_back((token_id, now));
+                                stream.last_part_pos = part.get_position().clone();
+                                live_responses.entry(chat_id).or_insert_with(|| {LiveResponse::new(tokenizer)}).start_part(part, now);
                             }
                         },
                         None => {
-                            token_streams.insert(chat_id, StreamingData { token_ids: vec![(token_id, Utc::now())], all_ids:HashSet::from([token_id]), last_update:Utc::now(), last_part_pos:part.get_position().clone() });
-                            chat.context.add_part(part);
+                            token_streams.insert(chat_id, StreamingData { token_ids: im_rc::Vector::unit((token_id, Utc::now())), all_ids:im_rc::HashSet::unit(token_id), last_update:Utc::now(), last_part_pos:part.get_position().clone() });
+                            live_responses.entry(chat_id).or_insert_with(|| {LiveResponse::new(tokenizer)}).start_part(part, now);
                         }
                     }
-                });
+                }
             },
             DatabaseAction::AddDataToLastPartOfChat {
                 chat_id,
@@ -409,37 +691,143 @@ impl Reducible for DatabaseState {
             } => {
 
                 update_flipper = !update_flipper;
-                database.chats.get_chats_mut().get_mut(&chat_id).map(|chat| {
+                let tokenizer = request_starts.get(&chat_id).map(|(_, _, tokenizer)| {*tokenizer}).unwrap_or_default();
+                if let Some(chat) = chats.get(chat_id) {
 
                     match token_streams.get_mut(&chat_id) {
                         Some(stream) => {
                             if !stream.all_ids.contains(&token_id) {
-                                let last_part = chat.context.get_parts_mut().last_mut().unwrap();
-                                match last_part.get_data_mut().last_mut().unwrap() {
-                                    ContextData::Text(text) => {
-                                        match data {
-                                            ContextData::Text(new_text) => *text += &new_text,
-                                            ContextData::Media(image) => last_part.add_data(ContextData::Media(image)),
-                                        }
-                                    },
-                                    ContextData::Media(image) => {
-                                        last_part.add_data(data);
-                                    }
+                                let added = match live_responses.get_mut(&chat_id) {
+                                    Some(live) => live.add_data(data.clone(), now),
+                                    None => false
+                                };
+                                // the chat was updated while responding, the tokens go on after its last part
+                                if !added && let Some(mut live) = LiveResponse::continuing(chat, tokenizer) {
+                                    live.add_data(data, now);
+                                    live_responses.insert(chat_id, live);
                                 }
                                 stream.all_ids.insert(token_id);
                                 stream.last_update = Utc::now();
-                                stream.token_ids.push((token_id, stream.last_update.clone()));
+                                stream.token_ids.push_back((token_id, stream.last_update.clone()));
                             }
                         },
                         None => ()
                     }
-                });
+                }
             },
-            DatabaseAction::AddToOngoingChats { chat } => {ongoing_chats.insert(chat);},
-            DatabaseAction::RemoveFromOngoingChats { chat } => {ongoing_chats.remove(&chat);}
+            DatabaseAction::AddToOngoingChats { chat, config, tokenizer } => {
+                // token ids start back at 0 for every new request
+                token_streams.remove(&chat);
+                ongoing_chats.insert(chat);
+                request_starts.insert(chat, (now, config, tokenizer));
+            },
+            DatabaseAction::RemoveFromOngoingChats { chat } => {
+                ongoing_chats.remove(&chat);
+                // the finished response joins the chat, the only copy of it made for the response
+                let live = live_responses.remove(&chat);
+                if let Some(live) = &live && let Some(finished_chat) = chats.get_mut(chat) {
+                    let first_part = live.first_part_index(finished_chat.context.get_parts().len());
+                    live.apply_to(finished_chat);
+                    let metrics = Rc::make_mut(&mut stream_metrics);
+                    for (part, times) in finished_chat.context.get_parts()[first_part..].iter().zip(live.tag_times()) {
+                        metrics.record_tag_times(chat, part_key(part), times.clone());
+                    }
+                }
+                // templates are never answered, nothing is measured for them
+                if let Some((started_at, config, _)) = request_starts.remove(&chat) && chats.contains(chat) {
+                    let token_times = token_streams.get(&chat).map(|stream| {stream.token_ids.iter().map(|(_, time)| {*time}).collect::<Vec<DateTime<Utc>>>()}).unwrap_or_default();
+                    let tokens = live.as_ref().map(|live| {live.tokens()}).unwrap_or(0);
+                    // only streamed responses are already in the chat, blocks arrive after this
+                    let part = if token_times.is_empty() {
+                        None
+                    }
+                    else {
+                        chats.get(chat).and_then(|finished_chat| {finished_chat.context.get_parts().last()}).map(part_key)
+                    };
+                    Rc::make_mut(&mut stream_metrics).record(chat, part, ResponseMetrics::from_token_times(config, started_at, &token_times, tokens, now));
+                }
+                // streamed tokens are not indexed as they arrive, the finished response is
+                if let Some(finished_chat) = chats.get(chat) {
+                    search_index.index_chat(finished_chat);
+                }
+            }
+
+        }
+        DatabaseState{db:database, chats, cursors, update_flipper, token_streams, live_responses, received_updates, ongoing_chats, search_index, title_settings, tokenizer_settings, compaction_settings, request_starts, stream_metrics, prompt_queues, drafts, compare}.into()
+    }
+}
+
+/// Chats leave the chat store, the database is only copied for the other items
+fn remove_item(database:&mut Rc<ProxDatabase>, chats:&mut ChatStore, metrics:&mut Rc<Stored<MetricsStore>>, rem:DatabaseItemID) {
+    match rem {
+        DatabaseItemID::Chat(chat) => {
+            chats.remove(chat);
+            prune_part_data(metrics, chats, chat);
+        },
+        _ => {Rc::make_mut(database).remove_request(rem);}
+    }
+}
+
+/// Only copies the metrics when the chat has some to drop, ratings are in the chat itself
+fn prune_part_data(metrics:&mut Rc<Stored<MetricsStore>>, chats:&ChatStore, chat_id:ChatID) {
+    let chat = chats.get(chat_id).map(|chat| {chat.as_ref()});
+    if metrics.has_chat(chat_id) {
+        Rc::make_mut(metrics).prune_chat(chat_id, chat);
+    }
+}
+
+async fn apply_stream_event(db_state:&UseReducerHandle<DatabaseState>, alert_state:&UseReducerHandle<Alerts>, chat_id:ChatID, token_id:u64, event:EndpointResponseVariant) -> bool {
+    match event {
+        EndpointResponseVariant::StartStream(data, position) => {
+
+            print("It's a start stream event").await;
+            db_state.dispatch(DatabaseAction::AddPartToChat{
+                chat_id,
+                token_id,
+                part:ContextPart::new(vec![data], position)
+            });
+        },
+        EndpointResponseVariant::ContinueStream(data, position) => {
+            print("It's a continue stream event").await;
+            db_state.dispatch(DatabaseAction::AddDataToLastPartOfChat {
+                chat_id,
+                token_id,
+                data
+            });
+        },
+        EndpointResponseVariant::EndStream(data, position) => {
+            print("It's a end stream event").await;
+            return true
+        },
+        EndpointResponseVariant::EndpointError(error) => {
+            print("Got backend error").await;
+            alert_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::AIEndpoint, format!("AI Endpoint unreachable"))));
+        },
+        _ => {
+            print("It's an impossible stream event").await;
+        }
+    }
+    false
+}
+
+pub async fn resync_chat(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID) {
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Get(DatabaseItemID::Chat(chat_id)) };
+    match make_db_request(json_request, proxima_state.chat_url.clone()).await {
+        Ok(DBResponse { reply:DatabaseReplyVariant::ReturnedItem(DatabaseItem::Chat(chat)) }) => {
+            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat))]));
+        },
+        _ => ()
+    }
+}
 
+/// Applies what waited behind gaps that were not filled in time, then gets the server's copy of those chats
+async fn skip_timed_out_gaps(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alert_state:&UseReducerHandle<Alerts>, token_buffers:&mut ChatTokenBuffers) {
+    for (chat_id, events) in token_buffers.skip_timed_out_gaps(Utc::now()) {
+        print(format!("Gap in token stream for chat {chat_id} timed out, asking for a resync")).await;
+        for (token_id, event) in events {
+            apply_stream_event(db_state, alert_state, chat_id, token_id, event).await;
         }
-        DatabaseState{db:database, cursors, update_flipper, token_streams, received_updates, ongoing_chats}.into()
+        resync_chat(db_state, proxima_state, chat_id).await;
     }
 }
 
@@ -448,6 +836,13 @@ pub async fn print<T:ToString>(print:T) {
     invoke("print_to_console", args).await;
 }
 
+/// The server's copy of a chat replaces what was streamed into it
+fn drop_live_response(live_responses:&mut im_rc::HashMap<ChatID, LiveResponse>, id:&DatabaseItemID) {
+    if let DatabaseItemID::Chat(chat_id) = id {
+        live_responses.remove(chat_id);
+    }
+}
+
 fn mark_updated(cursors:&mut UserCursors, db_id:DatabaseItemID) {
     let index = match db_id {
         DatabaseItemID::Chat(_) => 1,
@@ -457,6 +852,7 @@ fn mark_updated(cursors:&mut UserCursors, db_id:DatabaseItemID) {
         DatabaseItemID::AccessMode(_) => 3,
         DatabaseItemID::ChatConfiguration(_) => 5,
         DatabaseItemID::Notification(_) => 6,
+        DatabaseItemID::Memory(_) => 8,
         _ => 100
     };
     if index < 100 && cursors.chosen_tab != index {
@@ -483,6 +879,14 @@ pub fn app_page() -> Html {
                 if db_clone_is_ok {
                     db_state.dispatch(DatabaseAction::SetDB(proxima_state.start_db.clone().unwrap()));
                     got_start.set(true);
+                    let db_state = db_state.clone();
+                    spawn_local(async move {
+                        db_state.dispatch(DatabaseAction::LoadTitleSettings(load_local(TITLES_STORE).await));
+                        db_state.dispatch(DatabaseAction::LoadTokenizerSettings(load_local(TOKENIZERS_STORE).await));
+                        db_state.dispatch(DatabaseAction::LoadCompactionSettings(load_local(COMPACTION_STORE).await));
+                        db_state.dispatch(DatabaseAction::LoadStreamMetrics(load_local(METRICS_STORE).await));
+                        db_state.dispatch(DatabaseAction::LoadDrafts(load_local(DRAFTS_STORE).await));
+                    });
                 }
                 
             }
@@ -490,6 +894,80 @@ pub fn app_page() -> Html {
         });
     }
 
+    use_effect_with(db_state.title_settings.clone(), |settings| {save_stored(TITLES_STORE, settings)});
+    use_effect_with(db_state.tokenizer_settings.clone(), |settings| {save_stored(TOKENIZERS_STORE, settings)});
+    use_effect_with(db_state.compaction_settings.clone(), |settings| {save_stored(COMPACTION_STORE, settings)});
+
+    use_effect_with(db_state.stream_metrics.clone(), |metrics| {save_stored(METRICS_STORE, metrics)});
+    use_effect_with(db_state.drafts.clone(), |drafts| {save_stored(DRAFTS_STORE, drafts)});
+
+    // text media contents are not part of the client database, they get fetched once to be indexed
+    let media_in_flight = use_mut_ref(HashSet::<String>::new);
+    use_effect_with(db_state.search_index.unindexed_media().clone(), {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        move |unindexed:&im_rc::HashMap<String, (ChatID, usize)>| {
+            for (hash, location) in unindexed {
+                match db_state.db.media.get_media(hash).map(|media| {matches!(media.media_type, proxima_backend::database::media::MediaType::Text)}) {
+                    Some(true) => if media_in_flight.borrow_mut().insert(hash.clone()) {
+                        let db_state = db_state.clone();
+                        let proxima_state = proxima_state.clone();
+                        let media_in_flight = media_in_flight.clone();
+                        let hash = hash.clone();
+                        let location = *location;
+                        spawn_local(async move {
+                            match make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Get(DatabaseItemID::Media(hash.clone())) }, proxima_state.chat_url.clone()).await {
+                                Ok(DBResponse { reply:DatabaseReplyVariant::ReturnedItem(DatabaseItem::Media(_, data)) }) => match String::from_utf8(data.get_data()) {
+                                    Ok(text) => db_state.dispatch(DatabaseAction::IndexMediaText { hash: hash.clone(), text, location: Some(location) }),
+                                    Err(_) => db_state.dispatch(DatabaseAction::SkipMediaIndexing(hash.clone()))
+                                },
+                                _ => db_state.dispatch(DatabaseAction::SkipMediaIndexing(hash.clone()))
+                            }
+                            media_in_flight.borrow_mut().remove(&hash);
+                        });
+                    },
+                    Some(false) => db_state.dispatch(DatabaseAction::SkipMediaIndexing(hash.clone())),
+                    // the media item itself has not arrived yet
+                    None => ()
+                }
+            }
+        }
+    });
+
+    use_effect_with((db_state.prompt_queues.clone(), db_state.ongoing_chats.clone()), {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alert_state = alert_state.clone();
+        move |(queues, ongoing):&(Rc<PromptQueues>, im_rc::HashSet<ChatID>)| {
+            for (chat_id, prompt) in queues.ready_chats(ongoing) {
+                // taking the prompt marks the chat as busy before the next render can send it again
+                db_state.dispatch(DatabaseAction::TakeQueuedPrompt(chat_id));
+                let db_state = db_state.clone();
+                let proxima_state = proxima_state.clone();
+                let alert_state = alert_state.clone();
+                spawn_local(async move {
+                    send_queued_prompt(&db_state, &proxima_state, &alert_state, chat_id, prompt).await;
+                });
+            }
+        }
+    });
+
+    use_effect_with(db_state.compare.promoting.clone(), {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alert_state = alert_state.clone();
+        move |promoting:&Option<Promotion>| {
+            if let Some(promotion) = promoting.clone() {
+                spawn_local(async move {
+                    if let Err(error) = promote_answer(&db_state, &proxima_state, &promotion).await {
+                        alert_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Database, format!("Could not promote the answer : {error}"))));
+                    }
+                    db_state.dispatch(DatabaseAction::FinishPromotion);
+                });
+            }
+        }
+    });
+
     let event_div_node_ref = use_node_ref();
 
     let second_db = db_state.clone();
@@ -517,46 +995,39 @@ pub fn app_page() -> Html {
 
                         print("started listening").await;
                         let (mut listener, mut abort_handle) = futures::stream::abortable(listener);
-                        while let Some(raw_event) = listener.next().await {
+                        let mut token_buffers = ChatTokenBuffers::new();
+                        loop {
+                            let next_event = match token_buffers.next_gap_deadline() {
+                                Some(deadline) => {
+                                    let wait = deadline.signed_duration_since(Utc::now()).num_milliseconds().max(0) as u64;
+                                    match futures::future::select(listener.next(), Box::pin(sleep(Duration::from_millis(wait)))).await {
+                                        Either::Left((next_event, _)) => next_event,
+                                        Either::Right(_) => {
+                                            skip_timed_out_gaps(&db_state, &proxima_state, &alert_state, &mut token_buffers).await;
+                                            continue;
+                                        }
+                                    }
+                                },
+                                None => listener.next().await
+                            };
+                            let Some(raw_event) = next_event else {
+                                break;
+                            };
 
-                            
                             let event = raw_event.payload.0;
                             let chat_id = raw_event.payload.1;
                             let token_id = raw_event.payload.2;
 
-                            print(format!("IT'S FOR CHAT_ID {chat_id} | CHAT LEN {} | EVENT ID {}", db_state.db.chats.get_chats().len(), raw_event.id)).await;
+                            print(format!("IT'S FOR CHAT_ID {chat_id} | CHAT LEN {} | EVENT ID {}", db_state.chats.len(), raw_event.id)).await;
 
-                            match event {
-                                EndpointResponseVariant::StartStream(data, position) => {
-
-                                    print("It's a start stream event").await;
-                                    db_state.dispatch(DatabaseAction::AddPartToChat{
-                                        chat_id,
-                                        token_id,
-                                        part:ContextPart::new(vec![data], position)
-                                    });
-                                },
-                                EndpointResponseVariant::ContinueStream(data, position) => {
-                                    print("It's a continue stream event").await;
-                                    db_state.dispatch(DatabaseAction::AddDataToLastPartOfChat {
-                                        chat_id,
-                                        token_id,
-                                        data
-                                    });
-                                    
-                                     
-                                },
-                                EndpointResponseVariant::EndStream(data, position) => {
-                                    print("It's a end stream event").await;
-                                },
-                                EndpointResponseVariant::EndpointError(error) => {
-                                    print("Got backend error").await;
-                                    alert_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::AIEndpoint, format!("AI Endpoint unreachable"))));
-                                },
-                                _ => {
-                                    print("It's an impossible stream event").await;
+                            for (token_id, event) in token_buffers.receive(chat_id, token_id, event, Utc::now()) {
+                                let stream_ended = apply_stream_event(&db_state, &alert_state, chat_id, token_id, event).await;
+                                if stream_ended && token_buffers.take_skipped(chat_id) {
+                                    resync_chat(&db_state, &proxima_state, chat_id).await;
                                 }
                             }
+                            // events of other chats keep the sleep above from ever finishing while they stream
+                            skip_timed_out_gaps(&db_state, &proxima_state, &alert_state, &mut token_buffers).await;
 
                             print("Finished chat streaming event").await;
                         }
@@ -631,7 +1102,7 @@ pub fn app_page() -> Html {
     );
 
     let mut values = Vec::with_capacity(4);
-    for i in 0..8 {
+    for i in 0..10 {
         if db_state.cursors.chosen_tab == i {
             values.push(String::from("chosen"));
         }
@@ -639,7 +1110,7 @@ pub fn app_page() -> Html {
             values.push(String::from("not-chosen"));
         }
     }
-    let tab_picker_callbacks:Vec<Callback<MouseEvent>> = (0..8).into_iter().map(|i| {
+    let tab_picker_callbacks:Vec<Callback<MouseEvent>> = (0..10).into_iter().map(|i| {
         let db_state = db_state.clone();
         Callback::from(move |e: MouseEvent| {
             e.prevent_default();
@@ -712,6 +1183,36 @@ pub fn app_page() -> Html {
                 </ContextProvider<UseReducerHandle<Alerts>>>
             )
         }
+        /* Search */ 7 => {
+            let db_state = db_state.clone();
+            html!(
+                <ContextProvider<UseReducerHandle<Alerts>> context={alert_state.clone()}>
+                <ContextProvider<UseReducerHandle<DatabaseState>> context={db_state.clone()}>
+                    <SearchTab/>
+                </ContextProvider<UseReducerHandle<DatabaseState>>>
+                </ContextProvider<UseReducerHandle<Alerts>>>
+            )
+        }
+        /* Memory */ 8 => {
+            let db_state = db_state.clone();
+            html!(
+                <ContextProvider<UseReducerHandle<Alerts>> context={alert_state.clone()}>
+                <ContextProvider<UseReducerHandle<DatabaseState>> context={db_state.clone()}>
+                    <MemoryTab/>
+                </ContextProvider<UseReducerHandle<DatabaseState>>>
+                </ContextProvider<UseReducerHandle<Alerts>>>
+            )
+        }
+        /* Compare */ 9 => {
+            let db_state = db_state.clone();
+            html!(
+                <ContextProvider<UseReducerHandle<Alerts>> context={alert_state.clone()}>
+                <ContextProvider<UseReducerHandle<DatabaseState>> context={db_state.clone()}>
+                    <CompareTab/>
+                </ContextProvider<UseReducerHandle<DatabaseState>>>
+                </ContextProvider<UseReducerHandle<Alerts>>>
+            )
+        }
         _ => html!({"Something is very wrong"})
     };
     let access_mode_select = use_node_ref();
@@ -758,6 +1259,9 @@ pub fn app_page() -> Html {
                     <button class="menu-item" id={values[4].clone()} onclick={tab_picker_callbacks[4].clone()}>{format!("Files{}", if second_db_here.cursors.anything_new_for[4] {" ●"} else {""})}</button>
                     <button class="menu-item" id={values[5].clone()} onclick={tab_picker_callbacks[5].clone()}>{format!("Configurations{}", if second_db_here.cursors.anything_new_for[5] {" ●"} else {""})}</button>
                     <button class="menu-item" id={values[6].clone()} onclick={tab_picker_callbacks[6].clone()}>{format!("Notifications{}", if second_db_here.cursors.anything_new_for[6] {" ●"} else {""})}</button>
+                    <button class="menu-item" id={values[7].clone()} onclick={tab_picker_callbacks[7].clone()}>{"Search"}</button>
+                    <button class="menu-item" id={values[8].clone()} onclick={tab_picker_callbacks[8].clone()}>{format!("Memory{}", if second_db_here.cursors.anything_new_for[8] {" ●"} else {""})}</button>
+                    <button class="menu-item" id={values[9].clone()} onclick={tab_picker_callbacks[9].clone()}>{"Compare"}</button>
                     <select class="menu-item" ref={access_mode_select} onchange={access_mode_callback}>
                         {access_modes_htmls}
                     </select>
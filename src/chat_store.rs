@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use proxima_backend::database::ProxDatabase;
+use proxima_backend::database::chats::{Chat, ChatID};
+
+use crate::templates::is_template;
+
+/// Chats of the database state, each one shared on its own so that changing a chat only copies that chat and not
+/// every other chat with it
+///
+/// Templates are stored as chats too, the chat accessors leave them out and only the template ones give them
+#[derive(Clone, PartialEq, Default)]
+pub struct ChatStore {
+    chats:im_rc::HashMap<ChatID, Rc<Chat>>
+}
+
+impl ChatStore {
+    /// Takes the chats out of a database, which keeps everything else and its next chat id
+    pub fn take_from(db:&mut ProxDatabase) -> Self {
+        let chats = std::mem::take(db.chats.get_chats_mut());
+        Self { chats: chats.into_iter().map(|(id, chat)| {(id, Rc::new(chat))}).collect() }
+    }
+    pub fn get(&self, chat_id:ChatID) -> Option<&Rc<Chat>> {
+        self.chats.get(&chat_id).filter(|chat| {!is_template(chat)})
+    }
+    pub fn contains(&self, chat_id:ChatID) -> bool {
+        self.get(chat_id).is_some()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&ChatID, &Chat)> {
+        self.chats.iter().map(|(id, chat)| {(id, chat.as_ref())}).filter(|(_, chat)| {!is_template(chat)})
+    }
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    /// Copies the chat only when an earlier state still shares it
+    pub fn get_mut(&mut self, chat_id:ChatID) -> Option<&mut Chat> {
+        self.chats.get_mut(&chat_id).filter(|chat| {!is_template(chat)}).map(Rc::make_mut)
+    }
+    pub fn template(&self, chat_id:ChatID) -> Option<&Chat> {
+        self.chats.get(&chat_id).map(|chat| {chat.as_ref()}).filter(|chat| {is_template(chat)})
+    }
+    pub fn templates(&self) -> impl Iterator<Item = &Chat> {
+        self.chats.values().map(|chat| {chat.as_ref()}).filter(|chat| {is_template(chat)})
+    }
+    pub fn insert(&mut self, chat:Chat) {
+        self.chats.insert(chat.get_id(), Rc::new(chat));
+    }
+    pub fn remove(&mut self, chat_id:ChatID) -> Option<Rc<Chat>> {
+        self.chats.remove(&chat_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+
+    use crate::templates::template_context;
+
+    use super::*;
+
+    fn database(chats:usize) -> ProxDatabase {
+        let mut db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        for _ in 0..chats {
+            let chat = db.chats.create_possible_chat(WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(String::from("hi"))], ContextPosition::User)]), None, 0, None);
+            db.chats.add_chat_raw(chat);
+        }
+        db
+    }
+
+    #[test]
+    fn takes_the_chats_out_of_the_database() {
+        let mut db = database(3);
+        let latest_id = db.chats.latest_id;
+        let store = ChatStore::take_from(&mut db);
+        assert_eq!(store.len(), 3);
+        assert!(db.chats.get_chats().is_empty());
+        assert_eq!(db.chats.latest_id, latest_id);
+    }
+
+    #[test]
+    fn changing_a_chat_leaves_the_others_shared() {
+        let store = ChatStore::take_from(&mut database(2));
+        let mut changed = store.clone();
+        changed.get_mut(0).unwrap().context.add_part(ContextPart::new(vec![ContextData::Text(String::from("more"))], ContextPosition::AI));
+        assert_eq!(store.get(0).unwrap().context.get_parts().len(), 1);
+        assert_eq!(changed.get(0).unwrap().context.get_parts().len(), 2);
+        assert!(Rc::ptr_eq(store.get(1).unwrap(), changed.get(1).unwrap()));
+        assert!(!Rc::ptr_eq(store.get(0).unwrap(), changed.get(0).unwrap()));
+    }
+
+    #[test]
+    fn templates_are_only_given_by_the_template_accessors() {
+        let mut db = database(2);
+        let template = db.chats.create_possible_chat(template_context("Review {{code}}"), None, 0, None);
+        db.chats.add_chat_raw(template);
+        let mut store = ChatStore::take_from(&mut db);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.iter().map(|(id, _)| {*id}).collect::<BTreeSet<ChatID>>(), BTreeSet::from([0, 1]));
+        assert!(store.get(2).is_none() && !store.contains(2) && store.get_mut(2).is_none());
+        assert!(store.template(2).is_some() && store.template(0).is_none());
+        assert_eq!(store.templates().map(|template| {template.get_id()}).collect::<Vec<ChatID>>(), vec![2]);
+    }
+}
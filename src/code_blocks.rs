@@ -0,0 +1,393 @@
+use wasm_bindgen_futures::{JsFuture, spawn_local};
+use yew::virtual_dom::VNode;
+use yew::{AttrValue, Callback, Html, MouseEvent, Properties, function_component, html, use_state_eq};
+
+use crate::files::{pick_save_path, save_text_file};
+use crate::find_matches::highlight_in_html;
+
+pub struct Language {
+    pub names:&'static [&'static str],
+    pub extension:&'static str,
+    keywords:&'static [&'static str],
+    literals:&'static [&'static str],
+    line_comment:Option<&'static str>,
+    block_comment:Option<(&'static str, &'static str)>,
+    quotes:&'static [char]
+}
+
+const LANGUAGES:&[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        extension: "rs",
+        keywords: &["as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while"],
+        literals: &["true", "false", "None", "Some", "Ok", "Err"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"']
+    },
+    Language {
+        names: &["python", "py", "python3"],
+        extension: "py",
+        keywords: &["and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield", "self"],
+        literals: &["True", "False", "None"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\'']
+    },
+    Language {
+        names: &["javascript", "js", "typescript", "ts", "jsx", "tsx"],
+        extension: "js",
+        keywords: &["async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else", "export", "extends", "finally", "for", "from", "function", "if", "import", "in", "instanceof", "interface", "let", "new", "of", "return", "static", "switch", "this", "throw", "try", "type", "typeof", "var", "void", "while", "yield"],
+        literals: &["true", "false", "null", "undefined"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`']
+    },
+    Language {
+        names: &["c", "cpp", "c++", "h", "hpp", "java", "cs", "csharp", "go"],
+        extension: "c",
+        keywords: &["auto", "break", "case", "catch", "char", "class", "const", "continue", "default", "delete", "do", "double", "else", "enum", "extends", "final", "float", "for", "func", "go", "if", "implements", "import", "include", "int", "interface", "long", "namespace", "new", "package", "private", "protected", "public", "return", "short", "static", "struct", "switch", "template", "this", "throw", "try", "typedef", "unsigned", "using", "var", "void", "while"],
+        literals: &["true", "false", "null", "nullptr", "nil", "NULL"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'']
+    },
+    Language {
+        names: &["bash", "sh", "shell", "zsh", "console"],
+        extension: "sh",
+        keywords: &["case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for", "function", "if", "in", "local", "return", "then", "until", "while"],
+        literals: &["true", "false"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\'']
+    },
+    Language {
+        names: &["sql"],
+        extension: "sql",
+        keywords: &["select", "from", "where", "insert", "into", "values", "update", "set", "delete", "create", "table", "drop", "alter", "join", "left", "right", "inner", "outer", "on", "group", "by", "order", "having", "limit", "as", "and", "or", "not", "distinct", "union", "primary", "key", "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE", "SET", "DELETE", "CREATE", "TABLE", "DROP", "ALTER", "JOIN", "LEFT", "RIGHT", "INNER", "OUTER", "ON", "GROUP", "BY", "ORDER", "HAVING", "LIMIT", "AS", "AND", "OR", "NOT", "DISTINCT", "UNION", "PRIMARY", "KEY"],
+        literals: &["NULL", "null", "TRUE", "FALSE", "true", "false"],
+        line_comment: Some("--"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\'', '"']
+    },
+    Language {
+        names: &["json"],
+        extension: "json",
+        keywords: &[],
+        literals: &["true", "false", "null"],
+        line_comment: None,
+        block_comment: None,
+        quotes: &['"']
+    },
+    Language {
+        names: &["toml", "yaml", "yml", "ini"],
+        extension: "toml",
+        keywords: &[],
+        literals: &["true", "false", "null"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\'']
+    },
+];
+
+pub fn find_language(name:&str) -> Option<&'static Language> {
+    let name = name.trim().to_lowercase();
+    LANGUAGES.iter().find(|language| {language.names.contains(&name.as_str())})
+}
+
+pub fn escape_html(text:&str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '&' => out += "&amp;",
+            '"' => out += "&quot;",
+            _ => out.push(character)
+        }
+    }
+    out
+}
+
+fn push_span(out:&mut String, class:&str, text:&str) {
+    out.push_str("<span class=\"");
+    out.push_str(class);
+    out.push_str("\">");
+    out.push_str(&escape_html(text));
+    out.push_str("</span>");
+}
+
+/// Turns code into escaped HTML with a span around every keyword, literal, string, number, comment and function name
+pub fn highlight_code(code:&str, language:Option<&Language>) -> String {
+    let Some(language) = language else {
+        return escape_html(code)
+    };
+    let mut out = String::with_capacity(code.len() * 2);
+    let mut pos = 0;
+    while pos < code.len() {
+        let rest = &code[pos..];
+        let character = rest.chars().next().unwrap();
+        if let Some(comment) = language.line_comment && rest.starts_with(comment) {
+            let length = rest.find('\n').unwrap_or(rest.len());
+            push_span(&mut out, "hl-comment", &rest[..length]);
+            pos += length;
+        }
+        else if let Some((start, end)) = language.block_comment && rest.starts_with(start) {
+            let length = rest[start.len()..].find(end).map(|found| {start.len() + found + end.len()}).unwrap_or(rest.len());
+            push_span(&mut out, "hl-comment", &rest[..length]);
+            pos += length;
+        }
+        else if language.quotes.contains(&character) {
+            let mut length = character.len_utf8();
+            let mut escaped = false;
+            for next in rest[length..].chars() {
+                length += next.len_utf8();
+                if escaped {
+                    escaped = false;
+                }
+                else if next == '\\' {
+                    escaped = true;
+                }
+                else if next == character || (next == '\n' && character != '`') {
+                    break;
+                }
+            }
+            push_span(&mut out, "hl-string", &rest[..length]);
+            pos += length;
+        }
+        else if character.is_ascii_digit() {
+            let length = rest.find(|next:char| {!(next.is_ascii_alphanumeric() || next == '.' || next == '_')}).unwrap_or(rest.len());
+            push_span(&mut out, "hl-number", &rest[..length]);
+            pos += length;
+        }
+        else if character.is_alphabetic() || character == '_' {
+            let length = rest.find(|next:char| {!(next.is_alphanumeric() || next == '_')}).unwrap_or(rest.len());
+            let word = &rest[..length];
+            if language.keywords.contains(&word) {
+                push_span(&mut out, "hl-keyword", word);
+            }
+            else if language.literals.contains(&word) {
+                push_span(&mut out, "hl-literal", word);
+            }
+            else if rest[length..].starts_with('(') || rest[length..].starts_with("!(") {
+                push_span(&mut out, "hl-function", word);
+            }
+            else {
+                out.push_str(&escape_html(word));
+            }
+            pos += length;
+        }
+        else {
+            out.push_str(&escape_html(&rest[..character.len_utf8()]));
+            pos += character.len_utf8();
+        }
+    }
+    out
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MarkdownChunk {
+    Markdown(String),
+    Code { language:String, code:String }
+}
+
+/// Opening fence of a line, with its character, its length and the info string after it
+fn opening_fence(line:&str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None
+    }
+    let fence_char = trimmed.chars().next().filter(|character| {*character == '`' || *character == '~'})?;
+    let fence_len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+    let info = trimmed[fence_len..].trim();
+    if fence_len < 3 || (fence_char == '`' && info.contains('`')) {
+        return None
+    }
+    Some((fence_char, fence_len, info))
+}
+
+/// Splits markdown into its fenced code blocks and the text around them, a block still being streamed goes to the end
+pub fn split_code_blocks(text:&str) -> Vec<MarkdownChunk> {
+    let mut chunks = Vec::with_capacity(2);
+    let mut markdown = String::new();
+    let mut lines = text.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        match opening_fence(line) {
+            Some((fence_char, fence_len, info)) => {
+                if !markdown.is_empty() {
+                    chunks.push(MarkdownChunk::Markdown(std::mem::take(&mut markdown)));
+                }
+                let language = info.split_whitespace().next().unwrap_or("").to_string();
+                let mut code = String::new();
+                for code_line in lines.by_ref() {
+                    let trimmed = code_line.trim_start_matches(' ');
+                    let closing_len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+                    if closing_len >= fence_len && trimmed[closing_len..].trim().is_empty() && code_line.len() - trimmed.len() <= 3 {
+                        break;
+                    }
+                    code.push_str(code_line);
+                }
+                if code.ends_with('\n') {
+                    code.pop();
+                }
+                chunks.push(MarkdownChunk::Code { language, code });
+            },
+            None => markdown.push_str(line)
+        }
+    }
+    if !markdown.is_empty() {
+        chunks.push(MarkdownChunk::Markdown(markdown));
+    }
+    chunks
+}
+
+async fn copy_to_clipboard(text:&str) -> bool {
+    let Some(window) = web_sys::window() else {
+        return false
+    };
+    JsFuture::from(window.navigator().clipboard().write_text(text)).await.is_ok()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CodeBlockProp {
+    pub code:String,
+    pub language:String,
+    /// Query of the find bar, its matches are marked inside the code
+    #[prop_or_default]
+    pub highlighted:Option<String>
+}
+
+#[function_component(CodeBlock)]
+pub fn code_block(prop:&CodeBlockProp) -> Html {
+    let status = use_state_eq(|| {String::new()});
+    let language = find_language(&prop.language);
+    let extension = language.map(|language| {language.extension}).unwrap_or("txt");
+
+    let copy_callback = {
+        let status = status.clone();
+        let code = prop.code.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let status = status.clone();
+            let code = code.clone();
+            spawn_local(async move {
+                status.set(if copy_to_clipboard(&code).await {"Copied".to_string()} else {"Could not copy".to_string()});
+            });
+        })
+    };
+    let save_callback = {
+        let status = status.clone();
+        let code = prop.code.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let status = status.clone();
+            let code = code.clone();
+            spawn_local(async move {
+                let Some(path) = pick_save_path(&format!("snippet.{extension}"), &[extension]).await else {
+                    return
+                };
+                match save_text_file(path.clone(), code).await {
+                    Ok(()) => status.set(format!("Saved to {}", path.display())),
+                    Err(error) => status.set(format!("Could not save to {} : {error}", path.display()))
+                }
+            });
+        })
+    };
+
+    let mut highlighted = highlight_code(&prop.code, language);
+    if let Some(query) = &prop.highlighted {
+        highlighted = highlight_in_html(&highlighted, query);
+    }
+    let language_name = if prop.language.trim().is_empty() {"code".to_string()} else {prop.language.trim().to_string()};
+
+    html!(
+        <div class="code-block">
+            <div class="code-block-header">
+                <p>{language_name}</p>
+                <div>
+                    <>{(*status).clone()}</>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={copy_callback}>{"Copy"}</button>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={save_callback}>{"Save as file"}</button>
+                </div>
+            </div>
+            <pre><code>{VNode::from_html_unchecked(AttrValue::from(highlighted))}</code></pre>
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(text:&str) -> MarkdownChunk {
+        MarkdownChunk::Markdown(text.to_string())
+    }
+
+    fn code(language:&str, code:&str) -> MarkdownChunk {
+        MarkdownChunk::Code { language:language.to_string(), code:code.to_string() }
+    }
+
+    #[test]
+    fn splits_text_around_blocks() {
+        let chunks = split_code_blocks("before\n```rust\nfn main() {}\n```\nafter\n");
+        assert_eq!(chunks, vec![markdown("before\n"), code("rust", "fn main() {}"), markdown("after\n")]);
+    }
+
+    #[test]
+    fn text_without_fence_stays_markdown() {
+        assert_eq!(split_code_blocks("some `inline` code\n``not a fence``"), vec![markdown("some `inline` code\n``not a fence``")]);
+        assert_eq!(split_code_blocks(""), vec![]);
+    }
+
+    #[test]
+    fn streamed_block_goes_to_the_end() {
+        assert_eq!(split_code_blocks("text\n```py\nprint(1)\nprint("), vec![markdown("text\n"), code("py", "print(1)\nprint(")]);
+        assert_eq!(split_code_blocks("```"), vec![code("", "")]);
+    }
+
+    #[test]
+    fn closing_fence_must_match() {
+        let chunks = split_code_blocks("````md\n```\ninner\n```\n````\n");
+        assert_eq!(chunks, vec![code("md", "```\ninner\n```")]);
+        let chunks = split_code_blocks("~~~\n```\n~~~\n");
+        assert_eq!(chunks, vec![code("", "```")]);
+        let chunks = split_code_blocks("```\n``` not closing\n```\n");
+        assert_eq!(chunks, vec![code("", "``` not closing")]);
+    }
+
+    #[test]
+    fn fence_indentation_and_info() {
+        assert_eq!(split_code_blocks("   ```js extra words\nx\n   ```"), vec![code("js", "x")]);
+        assert_eq!(split_code_blocks("    ```\nx\n"), vec![markdown("    ```\nx\n")]);
+        assert_eq!(split_code_blocks("``` a`b\nx\n"), vec![markdown("``` a`b\nx\n")]);
+    }
+
+    #[test]
+    fn finds_languages_by_alias() {
+        assert!(find_language(" Rust ").is_some());
+        assert!(find_language("not a language").is_none());
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(escape_html("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn highlights_tokens() {
+        let rust = find_language("rust");
+        assert_eq!(highlight_code("a < b", None), "a &lt; b");
+        assert_eq!(
+            highlight_code("let x = f(1); // <done>", rust),
+            "<span class=\"hl-keyword\">let</span> x = <span class=\"hl-function\">f</span>(<span class=\"hl-number\">1</span>); <span class=\"hl-comment\">// &lt;done&gt;</span>"
+        );
+        assert_eq!(
+            highlight_code("\"a\\\"b\" true", rust),
+            "<span class=\"hl-string\">&quot;a\\&quot;b&quot;</span> <span class=\"hl-literal\">true</span>"
+        );
+    }
+
+    #[test]
+    fn unterminated_string_stops_at_line_end() {
+        let highlighted = highlight_code("\"open\nnext", find_language("rust"));
+        assert_eq!(highlighted, "<span class=\"hl-string\">&quot;open\n</span>next");
+    }
+}
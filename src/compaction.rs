@@ -0,0 +1,305 @@
+use std::ops::Range;
+
+use proxima_backend::ai_interaction::endpoint_api::{EndpointRequestVariant, EndpointResponseVariant};
+use proxima_backend::database::chats::{ChatID, SessionType};
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_backend::database::{DatabaseItem, DatabaseItemID, DatabaseRequestVariant};
+use proxima_backend::web_payloads::{AIPayload, DBPayload};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Event, Html, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, fetch_chat, make_ai_request, make_db_request, save_chat_change};
+use crate::context_inspector::effective_context;
+use crate::context_parts::{part_key, position_name};
+use crate::db_sync::get_delta_for_add;
+use crate::local_store::MergeSaved;
+use crate::prompt_queue::PromptSettings;
+use crate::ratings::{is_ratings_part, splice_parts};
+use crate::tokenizer::{count_parts, max_context_length};
+
+pub const COMPACTION_STORE:&str = "compaction";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct CompactionSettings {
+    pub auto:bool,
+    pub threshold_percent:usize,
+    pub keep_recent:usize
+}
+
+impl Default for CompactionSettings {
+    fn default() -> Self {
+        Self { auto: false, threshold_percent: 80, keep_recent: 4 }
+    }
+}
+
+impl MergeSaved for CompactionSettings {
+    /// Only the defaults can be there before loading, the saved settings replace them
+    fn merge_saved(&mut self, saved:Self) {
+        *self = saved;
+    }
+}
+
+/// The parts to summarize, everything before the kept parts but the ratings, which stay in front of the chat.
+/// The kept parts always start with a user part so the kept turns start with a prompt
+fn compaction_cut(parts:&[ContextPart], keep_recent:usize) -> Option<Range<usize>> {
+    let start = usize::from(parts.first().is_some_and(is_ratings_part));
+    let mut cut = parts.len().saturating_sub(keep_recent).max(start);
+    while cut > start && !parts.get(cut).is_some_and(|part| {part.is_user()}) {
+        cut -= 1;
+    }
+    if cut - start >= 2 {
+        Some(start..cut)
+    }
+    else {
+        None
+    }
+}
+
+/// System parts like earlier summaries are summarized with the rest, so nothing before the summary is left out of it
+fn summary_request_context(parts:&[ContextPart]) -> WholeContext {
+    let transcript = parts.iter().map(|part| {
+        format!("{} :\n{}", position_name(part.get_position()), part.data_to_single_text())
+    }).collect::<Vec<String>>().join("\n\n");
+    let prompt = format!(
+        "Summarize the following conversation so it can be continued without it. Keep every fact, decision, open question, name, number and piece of code that later messages could depend on. Write the summary only.\n\n{}",
+        transcript
+    );
+    WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(prompt)], ContextPosition::User)])
+}
+
+/// Refused while the chat is generating, see update_chat
+pub async fn compact_chat(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID) -> Result<(), String> {
+    if db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats) {
+        return Err("the chat is still generating".to_string())
+    }
+    compact_now(db_state, proxima_state, chat_id, &PromptSettings::current(&db_state.cursors)).await
+}
+
+/// Summarizes everything but the latest turns into one system part with the given configuration, the original parts
+/// are kept in a new archive chat. Automatic compaction calls it directly as it runs while the prompt that triggered it
+/// is still being sent
+async fn compact_now(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID, settings:&PromptSettings) -> Result<(), String> {
+    let chat = fetch_chat(proxima_state, chat_id).await.ok_or("could not get the chat from the server".to_string())?;
+    let parts = chat.context.get_parts().clone();
+    let summarized = compaction_cut(&parts, db_state.compaction_settings.keep_recent).ok_or("not enough older turns to compact".to_string())?;
+
+    let config = settings.config.and_then(|config| {db_state.db.configs.get_configs().get(&config).cloned()});
+    let request = AIPayload::new(proxima_state.auth_token.clone(), EndpointRequestVariant::RespondToFullPrompt { whole_context: summary_request_context(&parts[summarized.clone()]), streaming: false, session_type: SessionType::Chat, chat_settings: config, chat_id: None, access_mode: settings.access_mode });
+    let summary = match make_ai_request(request, proxima_state.chat_url.clone(), chat_id).await {
+        Ok(response) => match response.reply {
+            EndpointResponseVariant::Block(part) => part.data_to_single_text(),
+            _ => return Err("the AI endpoint did not return a summary".to_string())
+        },
+        Err(_) => return Err("AI endpoint unreachable".to_string())
+    };
+
+    // the archive is added before the summary so the summary can name it, and removed again if the summary is not applied
+    let mut archive = db_state.db.chats.create_possible_chat(WholeContext::new(parts.clone()), None, proxima_state.device_id, None);
+    archive.chat_title = Some(format!("{} (archived before compaction)", chat.chat_title.clone().unwrap_or(format!("Chat {chat_id}"))));
+    archive.access_modes = chat.access_modes.clone();
+    let (delta, archive_id, archive_item) = get_delta_for_add(
+        DatabaseItemID::Chat(archive.id),
+        DatabaseItem::Chat(archive.clone()),
+        async |request| {make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request }, proxima_state.chat_url.clone()).await.map(|response| {response.reply})}
+    ).await;
+    let archive_name = match &archive_id {
+        DatabaseItemID::Chat(id) => format!("chat {id}"),
+        _ => "an archive chat".to_string()
+    };
+    db_state.dispatch(DatabaseAction::AddItem(delta, archive_id.clone(), archive_item));
+
+    let summarized_keys = parts[summarized.clone()].iter().map(part_key).collect::<Vec<String>>();
+    let mut compacted = false;
+    let saved = save_chat_change(db_state, proxima_state, chat_id, |chat| {
+        // the summary only replaces the parts it was written from, parts added meanwhile are kept after it
+        let current = chat.context.get_parts();
+        if current.len() < summarized.end || !current[summarized.clone()].iter().map(part_key).eq(summarized_keys.iter().cloned()) {
+            return
+        }
+        let summary_part = ContextPart::new(vec![ContextData::Text(format!("Summary of the earlier conversation, the full version is archived in {archive_name} :\n\n{}", summary.trim()))], ContextPosition::System);
+        splice_parts(chat, summarized, vec![summary_part]);
+        compacted = true;
+    }).await;
+    let failure = match saved {
+        Err(error) => Some(error),
+        Ok(_) if !compacted => Some("the older parts changed while they were summarized, the chat is left as is".to_string()),
+        Ok(_) => None
+    };
+    if let Some(error) = failure {
+        let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Remove(archive_id.clone()) };
+        return match make_db_request(json_request, proxima_state.chat_url.clone()).await {
+            Ok(_) => {
+                db_state.dispatch(DatabaseAction::RemoveItem(archive_id));
+                Err(error)
+            },
+            Err(_) => Err(format!("{error}, and the archive in {archive_name} could not be deleted"))
+        }
+    }
+
+    db_state.dispatch(DatabaseAction::PrunePartData(chat_id));
+    Ok(())
+}
+
+/// Called once a response is over, compacts the chat if it uses more of the context window than allowed
+/// with the configuration it was requested with
+pub async fn auto_compact_if_needed(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alert_state:&UseReducerHandle<Alerts>, chat_id:ChatID, settings:&PromptSettings) {
+    if !db_state.compaction_settings.auto {
+        return
+    }
+    let config = settings.config.and_then(|config| {db_state.db.configs.get_configs().get(&config)});
+    let Some(max) = max_context_length(config) else {
+        return
+    };
+    let Some(chat) = fetch_chat(proxima_state, chat_id).await else {
+        return
+    };
+    let tokenizer = db_state.tokenizer_settings.kind_for(settings.config).tokenizer();
+    let used = count_parts(tokenizer.as_ref(), effective_context(Some(&chat), config, "").get_parts());
+    if used * 100 >= max * db_state.compaction_settings.threshold_percent {
+        if let Err(error) = compact_now(db_state, proxima_state, chat_id, settings).await {
+            alert_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Automatic compaction failed : {error}"))));
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CompactPanelProp {
+    pub chat_id:ChatID
+}
+
+#[function_component(CompactPanel)]
+pub fn compact_panel(prop:&CompactPanelProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let auto_ref = use_node_ref();
+    let threshold_ref = use_node_ref();
+    let keep_ref = use_node_ref();
+    let status = use_state_eq(String::new);
+
+    let compact_callback = {
+        let proxima_state = proxima_state.clone();
+        let db_state = db_state.clone();
+        let status = status.clone();
+        let chat_id = prop.chat_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let proxima_state = proxima_state.clone();
+            let db_state = db_state.clone();
+            let status = status.clone();
+            status.set("Summarizing older parts...".to_string());
+            spawn_local(async move {
+                match compact_chat(&db_state, &proxima_state, chat_id).await {
+                    Ok(()) => status.set("Chat compacted".to_string()),
+                    Err(error) => status.set(format!("Could not compact : {error}"))
+                }
+            });
+        })
+    };
+    let auto_callback = {
+        let db_state = db_state.clone();
+        let auto_ref = auto_ref.clone();
+        Callback::from(move |evt:Event| {
+            db_state.dispatch(DatabaseAction::SetAutoCompaction(auto_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked()));
+        })
+    };
+    let threshold_callback = {
+        let db_state = db_state.clone();
+        let threshold_ref = threshold_ref.clone();
+        Callback::from(move |evt:Event| {
+            if let Ok(threshold) = threshold_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse::<usize>() {
+                db_state.dispatch(DatabaseAction::SetCompactionThreshold(threshold.clamp(10, 100)));
+            }
+        })
+    };
+    let keep_callback = {
+        let db_state = db_state.clone();
+        let keep_ref = keep_ref.clone();
+        Callback::from(move |evt:Event| {
+            if let Ok(keep) = keep_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse::<usize>() {
+                db_state.dispatch(DatabaseAction::SetCompactionKeepRecent(keep.max(1)));
+            }
+        })
+    };
+    let ongoing = db_state.ongoing_chats.contains(&prop.chat_id);
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="horizontal-flex">
+                <button class={if ongoing {"mainapp-unused-button standard-padding-margin-corners"} else {"mainapp-button standard-padding-margin-corners"}} disabled={ongoing} onclick={compact_callback}>{"Compact now"}</button>
+                <p class="standard-padding-margin-corners">{(*status).clone()}</p>
+            </div>
+            <div class="horizontal-flex">
+                <label class="standard-padding-margin-corners"><input type="checkbox" ref={auto_ref} checked={db_state.compaction_settings.auto} onchange={auto_callback}/>{"Compact automatically at"}</label>
+                <input class="standard-padding-margin-corners" type="number" min="10" max="100" ref={threshold_ref} value={db_state.compaction_settings.threshold_percent.to_string()} onchange={threshold_callback}/>
+                <p class="standard-padding-margin-corners">{"% of the max context length, keeping the last"}</p>
+                <input class="standard-padding-margin-corners" type="number" min="1" ref={keep_ref} value={db_state.compaction_settings.keep_recent.to_string()} onchange={keep_callback}/>
+                <p class="standard-padding-margin-corners">{"parts"}</p>
+            </div>
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::ProxDatabase;
+
+    use crate::ratings::{ResponseRating, set_rating};
+
+    use super::*;
+
+    fn part(text:&str, position:ContextPosition) -> ContextPart {
+        ContextPart::new(vec![ContextData::Text(text.to_string())], position)
+    }
+
+    /// Alternating user and AI parts
+    fn turns(count:usize) -> Vec<ContextPart> {
+        (0..count).map(|i| {part(&format!("part {i}"), if i % 2 == 0 {ContextPosition::User} else {ContextPosition::AI})}).collect()
+    }
+
+    #[test]
+    fn kept_parts_start_with_a_prompt() {
+        let parts = turns(8);
+        assert_eq!(compaction_cut(&parts, 4), Some(0..4));
+        // keeping 3 parts would start on an answer, so its prompt is kept too
+        assert_eq!(compaction_cut(&parts, 3), Some(0..4));
+        assert_eq!(compaction_cut(&parts, 1), Some(0..6));
+    }
+
+    #[test]
+    fn keeping_nothing_still_keeps_the_last_turn() {
+        assert_eq!(compaction_cut(&turns(6), 0), Some(0..4));
+        // an unanswered prompt is kept on its own
+        assert_eq!(compaction_cut(&turns(5), 0), Some(0..4));
+    }
+
+    #[test]
+    fn short_chats_are_not_compacted() {
+        assert_eq!(compaction_cut(&turns(6), 6), None);
+        assert_eq!(compaction_cut(&turns(6), 10), None);
+        // a single older part is not worth a summary
+        assert_eq!(compaction_cut(&[part("summary", ContextPosition::System), part("prompt", ContextPosition::User)], 1), None);
+        assert_eq!(compaction_cut(&[], 0), None);
+        assert_eq!(compaction_cut(&[part("answer", ContextPosition::AI), part("answer", ContextPosition::AI)], 0), None);
+    }
+
+    #[test]
+    fn earlier_summaries_are_summarized_again() {
+        let mut parts = vec![part("older summary", ContextPosition::System)];
+        parts.extend(turns(4));
+        assert_eq!(compaction_cut(&parts, 2), Some(0..3));
+        let request = summary_request_context(&parts[0..3]).get_parts()[0].data_to_single_text();
+        assert!(request.contains("System :\nolder summary"));
+        assert!(request.contains("User :\npart 0"));
+    }
+
+    #[test]
+    fn the_ratings_part_is_left_in_front() {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let mut chat = db.chats.create_possible_chat(WholeContext::new(turns(6)), None, 0, None);
+        set_rating(&mut chat, 1, ResponseRating { thumb: Some(true), ..Default::default() });
+        let parts = chat.context.get_parts();
+        assert_eq!(compaction_cut(parts, 2), Some(1..5));
+        assert_eq!(compaction_cut(parts, 6), None);
+    }
+}
@@ -0,0 +1,221 @@
+use futures::future::join_all;
+use proxima_backend::database::chats::{Chat, ChatID};
+use proxima_backend::database::context::ContextPart;
+use proxima_backend::database::{DatabaseItem, DatabaseItemID};
+use proxima_backend::web_payloads::DBPayload;
+use yew::UseReducerHandle;
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, make_db_request, save_chat_change};
+use crate::db_sync::get_delta_for_add;
+use crate::prompt_queue::{PromptSettings, add_prompt_with_config, request_response, starting_context_with_config};
+
+/// A configuration being compared, with the chat its answers go to once it has one
+#[derive(Clone, PartialEq, Debug)]
+pub struct CompareColumn {
+    pub config:usize,
+    pub chat:Option<ChatID>
+}
+
+/// One prompt sent to several configurations at once, each in its own chat
+#[derive(Clone, PartialEq, Default)]
+pub struct CompareSession {
+    /// Chat the promoted answers are added to, none promotes the answer's own chat
+    pub main_chat:Option<ChatID>,
+    pub columns:Vec<CompareColumn>,
+    /// Answer being promoted, its chats count as sending until it is saved
+    pub promoting:Option<Promotion>
+}
+
+/// The answer of a column's chat going to the main chat
+#[derive(Clone, PartialEq, Debug)]
+pub struct Promotion {
+    pub main_chat:Option<ChatID>,
+    pub column_chat:ChatID
+}
+
+impl Promotion {
+    /// Chat the answer ends up in and that gets opened
+    pub fn target(&self) -> ChatID {
+        match self.main_chat {
+            Some(main_id) if main_id != self.column_chat => main_id,
+            _ => self.column_chat
+        }
+    }
+    /// Chats nothing else may change while the answer is promoted
+    pub fn chats(&self) -> Vec<ChatID> {
+        if self.target() == self.column_chat {
+            vec![self.column_chat]
+        }
+        else {
+            vec![self.column_chat, self.target()]
+        }
+    }
+}
+
+impl CompareSession {
+    pub fn has_config(&self, config:usize) -> bool {
+        self.columns.iter().any(|column| {column.config == config})
+    }
+    pub fn toggle_config(&mut self, config:usize) {
+        if self.has_config(config) {
+            self.columns.retain(|column| {column.config != config});
+        }
+        else {
+            self.columns.push(CompareColumn { config, chat: None });
+        }
+    }
+    pub fn set_chat(&mut self, config:usize, chat:ChatID) {
+        if let Some(column) = self.columns.iter_mut().find(|column| {column.config == config}) {
+            column.chat = Some(chat);
+        }
+    }
+    /// Whether a column is still waiting for its answer
+    pub fn is_busy(&self, db_state:&DatabaseState) -> bool {
+        self.columns.iter().filter_map(|column| {column.chat}).any(|chat_id| {db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats)})
+    }
+    /// One promotion at a time, and none while one of its chats is busy
+    pub fn can_promote(&self, promotion:&Promotion, is_busy:impl Fn(ChatID) -> bool) -> bool {
+        self.promoting.is_none() && !promotion.chats().into_iter().any(is_busy)
+    }
+}
+
+/// The last prompt of a chat and everything answered to it
+pub fn promoted_parts(chat:&Chat) -> Result<Vec<ContextPart>, String> {
+    let parts = chat.context.get_parts();
+    let prompt_index = parts.iter().rposition(|part| {part.is_user()}).ok_or("the compared chat has no prompt".to_string())?;
+    Ok(parts[prompt_index..].to_vec())
+}
+
+/// Adds the prompt to the chat of every column, creating the missing ones, then asks every configuration at the same time
+pub async fn send_to_columns(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, session:CompareSession, prompt_text:String) {
+    let mut requests = Vec::with_capacity(session.columns.len());
+    let access_mode = db_state.cursors.chosen_access_mode;
+    // chats are created one after the other, the server gives each its own id
+    for column in session.columns {
+        let config_name = db_state.db.configs.get_configs().get(&column.config).map(|config| {config.name.clone()}).unwrap_or_default();
+        match column.chat.and_then(|chat_id| {db_state.chat(chat_id)}) {
+            Some(chat) => {
+                let chat_id = chat.get_id();
+                let (chat, config_opt) = match add_prompt_with_config(db_state, chat.clone(), prompt_text.clone(), Some(column.config)) {
+                    Ok(added) => added,
+                    Err(error) => {
+                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Not sent to column {config_name}, {error}"))));
+                        continue
+                    }
+                };
+                db_state.dispatch(DatabaseAction::StartSending(chat_id));
+                db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat.clone()))]));
+                requests.push((chat_id, chat.get_context().clone(), config_opt, column.config));
+            },
+            None => {
+                let (starting_context, config_opt) = match starting_context_with_config(db_state, prompt_text.clone(), Some(column.config)) {
+                    Ok(started) => started,
+                    Err(error) => {
+                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Not sent to column {config_name}, {error}"))));
+                        continue
+                    }
+                };
+                let mut new_chat = db_state.db.chats.create_possible_chat(starting_context.clone(), None, proxima_state.device_id, config_opt.clone());
+                new_chat.access_modes.insert(access_mode);
+                new_chat.chat_title = Some(format!("Compare : {config_name}"));
+                let (delta, new_id, new_item) = get_delta_for_add(
+                    DatabaseItemID::Chat(new_chat.id),
+                    DatabaseItem::Chat(new_chat),
+                    async |request| {make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request }, proxima_state.chat_url.clone()).await.map(|response| {response.reply})}
+                ).await;
+                let chat_id = match new_id {
+                    DatabaseItemID::Chat(id) => id,
+                    _ => panic!("Wrong kind of ID after check, impossible")
+                };
+                db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+                db_state.dispatch(DatabaseAction::SetCompareChat { config: column.config, chat: chat_id });
+                db_state.dispatch(DatabaseAction::StartSending(chat_id));
+                requests.push((chat_id, starting_context, config_opt, column.config));
+            }
+        }
+    }
+    join_all(requests.into_iter().map(|(chat_id, context, config_opt, config)| {
+        request_response(db_state, proxima_state, alerts_state, chat_id, context, config_opt, PromptSettings { config: Some(config), access_mode })
+    })).await;
+}
+
+/// Adds the last prompt of a column's chat and its answer to the main chat, and opens it, once the reducer
+/// accepted the promotion and marked its chats as sending
+pub async fn promote_answer(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, promotion:&Promotion) -> Result<(), String> {
+    let chat = db_state.chat(promotion.column_chat).ok_or("the compared chat does not exist anymore".to_string())?;
+    let promoted = promoted_parts(chat)?;
+    let target = promotion.target();
+    if target != promotion.column_chat {
+        if db_state.chats.template(target).is_some() {
+            return Err("templates are edited in the template panel".to_string())
+        }
+        save_chat_change(db_state, proxima_state, target, |main| {
+            for part in promoted {
+                main.context.add_part(part);
+            }
+        }).await?;
+    }
+    db_state.dispatch(DatabaseAction::SetChat(Some(target)));
+    db_state.dispatch(DatabaseAction::SetTab(1));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::ProxDatabase;
+    use proxima_backend::database::context::{ContextData, ContextPosition, WholeContext};
+
+    use super::*;
+
+    fn chat(parts:&[(&str, ContextPosition)]) -> Chat {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let parts = parts.iter().map(|(text, position)| {ContextPart::new(vec![ContextData::Text(text.to_string())], position.clone())}).collect();
+        db.chats.create_possible_chat(WholeContext::new(parts), None, 0, None)
+    }
+
+    #[test]
+    fn configs_are_toggled_and_get_their_chat() {
+        let mut session = CompareSession::default();
+        session.toggle_config(1);
+        session.toggle_config(2);
+        session.set_chat(2, 7);
+        // a configuration that is not compared gets no chat
+        session.set_chat(3, 8);
+        assert!(session.has_config(1) && session.has_config(2) && !session.has_config(3));
+        assert_eq!(session.columns, vec![CompareColumn { config: 1, chat: None }, CompareColumn { config: 2, chat: Some(7) }]);
+        session.toggle_config(1);
+        assert_eq!(session.columns, vec![CompareColumn { config: 2, chat: Some(7) }]);
+    }
+
+    #[test]
+    fn the_last_prompt_and_its_answer_are_promoted() {
+        let chat = chat(&[("first", ContextPosition::User), ("old answer", ContextPosition::AI), ("second", ContextPosition::User), ("thinking", ContextPosition::AI), ("answer", ContextPosition::AI)]);
+        let promoted = promoted_parts(&chat).unwrap().iter().map(|part| {part.data_to_single_text()}).collect::<Vec<String>>();
+        assert_eq!(promoted, vec!["second", "thinking", "answer"]);
+        assert!(promoted_parts(&self::chat(&[("only a system prompt", ContextPosition::System)])).is_err());
+    }
+
+    #[test]
+    fn promotions_go_to_the_main_chat_unless_it_is_the_column() {
+        let to_main = Promotion { main_chat: Some(1), column_chat: 2 };
+        assert_eq!(to_main.target(), 1);
+        assert_eq!(to_main.chats(), vec![2, 1]);
+        for own_chat in [Promotion { main_chat: None, column_chat: 2 }, Promotion { main_chat: Some(2), column_chat: 2 }] {
+            assert_eq!(own_chat.target(), 2);
+            assert_eq!(own_chat.chats(), vec![2]);
+        }
+    }
+
+    #[test]
+    fn busy_chats_and_a_second_promotion_are_refused() {
+        let promotion = Promotion { main_chat: Some(1), column_chat: 2 };
+        let mut session = CompareSession::default();
+        assert!(session.can_promote(&promotion, |_| {false}));
+        // the main chat is as much in the way as the compared one
+        assert!(!session.can_promote(&promotion, |chat_id| {chat_id == 1}));
+        assert!(!session.can_promote(&promotion, |chat_id| {chat_id == 2}));
+        session.promoting = Some(promotion.clone());
+        assert!(!session.can_promote(&Promotion { main_chat: None, column_chat: 3 }, |_| {false}));
+    }
+}
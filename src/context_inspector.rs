@@ -0,0 +1,157 @@
+use proxima_backend::database::chats::Chat;
+use proxima_backend::database::configuration::{ChatConfiguration, ChatSetting, RepeatPosition};
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use yew::{Callback, Html, MouseEvent, NodeRef, Properties, UseReducerHandle, function_component, html, use_context, use_state_eq};
+
+use crate::app::DatabaseState;
+use crate::context_parts::position_name;
+use crate::tokenizer::{Tokenizer, max_context_length};
+
+#[derive(Clone, PartialEq)]
+pub enum PartSource {
+    Chat(usize),
+    NextPrompt{with_tools:bool},
+    Setting{config:String, setting:String},
+    Injected
+}
+
+impl PartSource {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Chat(index) => format!("from chat : part {index}"),
+            Self::NextPrompt { with_tools:true } => format!("next prompt, with tool instructions"),
+            Self::NextPrompt { with_tools:false } => format!("next prompt"),
+            Self::Setting { config, setting } => format!("from config {config} : {setting}"),
+            Self::Injected => format!("added by the configuration")
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct InspectedPart {
+    pub part:ContextPart,
+    pub source:PartSource,
+    pub tokens:usize
+}
+
+fn prompt_part(prompt:&str, config:Option<&ChatConfiguration>) -> ContextPart {
+    match config.and_then(|config| {config.tools.as_ref()}) {
+        Some(_) => ContextPart::new_user_prompt_with_tools(vec![ContextData::Text(prompt.to_string())]),
+        None => ContextPart::new(vec![ContextData::Text(prompt.to_string())], ContextPosition::User)
+    }
+}
+
+/// Builds the context the way sending a prompt from the chat tab does
+pub fn effective_context(chat:Option<&Chat>, config:Option<&ChatConfiguration>, prompt:&str) -> WholeContext {
+    let part = prompt_part(prompt, config);
+    match chat {
+        Some(chat) => {
+            let mut context = chat.context.clone();
+            context.add_part(part);
+            if let Some(config) = config {
+                context.add_per_turn_settings(config);
+            }
+            context
+        },
+        None => match config {
+            Some(config) => WholeContext::new_with_all_settings(vec![part], config),
+            None => WholeContext::new(vec![part])
+        }
+    }
+}
+
+fn setting_name(setting:&ChatSetting) -> Option<(String, String)> {
+    match setting {
+        ChatSetting::SystemPrompt(prompt) => Some(("SystemPrompt".to_string(), prompt.data_to_single_text())),
+        ChatSetting::PrePrompt(prompt) => Some(("PrePrompt".to_string(), prompt.data_to_single_text())),
+        ChatSetting::RepeatedPrePrompt(prompt, position) => {
+            let position = match position {
+                RepeatPosition::BeforeLatest => "BeforeLatest",
+                RepeatPosition::AfterLatest => "AfterLatest"
+            };
+            Some((format!("RepeatedPrePrompt {position}"), prompt.data_to_single_text()))
+        },
+        _ => None
+    }
+}
+
+/// Matches every part of the effective context back to the chat, the next prompt or the configuration setting it came from
+pub fn inspect(chat:Option<&Chat>, config:Option<&ChatConfiguration>, prompt:&str, tokenizer:&dyn Tokenizer) -> Vec<InspectedPart> {
+    let context = effective_context(chat, config, prompt);
+    let next_prompt = prompt_part(prompt, config);
+    let chat_parts = chat.map(|chat| {chat.context.get_parts().clone()}).unwrap_or_default();
+    let settings = config.map(|config| {config.raw_settings.iter().filter_map(setting_name).collect::<Vec<(String, String)>>()}).unwrap_or_default();
+    let mut next_chat_part = 0;
+    context.get_parts().iter().map(|part| {
+        let text = part.data_to_single_text();
+        let source = if chat_parts[next_chat_part..].first() == Some(part) {
+            next_chat_part += 1;
+            PartSource::Chat(next_chat_part - 1)
+        }
+        else if *part == next_prompt {
+            PartSource::NextPrompt { with_tools: config.is_some_and(|config| {config.tools.is_some()}) }
+        }
+        else if let Some((setting, _)) = settings.iter().find(|(_, setting_text)| {!setting_text.is_empty() && text.contains(setting_text.as_str())}) {
+            PartSource::Setting { config: config.map(|config| {config.name.clone()}).unwrap_or_default(), setting: setting.clone() }
+        }
+        else {
+            PartSource::Injected
+        };
+        InspectedPart { tokens: tokenizer.count(&text), part: part.clone(), source }
+    }).collect()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ContextInspectorProp {
+    pub prompt_ref:NodeRef
+}
+
+#[function_component(ContextInspector)]
+pub fn context_inspector(prop:&ContextInspectorProp) -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let refresh = use_state_eq(|| {0_usize});
+
+    let refresh_callback = {
+        let refresh = refresh.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            refresh.set(*refresh + 1);
+        })
+    };
+
+    let prompt = prop.prompt_ref.cast::<web_sys::HtmlInputElement>().map(|input| {input.value()}).unwrap_or_default();
+    let chat = db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.chat(chat_id)});
+    let config = db_state.cursors.chosen_config.and_then(|config| {db_state.db.configs.get_configs().get(&config)});
+    let tokenizer = db_state.tokenizer_settings.kind_for(db_state.cursors.chosen_config).tokenizer();
+    let parts = inspect(chat, config, &prompt, tokenizer.as_ref());
+    let total = parts.iter().map(|part| {part.tokens}).sum::<usize>();
+    let usage = match max_context_length(config) {
+        Some(max) => html!(
+            <p class={if total > max {"context-over-limit"} else {""}}>{format!("About {total} tokens out of {max} (MaxContextLength), {}%", total * 100 / max.max(1))}</p>
+        ),
+        None => html!(<p>{format!("About {total} tokens, no MaxContextLength set")}</p>)
+    };
+
+    let part_htmls = parts.iter().enumerate().map(|(i, inspected)| {
+        let source_class = match inspected.source {
+            PartSource::Chat(_) => "inspected-part",
+            _ => "inspected-part injected-part"
+        };
+        html!(
+            <details class={source_class}>
+                <summary>{format!("{i} - {} ({}) - about {} tokens", position_name(inspected.part.get_position()), inspected.source.describe(), inspected.tokens)}</summary>
+                <pre>{inspected.part.data_to_single_text()}</pre>
+            </details>
+        )
+    }).collect::<Html>();
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="chat-title-display">
+                <h3>{"Context sent with the next prompt"}</h3>
+                <button class="mainapp-button standard-padding-margin-corners align-right" onclick={refresh_callback}>{"Refresh"}</button>
+            </div>
+            {usage}
+            {part_htmls}
+        </div>
+    )
+}
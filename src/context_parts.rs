@@ -0,0 +1,50 @@
+use proxima_backend::database::context::{ContextPart, ContextPosition};
+
+pub fn position_name(position:&ContextPosition) -> String {
+    match position {
+        ContextPosition::User => "User",
+        ContextPosition::Tool(_) => "Tool",
+        ContextPosition::AI => "AI",
+        ContextPosition::System => "System",
+        ContextPosition::Total => "Total"
+    }.to_string()
+}
+
+/// Parts have no id of their own, so what is kept about a part on this device, like its streaming metrics, follows it
+/// through its position, date and text, which stay the same when parts before it are removed or the chat is compacted
+pub fn part_key(part:&ContextPart) -> String {
+    // FNV-1a, the key is saved so it has to hash the same in every version
+    let mut hash:u64 = 0xcbf29ce484222325;
+    let described = format!("{}|{}|{}", position_name(part.get_position()), part.get_date().map(|date| {format!("{date}")}).unwrap_or_default(), part.data_to_single_text());
+    for byte in described.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::context::ContextData;
+
+    use super::*;
+
+    fn part(text:&str, position:ContextPosition) -> ContextPart {
+        ContextPart::new(vec![ContextData::Text(text.to_string())], position)
+    }
+
+    #[test]
+    fn keys_are_stable_hex_hashes() {
+        let key = part_key(&part("Hello", ContextPosition::AI));
+        assert_eq!(key.len(), 16);
+        assert!(key.chars().all(|character| {character.is_ascii_hexdigit()}));
+        assert_eq!(key, part_key(&part("Hello", ContextPosition::AI)));
+    }
+
+    #[test]
+    fn keys_depend_on_the_position_and_the_text() {
+        let key = part_key(&part("Hello", ContextPosition::AI));
+        assert_ne!(key, part_key(&part("Hello", ContextPosition::User)));
+        assert_ne!(key, part_key(&part("Hello!", ContextPosition::AI)));
+    }
+}
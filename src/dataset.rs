@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use proxima_backend::database::{ProxDatabase, chats::Chat, configuration::ChatSetting};
+use proxima_frontend_shared::dataset::{DatasetConversation, DatasetMessage, DatasetToolCall};
+use serde::{Deserialize, Serialize};
+
+use crate::export::{ExportedSection, export_chat};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum MediaHandling {
+    Drop,
+    Placeholder
+}
+
+#[derive(Clone, PartialEq)]
+pub struct DatasetOptions {
+    pub keep_thinking:bool,
+    pub keep_tools:bool,
+    pub merge_system_prompt:bool,
+    pub media:MediaHandling
+}
+
+fn system_prompt_of(chat:&Chat) -> Option<String> {
+    let config = chat.latest_used_config.as_ref()?;
+    let prompts = config.raw_settings.iter().filter_map(|setting| {
+        match setting {
+            ChatSetting::SystemPrompt(prompt) => Some(prompt.data_to_single_text()),
+            _ => None
+        }
+    }).filter(|prompt| {!prompt.trim().is_empty()}).collect::<Vec<String>>();
+    if prompts.is_empty() {
+        None
+    }
+    else {
+        Some(prompts.join("\n\n"))
+    }
+}
+
+/// Adds text to the turn of that role if it is the last one, as removing tool parts or empty parts can
+/// leave several turns of the same role in a row
+fn push_text(messages:&mut Vec<DatasetMessage>, role:&str, text:String) {
+    if text.trim().is_empty() {
+        return
+    }
+    match messages.last_mut() {
+        Some(last) if last.role == role && role != "tool" => {
+            if !last.content.is_empty() {
+                last.content += "\n\n";
+            }
+            last.content += &text;
+        },
+        _ => messages.push(DatasetMessage::new(role, text))
+    }
+}
+
+/// Outputs answer the oldest call still waiting for one, outputs that answer no call stay in the text of the turn
+fn push_outputs(messages:&mut Vec<DatasetMessage>, waiting:&mut VecDeque<String>, text:String) {
+    match waiting.pop_front() {
+        Some(id) => messages.push(DatasetMessage { tool_call_id: Some(id), ..DatasetMessage::new("tool", text) }),
+        None => push_text(messages, "assistant", format!("<outputs>{text}</outputs>"))
+    }
+}
+
+/// A call without outputs cannot stay a call, so it goes back into the text of its turn
+fn fold_unanswered(messages:&mut [DatasetMessage], waiting:&mut VecDeque<String>) {
+    for id in waiting.drain(..) {
+        for message in messages.iter_mut() {
+            if let Some(index) = message.tool_calls.iter().position(|call| {call.id == id}) {
+                let call = message.tool_calls.remove(index);
+                if !message.content.is_empty() {
+                    message.content += "\n\n";
+                }
+                message.content += &format!("<call><tool>{}</tool>{}</call>", call.name, call.arguments);
+            }
+        }
+    }
+}
+
+pub fn dataset_conversation(db:&ProxDatabase, chat:&Chat, options:&DatasetOptions) -> Option<DatasetConversation> {
+    let exported = export_chat(db, chat);
+    let mut messages:Vec<DatasetMessage> = Vec::with_capacity(exported.parts.len() + 1);
+    let mut waiting = VecDeque::new();
+    let mut calls = 0;
+    if options.merge_system_prompt && let Some(prompt) = system_prompt_of(chat) {
+        messages.push(DatasetMessage::new("system", prompt));
+    }
+    for part in exported.parts {
+        let media = if options.media == MediaHandling::Placeholder {
+            part.media.into_iter().map(|media| {
+                format!("[{} : {}]", media.media_type.unwrap_or("Media".to_string()), media.file_name.unwrap_or(media.hash))
+            }).collect::<Vec<String>>()
+        }
+        else {
+            Vec::new()
+        };
+        let role = match part.position.as_str() {
+            "User" => "user",
+            "AI" => "assistant",
+            "System" => "system",
+            "Tool" if options.keep_tools => {
+                let text = part.sections.into_iter().filter_map(|section| {
+                    match section {
+                        ExportedSection::Text { text } | ExportedSection::ToolOutputs { text } => Some(text),
+                        _ => None
+                    }
+                }).chain(media).collect::<Vec<String>>().join("\n\n");
+                if !text.trim().is_empty() {
+                    push_outputs(&mut messages, &mut waiting, text);
+                }
+                continue
+            },
+            _ => continue
+        };
+        if role != "assistant" {
+            fold_unanswered(&mut messages, &mut waiting);
+        }
+        for section in part.sections {
+            match section {
+                ExportedSection::Text { text } => push_text(&mut messages, role, text),
+                ExportedSection::Thinking { text } if options.keep_thinking => push_text(&mut messages, role, format!("<think>{text}</think>")),
+                ExportedSection::ToolCall { tool, text } if options.keep_tools && role == "assistant" => {
+                    calls += 1;
+                    let id = format!("call_{calls}");
+                    if !matches!(messages.last(), Some(last) if last.role == role) {
+                        messages.push(DatasetMessage::new(role, String::new()));
+                    }
+                    if let Some(last) = messages.last_mut() {
+                        last.tool_calls.push(DatasetToolCall { id: id.clone(), name: tool, arguments: text });
+                    }
+                    waiting.push_back(id);
+                },
+                ExportedSection::ToolOutputs { text } if options.keep_tools => push_outputs(&mut messages, &mut waiting, text),
+                ExportedSection::Other { name, text } => push_text(&mut messages, role, format!("<{name}>{text}</{name}>")),
+                _ => ()
+            }
+        }
+        for placeholder in media {
+            push_text(&mut messages, role, placeholder);
+        }
+    }
+    fold_unanswered(&mut messages, &mut waiting);
+    if messages.iter().any(|message| {message.role == "assistant"}) {
+        Some(DatasetConversation { messages })
+    }
+    else {
+        None
+    }
+}
@@ -1,7 +1,10 @@
 use std::collections::HashSet;
+use std::rc::Rc;
 
 use proxima_backend::{database::{DatabaseInfoReply, DatabaseInfoRequest, DatabaseItem, DatabaseItemID, DatabaseReplyVariant, DatabaseRequestVariant, ProxDatabase, configuration::ChatSetting}, web_payloads::{DBPayload, DBResponse}};
 
+use crate::chat_store::ChatStore;
+
 pub enum NeedToSync {
     Everything,
     RetrieveThoseItems(Vec<DatabaseItemID>),
@@ -24,6 +27,7 @@ pub fn get_database_conflict_fix<F:Fn(DatabaseRequestVariant) -> Result<Database
 pub struct UserCursors {
     pub chosen_tab:usize,
     pub chosen_chat:Option<usize>,
+    pub focused_part:Option<usize>,
     pub chosen_access_mode:usize,
     pub access_mode_for_modification:Option<usize>,
     pub chosen_tag:Option<usize>,
@@ -34,18 +38,28 @@ pub struct UserCursors {
     pub chosen_setting:Option<usize>,
     pub setting_for_modification:Option<ChatSetting>,
     pub chosen_access_mode_tags:HashSet<usize>,
+    pub focused_memory:Option<u64>,
     pub anything_new_for:Vec<bool>
 }
 
 impl UserCursors {
     pub fn zero() -> Self {
-        Self { chosen_tab:0, chosen_chat: None, chosen_access_mode: 0, access_mode_for_modification: None, chosen_tag: None, chosen_parent_tag: None, chosen_access_mode_tags: HashSet::new(), chosen_config:None, config_for_modification:None, chosen_setting:None, setting_for_modification:None, chosen_tags:HashSet::new(), anything_new_for:vec![false;8] }
+        Self { chosen_tab:0, chosen_chat: None, focused_part: None, chosen_access_mode: 0, access_mode_for_modification: None, chosen_tag: None, chosen_parent_tag: None, chosen_access_mode_tags: HashSet::new(), chosen_config:None, config_for_modification:None, chosen_setting:None, setting_for_modification:None, chosen_tags:HashSet::new(), focused_memory:None, anything_new_for:vec![false;10] }
     }
 }
 
-pub fn apply_server_updates(client_db: &mut ProxDatabase, updates:Vec<(DatabaseItemID, DatabaseItem)>, cursors:UserCursors) -> UserCursors {
+/// Chats go to the chat store, the database is only copied for the other items and for new chats moving its next chat id
+pub fn apply_server_updates(client_db: &mut Rc<ProxDatabase>, chats:&mut ChatStore, updates:Vec<(DatabaseItemID, DatabaseItem)>, cursors:UserCursors) -> UserCursors {
     let mut new_cursors = cursors.clone();
     for (id, new_item) in updates {
+        if let DatabaseItem::Chat(chat) = new_item {
+            if chat.get_id() >= client_db.chats.latest_id {
+                Rc::make_mut(client_db).chats.latest_id = chat.get_id() + 1;
+            }
+            chats.insert(chat);
+            continue;
+        }
+        let client_db = Rc::make_mut(client_db);
         match new_item {
             DatabaseItem::AccessMode(access_mode) => {
                 if access_mode.get_id() >= client_db.access_modes.latest_id {
@@ -55,14 +69,7 @@ pub fn apply_server_updates(client_db: &mut ProxDatabase, updates:Vec<(DatabaseI
                     client_db.access_modes.update_mode(access_mode);
                 }
             },
-            DatabaseItem::Chat(chat) => {
-                if chat.get_id() >= client_db.chats.latest_id {
-                    client_db.chats.add_chat_raw(chat);
-                }
-                else {
-                    client_db.chats.update_chat(chat);
-                }
-            },
+            DatabaseItem::Chat(_) => (),
             DatabaseItem::Device(device) => {
                 if device.get_id() >= client_db.devices.latest_id {
                     client_db.devices.add_device(device);
@@ -111,7 +118,12 @@ pub fn apply_server_updates(client_db: &mut ProxDatabase, updates:Vec<(DatabaseI
                 client_db.media.insert_media_raw(med);
             },
             DatabaseItem::Memory(mem, _) => {
-
+                if mem.get_id() >= client_db.memories.last_memory_id {
+                    client_db.memories.add_memory_raw(mem);
+                }
+                else {
+                    client_db.memories.update_memory(mem);
+                }
             },
             DatabaseItem::Notification(notif) => {
                 client_db.notifications.insert_notification_raw(notif);
@@ -130,7 +142,8 @@ pub fn apply_server_updates(client_db: &mut ProxDatabase, updates:Vec<(DatabaseI
 pub fn get_next_id_for_category(db:&ProxDatabase, category:&DatabaseItem) -> DatabaseItemID {
     match category {
         DatabaseItem::AccessMode(_) => DatabaseItemID::AccessMode(db.access_modes.get_modes().len()),
-        DatabaseItem::Chat(_) => DatabaseItemID::Chat(db.chats.get_chats().len()),
+        // the chats themselves are in the chat store
+        DatabaseItem::Chat(_) => DatabaseItemID::Chat(db.chats.latest_id),
         DatabaseItem::ChatConfig(_) => DatabaseItemID::ChatConfiguration(db.configs.get_configs().len()),
         DatabaseItem::Device(_) => DatabaseItemID::Device(db.devices.get_devices().len()),
         DatabaseItem::File(_) => DatabaseItemID::File(db.files.len()),
@@ -170,10 +183,10 @@ pub async fn get_delta_for_add<F:AsyncFn(DatabaseRequestVariant) -> Result<Datab
     (delta, new_id, added_item)
 }
 
-pub fn handle_add_reducible(client_db: &mut ProxDatabase, local_given_id:DatabaseItemID, remote_id:DatabaseItemID, added_item:DatabaseItem, cursors:UserCursors, delta:Vec<(DatabaseItemID, DatabaseItem)>) -> UserCursors {
+pub fn handle_add_reducible(client_db: &mut Rc<ProxDatabase>, chats:&mut ChatStore, local_given_id:DatabaseItemID, remote_id:DatabaseItemID, added_item:DatabaseItem, cursors:UserCursors, delta:Vec<(DatabaseItemID, DatabaseItem)>) -> UserCursors {
     let mut new_cursors = cursors.clone();
-    new_cursors = apply_server_updates(client_db, delta, new_cursors);
-    new_cursors = apply_server_updates(client_db, vec![(remote_id.clone(), added_item)], new_cursors);
+    new_cursors = apply_server_updates(client_db, chats, delta, new_cursors);
+    new_cursors = apply_server_updates(client_db, chats, vec![(remote_id.clone(), added_item)], new_cursors);
     match local_given_id.clone() {
         DatabaseItemID::AccessMode(local_id) => match remote_id {
             DatabaseItemID::AccessMode(remote_id) => {
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use proxima_backend::database::chats::ChatID;
+use serde::{Deserialize, Serialize};
+
+use crate::local_store::MergeSaved;
+
+pub const DRAFTS_STORE:&str = "drafts";
+
+/// What was typed and attached in the composer of a chat but not sent yet
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Draft {
+    pub text:String,
+    pub files:Vec<PathBuf>
+}
+
+impl Draft {
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty() && self.files.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Drafts {
+    pub chats:HashMap<ChatID, Draft>,
+    /// Draft of the chat that gets created by the next send when no chat is chosen
+    pub new_chat:Draft
+}
+
+impl Drafts {
+    pub fn get(&self, chat_id:Option<ChatID>) -> Draft {
+        match chat_id {
+            Some(chat_id) => self.chats.get(&chat_id).cloned().unwrap_or_default(),
+            None => self.new_chat.clone()
+        }
+    }
+    pub fn set(&mut self, chat_id:Option<ChatID>, draft:Draft) {
+        match chat_id {
+            Some(chat_id) => if draft.is_empty() {
+                self.chats.remove(&chat_id);
+            }
+            else {
+                self.chats.insert(chat_id, draft);
+            },
+            None => self.new_chat = draft
+        }
+    }
+    pub fn remove_chat(&mut self, chat_id:ChatID) {
+        self.chats.remove(&chat_id);
+    }
+}
+
+impl MergeSaved for Drafts {
+    /// Drafts typed before loading replace the saved ones of their chat
+    fn merge_saved(&mut self, saved:Self) {
+        for (chat_id, draft) in saved.chats {
+            self.chats.entry(chat_id).or_insert(draft);
+        }
+        if self.new_chat.is_empty() {
+            self.new_chat = saved.new_chat;
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::path::PathBuf;
+
+use gloo_utils::format::JsValueSerdeExt;
+use proxima_backend::database::{ProxDatabase, chats::{Chat, ChatID}, context::{ContextData, ContextPart, ContextPosition}, media::MediaType};
+use proxima_frontend_shared::dataset::{DatasetConversation, DatasetFormat, DatasetWritten};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Event, Html, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::{alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction}, app::{DatabaseState, ProximaState, try_invoke}, files::{pick_folder, pick_save_path}, dataset::{DatasetOptions, MediaHandling, dataset_conversation}, html_parsing::{HtmlNode, TagKind, parse_tags}, context_parts::position_name, ratings::{RatingFilter, is_ratings_part}};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportedChat {
+    pub id:ChatID,
+    pub title:Option<String>,
+    pub config:Option<String>,
+    pub parts:Vec<ExportedPart>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportedPart {
+    pub index:usize,
+    pub position:String,
+    pub date:Option<String>,
+    pub sections:Vec<ExportedSection>,
+    pub media:Vec<ExportedMedia>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum ExportedSection {
+    Text{text:String},
+    Thinking{text:String},
+    ToolCall{tool:String, text:String},
+    ToolOutputs{text:String},
+    Memory{text:String},
+    CurrentTime{text:String},
+    Other{name:String, text:String}
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportedMedia {
+    pub hash:String,
+    pub file_name:Option<String>,
+    pub media_type:Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportArgs {
+    chats:Vec<ExportedChat>,
+    format:ExportFormat,
+    path:PathBuf,
+    url:String
+}
+
+#[derive(Serialize, Deserialize)]
+struct DatasetArgs {
+    conversations:Vec<DatasetConversation>,
+    format:DatasetFormat,
+    path:PathBuf
+}
+
+pub fn sections_of_part(part:&ContextPart) -> Vec<ExportedSection> {
+    let mut all_text = part.data_to_single_text();
+    if part.is_user() {
+        all_text = all_text.trim().to_string();
+        all_text.remove_matches("<user_prompt>");
+        all_text.remove_matches("</user_prompt>");
+        return vec![ExportedSection::Text { text: all_text }]
+    }
+    let parsed = parse_tags(&all_text);
+    let mut sections = Vec::with_capacity(parsed.children.len());
+    if parsed.has_elements() {
+        for child in parsed.children {
+            push_node_sections(&mut sections, child);
+        }
+        if parsed.pending.trim().len() > 0 {
+            sections.push(ExportedSection::Text { text: parsed.pending.trim().to_string() });
+        }
+    }
+    else if all_text.trim().len() > 0 {
+        sections.push(ExportedSection::Text { text: all_text.trim().to_string() });
+    }
+    sections
+}
+
+fn push_node_sections(sections:&mut Vec<ExportedSection>, node:HtmlNode) {
+    match node {
+        HtmlNode::Element(element) => {
+            let text = element.content.trim().to_string();
+            match element.kind {
+                TagKind::Think => sections.push(ExportedSection::Thinking { text }),
+                TagKind::Call => {
+                    let tool = element.children.first_of_kind(TagKind::Tool).map(|tool| {tool.text().trim().to_string()}).unwrap_or(String::from("unknown"));
+                    sections.push(ExportedSection::ToolCall { tool, text });
+                },
+                TagKind::Outputs => sections.push(ExportedSection::ToolOutputs { text }),
+                TagKind::AutomaticMemory => sections.push(ExportedSection::Memory { text }),
+                TagKind::CurrentTime => sections.push(ExportedSection::CurrentTime { text }),
+                TagKind::Response => for child in element.children.children {
+                    push_node_sections(sections, child);
+                },
+                _ => if text.len() > 0 {
+                    sections.push(ExportedSection::Other { name: element.name, text });
+                }
+            }
+        },
+        HtmlNode::Text(text) => if text.trim().len() > 0 {
+            sections.push(ExportedSection::Text { text: text.trim().to_string() });
+        }
+    }
+}
+
+pub fn export_chat(db:&ProxDatabase, chat:&Chat) -> ExportedChat {
+    let parts = chat.context.get_parts().iter().enumerate().filter(|(_, part)| {part.in_visible_position() && !is_ratings_part(part)}).map(|(index, part)| {
+        let media = part.get_data().iter().filter_map(|data| {
+            match data {
+                ContextData::Media(hash) => {
+                    let media = db.media.get_media(hash);
+                    Some(ExportedMedia {
+                        hash: hash.clone(),
+                        file_name: media.map(|media| {media.file_name.clone()}),
+                        media_type: media.map(|media| {
+                            match media.media_type {
+                                MediaType::Text => "Text",
+                                MediaType::PDF => "PDF",
+                                MediaType::Image => "Image",
+                                _ => "Other"
+                            }.to_string()
+                        })
+                    })
+                },
+                ContextData::Text(_) => None
+            }
+        }).collect();
+        ExportedPart {
+            index,
+            position: position_name(part.get_position()),
+            date: part.get_date().map(|date| {format!("{date}")}),
+            sections: sections_of_part(part),
+            media
+        }
+    }).collect();
+    ExportedChat {
+        id: chat.get_id(),
+        title: chat.chat_title.clone(),
+        config: chat.latest_used_config.as_ref().map(|config| {config.name.clone()}),
+        parts
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum ExportScope {
+    CurrentChat,
+    Tag(usize),
+    AccessMode(usize)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ExportPanelProp {
+    pub chat_id:Option<ChatID>
+}
+
+#[function_component(ExportPanel)]
+pub fn export_panel(prop:&ExportPanelProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let format_ref = use_node_ref();
+    let scope_ref = use_node_ref();
+    let rating_ref = use_node_ref();
+    let thinking_ref = use_node_ref();
+    let tools_ref = use_node_ref();
+    let system_ref = use_node_ref();
+    let media_ref = use_node_ref();
+    let status = use_state_eq(String::new);
+    let dataset_mode = use_state_eq(|| {false});
+
+    let format_change_callback = {
+        let format_ref = format_ref.clone();
+        let dataset_mode = dataset_mode.clone();
+        Callback::from(move |evt:Event| {
+            let format = format_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            dataset_mode.set(matches!(format.trim(), "OpenAI JSONL" | "ShareGPT" | "Alpaca"));
+        })
+    };
+
+    let export_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let format_ref = format_ref.clone();
+        let scope_ref = scope_ref.clone();
+        let rating_ref = rating_ref.clone();
+        let thinking_ref = thinking_ref.clone();
+        let tools_ref = tools_ref.clone();
+        let system_ref = system_ref.clone();
+        let media_ref = media_ref.clone();
+        let status = status.clone();
+        let chat_id = prop.chat_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let format_value = format_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            let dataset_format = match format_value.trim() {
+                "OpenAI JSONL" => Some(DatasetFormat::OpenAIJsonl),
+                "ShareGPT" => Some(DatasetFormat::ShareGPT),
+                "Alpaca" => Some(DatasetFormat::Alpaca),
+                _ => None
+            };
+            let format = match format_value.trim() {
+                "JSON" => ExportFormat::Json,
+                "HTML" => ExportFormat::Html,
+                _ => ExportFormat::Markdown
+            };
+            let scope_value = scope_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            let scope = match scope_value.split_once(':') {
+                Some(("tag", id)) => id.parse().map(ExportScope::Tag).unwrap_or(ExportScope::CurrentChat),
+                Some(("am", id)) => id.parse().map(ExportScope::AccessMode).unwrap_or(ExportScope::CurrentChat),
+                _ => ExportScope::CurrentChat
+            };
+            let rating_filter = RatingFilter::from_value(&rating_ref.cast::<web_sys::HtmlInputElement>().unwrap().value());
+            let selected = db_state.chats.iter().filter(|(id, chat)| {
+                let in_scope = match &scope {
+                    ExportScope::CurrentChat => Some(**id) == chat_id,
+                    ExportScope::Tag(tag) => chat.tags.contains(tag),
+                    ExportScope::AccessMode(access_mode) => chat.access_modes.contains(access_mode)
+                };
+                in_scope && rating_filter.matches_chat(chat)
+            }).map(|(_, chat)| {chat}).collect::<Vec<&Chat>>();
+            if let Some(dataset_format) = dataset_format {
+                let options = DatasetOptions {
+                    keep_thinking: thinking_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked(),
+                    keep_tools: tools_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked(),
+                    merge_system_prompt: system_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked(),
+                    media: if media_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked() {MediaHandling::Placeholder} else {MediaHandling::Drop}
+                };
+                let conversations = selected.iter().filter_map(|chat| {dataset_conversation(&db_state.db, chat, &options)}).collect::<Vec<DatasetConversation>>();
+                if conversations.is_empty() {
+                    status.set("Nothing to export, pick chats with AI answers first".to_string());
+                    return;
+                }
+                let alerts_state = alerts_state.clone();
+                let status = status.clone();
+                spawn_local(async move {
+                    let (name, extension) = match dataset_format {
+                        DatasetFormat::Alpaca => ("dataset.json", "json"),
+                        _ => ("dataset.jsonl", "jsonl")
+                    };
+                    let Some(path) = pick_save_path(name, &[extension]).await else {
+                        return
+                    };
+                    let number = conversations.len();
+                    let args = serde_wasm_bindgen::to_value(&DatasetArgs { conversations, format:dataset_format, path }).unwrap();
+                    match try_invoke("export_dataset", args).await.map(|written| {written.into_serde::<DatasetWritten>()}) {
+                        Ok(Ok(written)) if written.skipped > 0 => status.set(format!("Wrote {} record(s) from {number} chat(s), {} prompt(s) answered through tool calls were left out", written.records, written.skipped)),
+                        Ok(Ok(written)) => status.set(format!("Wrote {} record(s) from {number} chat(s)", written.records)),
+                        error => {
+                            status.set(String::new());
+                            let reason = match error {
+                                Err(error) => error.as_string().unwrap_or_default(),
+                                _ => String::new()
+                            };
+                            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Could not write the dataset : {reason}"))));
+                        }
+                    }
+                });
+                return;
+            }
+            let chats = selected.iter().map(|chat| {export_chat(&db_state.db, chat)}).collect::<Vec<ExportedChat>>();
+            if chats.is_empty() {
+                status.set("Nothing to export, pick chats first".to_string());
+                return;
+            }
+            let proxima_state = proxima_state.clone();
+            let alerts_state = alerts_state.clone();
+            let status = status.clone();
+            spawn_local(async move {
+                let extension = match format {
+                    ExportFormat::Markdown => "md",
+                    ExportFormat::Json => "json",
+                    ExportFormat::Html => "html"
+                };
+                // several chats are written next to each other in a folder, without replacing files already there
+                let path = if chats.len() == 1 {
+                    pick_save_path(&format!("chat_{}.{extension}", chats[0].id), &[extension]).await
+                }
+                else {
+                    pick_folder().await
+                };
+                let Some(path) = path else {
+                    return
+                };
+                let number = chats.len();
+                let args = serde_wasm_bindgen::to_value(&ExportArgs { chats, format, path, url:proxima_state.chat_url.clone() }).unwrap();
+                match try_invoke("export_chats", args).await.map(|written| {written.into_serde::<usize>()}) {
+                    Ok(Ok(written)) => status.set(format!("Exported {written} file(s) for {number} chat(s)")),
+                    error => {
+                        status.set(String::new());
+                        let reason = match error {
+                            Err(error) => error.as_string().unwrap_or_default(),
+                            _ => String::new()
+                        };
+                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Could not write the export : {reason}"))));
+                    }
+                }
+            });
+        })
+    };
+
+    let tag_options = db_state.db.tags.get_tags().iter().map(|(id, tag)| {
+        html!(<option value={format!("tag:{id}")}>{format!("Tag : {}", tag.get_name())}</option>)
+    }).collect::<Html>();
+    let access_mode_options = db_state.db.access_modes.get_modes().iter().map(|(id, access_mode)| {
+        html!(<option value={format!("am:{id}")}>{format!("Access mode : {}", access_mode.get_name())}</option>)
+    }).collect::<Html>();
+
+    html!(
+        <>
+        <div class="label-input-combo third-level standard-padding-margin-corners">
+            <select class="standard-padding-margin-corners" ref={scope_ref}>
+                <option value="chat">{"This chat"}</option>
+                {tag_options}
+                {access_mode_options}
+            </select>
+            <select class="standard-padding-margin-corners" ref={rating_ref}>
+            {
+                RatingFilter::options().into_iter().map(|(value, name)| {
+                    html!(<option value={value}>{name}</option>)
+                }).collect::<Html>()
+            }
+            </select>
+            <select class="standard-padding-margin-corners" ref={format_ref} onchange={format_change_callback}>
+                <option value="Markdown">{"Markdown"}</option>
+                <option value="JSON">{"JSON"}</option>
+                <option value="HTML">{"HTML"}</option>
+                <option value="OpenAI JSONL">{"Dataset : OpenAI chat JSONL"}</option>
+                <option value="ShareGPT">{"Dataset : ShareGPT"}</option>
+                <option value="Alpaca">{"Dataset : Alpaca"}</option>
+            </select>
+            <button class="mainapp-button standard-padding-margin-corners" onclick={export_callback}>{"Export..."}</button>
+            <p class="standard-padding-margin-corners">{(*status).clone()}</p>
+        </div>
+        <div class={if *dataset_mode {"horizontal-flex third-level standard-padding-margin-corners"} else {"hidden"}}>
+            <label class="standard-padding-margin-corners"><input type="checkbox" ref={thinking_ref}/>{"Keep <think> sections"}</label>
+            <label class="standard-padding-margin-corners"><input type="checkbox" ref={tools_ref}/>{"Keep tool calls and outputs"}</label>
+            <label class="standard-padding-margin-corners"><input type="checkbox" checked=true ref={system_ref}/>{"Merge system prompt from the configuration"}</label>
+            <label class="standard-padding-margin-corners"><input type="checkbox" ref={media_ref}/>{"Replace media with placeholders"}</label>
+        </div>
+        </>
+    )
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use gloo_utils::format::JsValueSerdeExt;
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_backend::database::media::{Base64EncodedString, Media, MediaType};
+use proxima_backend::database::{DatabaseItem, DatabaseItemID};
+use serde::{Deserialize, Serialize};
+use yew::UseReducerHandle;
+
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, try_invoke};
+
+#[derive(Serialize, Deserialize)]
+pub struct FileArgs {
+    test1: PathBuf,
+    test2:String, 
+    test3:String
+}
+
+#[derive(Serialize, Deserialize)]
+struct PickSaveArgs {
+    name:String,
+    extensions:Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct PickFileArgs {
+    extensions:Vec<String>
+}
+
+#[derive(Serialize, Deserialize)]
+struct EmptyArgs {
+
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenUrlArgs {
+    url:String
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfirmArgs {
+    title:String,
+    message:String,
+    button:String
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveTextArgs {
+    path:PathBuf,
+    content:String
+}
+
+/// Asks where to save through the system dialog, which confirms replacing existing files, None if it was cancelled
+pub async fn pick_save_path(name:&str, extensions:&[&str]) -> Option<PathBuf> {
+    let args = serde_wasm_bindgen::to_value(&PickSaveArgs { name:name.to_string(), extensions:extensions.iter().map(|extension| {extension.to_string()}).collect() }).unwrap();
+    try_invoke("pick_save_path", args).await.ok().and_then(|path| {path.into_serde::<Option<PathBuf>>().ok().flatten()})
+}
+
+/// Asks for an existing file through the system dialog, None if it was cancelled
+pub async fn pick_file(extensions:&[&str]) -> Option<PathBuf> {
+    let args = serde_wasm_bindgen::to_value(&PickFileArgs { extensions:extensions.iter().map(|extension| {extension.to_string()}).collect() }).unwrap();
+    try_invoke("pick_file", args).await.ok().and_then(|path| {path.into_serde::<Option<PathBuf>>().ok().flatten()})
+}
+
+pub async fn pick_folder() -> Option<PathBuf> {
+    let args = serde_wasm_bindgen::to_value(&EmptyArgs {}).unwrap();
+    try_invoke("pick_folder", args).await.ok().and_then(|path| {path.into_serde::<Option<PathBuf>>().ok().flatten()})
+}
+
+/// Asks through a system dialog, false if it was cancelled or could not be shown
+pub async fn confirm(title:&str, message:&str, button:&str) -> bool {
+    let args = serde_wasm_bindgen::to_value(&ConfirmArgs { title:title.to_string(), message:message.to_string(), button:button.to_string() }).unwrap();
+    try_invoke("confirm_dialog", args).await.ok().and_then(|confirmed| {confirmed.into_serde::<bool>().ok()}).unwrap_or(false)
+}
+
+pub async fn save_text_file(path:PathBuf, content:String) -> Result<(), String> {
+    let args = serde_wasm_bindgen::to_value(&SaveTextArgs { path, content }).unwrap();
+    try_invoke("save_text_file", args).await.map(|_| {()}).map_err(|error| {error.as_string().unwrap_or_default()})
+}
+
+/// Whether a link is an http or https one, the only kind opened from what tools or models give
+pub fn is_web_link(link:&str) -> bool {
+    let link = link.trim().to_lowercase();
+    link.starts_with("https://") || link.starts_with("http://")
+}
+
+/// Opens a web link in the system browser instead of the app window
+pub async fn open_url(url:String) -> Result<(), String> {
+    if !is_web_link(&url) {
+        return Err(format!("{url} is not a web link"))
+    }
+    let args = serde_wasm_bindgen::to_value(&OpenUrlArgs { url:url.trim().to_string() }).unwrap();
+    try_invoke("plugin:opener|open_url", args).await.map(|_| {()}).map_err(|error| {error.as_string().unwrap_or_default()})
+}
+
+/// Sends the files picked for a prompt to the server, gives the hashes of the media made of them, files it refused are left out
+pub async fn upload_prompt_files(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, files:Vec<PathBuf>) -> Vec<String> {
+    let mut hashes = Vec::with_capacity(files.len());
+    for file in files {
+        let args = serde_wasm_bindgen::to_value(&FileArgs {test1:file, test2:format!("{}/db", proxima_state.chat_url.clone()), test3:proxima_state.auth_token.clone()}).unwrap();
+        if let Ok(Ok((hash, file_name, media_type))) = try_invoke("add_media_from_file_if_exists", args).await.map(|value| {value.into_serde::<(String, String, MediaType)>()}) {
+            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Media(hash.clone()), DatabaseItem::Media(Media {hash: hash.clone(), media_type, file_name, tags:HashSet::new(), access_modes:HashSet::from([0]), added_at:Utc::now()}, Base64EncodedString::new(vec![])))]));
+            hashes.push(hash);
+        }
+    }
+    hashes
+}
+
+/// Media go with the latest prompt of the context
+pub fn attach_media(context:&mut WholeContext, hashes:Vec<String>) {
+    for hash in hashes {
+        match context.get_parts().iter().rposition(|part| {matches!(part.get_position(), ContextPosition::User)}) {
+            Some(i) => context.get_parts_mut()[i].add_data(ContextData::Media(hash)),
+            None => context.add_part(ContextPart::new(vec![ContextData::Media(hash)], ContextPosition::User))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_web_links_are_opened() {
+        assert!(is_web_link("https://www.rust-lang.org"));
+        assert!(is_web_link("  HTTP://example.com/page "));
+        assert!(!is_web_link("javascript:alert(1)"));
+        assert!(!is_web_link("JavaScript:alert(1)"));
+        assert!(!is_web_link("file:///etc/passwd"));
+        assert!(!is_web_link("/relative/page"));
+        assert!(!is_web_link("www.example.com"));
+        assert!(!is_web_link("data:text/html,<p>hi</p>"));
+    }
+}
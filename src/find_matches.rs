@@ -0,0 +1,137 @@
+use crate::search::fold_char;
+
+/// Non overlapping matches of the query in the text, ignoring case
+pub fn count_matches(text:&str, query:&str) -> usize {
+    let folded_query = query.chars().map(fold_char).collect::<Vec<char>>();
+    let folded = text.chars().map(fold_char).collect::<Vec<char>>();
+    if folded_query.is_empty() {
+        return 0
+    }
+    let mut count = 0;
+    let mut i = 0;
+    while i + folded_query.len() <= folded.len() {
+        if folded[i..].starts_with(&folded_query) {
+            count += 1;
+            i += folded_query.len();
+        }
+        else {
+            i += 1;
+        }
+    }
+    count
+}
+
+/// Wraps every match of the query found outside of tags in a <mark>
+pub fn highlight_in_html(html_text:&str, query:&str) -> String {
+    let folded_query = query.chars().map(fold_char).collect::<Vec<char>>();
+    if folded_query.is_empty() {
+        return html_text.to_string()
+    }
+    let mut out = String::with_capacity(html_text.len() + 64);
+    let mut rest = html_text;
+    while !rest.is_empty() {
+        match rest.find('<') {
+            Some(0) => {
+                let tag_end = rest.find('>').map(|end| {end + 1}).unwrap_or(rest.len());
+                out += &rest[..tag_end];
+                rest = &rest[tag_end..];
+            },
+            Some(text_end) => {
+                highlight_text(&rest[..text_end], &folded_query, &mut out);
+                rest = &rest[text_end..];
+            },
+            None => {
+                highlight_text(rest, &folded_query, &mut out);
+                rest = "";
+            }
+        }
+    }
+    out
+}
+
+/// Matches on the characters the text shows, so "&amp;" is found by "&", while keeping it written as it was
+fn highlight_text(text:&str, folded_query:&[char], out:&mut String) {
+    let decoded = decode_entities(text);
+    let mut i = 0;
+    while i < decoded.len() {
+        if i + folded_query.len() <= decoded.len() && decoded[i..(i + folded_query.len())].iter().zip(folded_query.iter()).all(|((character, _), query_char)| {fold_char(*character) == *query_char}) {
+            *out += "<mark class=\"find-match\">";
+            out.extend(decoded[i..(i + folded_query.len())].iter().map(|(_, source)| {*source}));
+            *out += "</mark>";
+            i += folded_query.len();
+        }
+        else {
+            *out += decoded[i].1;
+            i += 1;
+        }
+    }
+}
+
+/// Every character the text shows, with the part of the text it was written as
+fn decode_entities(text:&str) -> Vec<(char, &str)> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(character) = rest.chars().next() {
+        let entity = if character == '&' {
+            rest[1..].find(';').filter(|end| {*end <= 10}).and_then(|end| {
+                entity_char(&rest[1..(end + 1)]).map(|entity_char| {(entity_char, end + 2)})
+            })
+        }
+        else {
+            None
+        };
+        let (shown, len) = entity.unwrap_or((character, character.len_utf8()));
+        decoded.push((shown, &rest[..len]));
+        rest = &rest[len..];
+    }
+    decoded
+}
+
+fn entity_char(name:&str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => match name.strip_prefix('#') {
+            Some(number) => match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse::<u32>().ok()
+            }.and_then(char::from_u32),
+            None => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_are_counted_without_overlaps() {
+        assert_eq!(count_matches("aaaa", "aa"), 2);
+        assert_eq!(count_matches("Rust, rust, RUST", "rust"), 3);
+        assert_eq!(count_matches("text", ""), 0);
+    }
+
+    #[test]
+    fn highlight_skips_tags_and_folds_case() {
+        assert_eq!(highlight_in_html("<p class=\"Rust\">Rust</p>", "rust"), "<p class=\"Rust\"><mark class=\"find-match\">Rust</mark></p>");
+        assert_eq!(highlight_in_html("<p>text</p>", ""), "<p>text</p>");
+    }
+
+    #[test]
+    fn highlight_matches_entities_as_what_they_show() {
+        assert_eq!(highlight_in_html("<p>a &amp; b</p>", "a & b"), "<p><mark class=\"find-match\">a &amp; b</mark></p>");
+        assert_eq!(highlight_in_html("<code>x &lt; y</code>", "<"), "<code>x <mark class=\"find-match\">&lt;</mark> y</code>");
+        assert_eq!(highlight_in_html("<p>&#39;q&#x27;</p>", "'q'"), "<p><mark class=\"find-match\">&#39;q&#x27;</mark></p>");
+    }
+
+    #[test]
+    fn highlight_does_not_match_inside_entities() {
+        assert_eq!(highlight_in_html("<p>&amp;</p>", "amp"), "<p>&amp;</p>");
+        assert_eq!(highlight_in_html("<p>fish &chips;</p>", "&chips"), "<p>fish <mark class=\"find-match\">&chips</mark>;</p>");
+    }
+}
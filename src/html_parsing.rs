@@ -1,197 +1,631 @@
+use std::collections::HashSet;
+
+use im_rc::Vector;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// Deeper tags are kept as text, models do not nest this much and it keeps the recursion bounded
+const MAX_DEPTH:usize = 32;
+/// Longest opening tag accepted, so a stray "<" in prose does not swallow a whole paragraph
+const MAX_TAG_LEN:usize = 512;
+
+/// Tags of the dialect used by the backend and the models in AI parts
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum TagKind {
+    Think,
+    Call,
+    Tool,
+    Outputs,
+    AutomaticMemory,
+    Response,
+    CurrentTime,
+    Other
+}
+
+impl TagKind {
+    pub fn from_name(name:&str) -> Self {
+        match name {
+            "think" => Self::Think,
+            "call" => Self::Call,
+            "tool" => Self::Tool,
+            "outputs" => Self::Outputs,
+            "automatic_memory" => Self::AutomaticMemory,
+            "response" => Self::Response,
+            "current_time" => Self::CurrentTime,
+            _ => Self::Other
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
 pub struct ParsedHtml {
-    pub children:Vec<HtmlNode>
+    pub children:Vec<HtmlNode>,
+    /// End of the input that may be the beginning of a tag still being streamed
+    pub pending:String
 }
 
 impl ParsedHtml {
     pub fn has_elements(&self) -> bool {
-        for child in &self.children {
-            if let HtmlNode::Element { name, content, children } = child {
-                return true
-            }
-        }
-        false
+        self.elements().next().is_some()
     }
-    pub fn get_first_element(&self) -> Option<HtmlNode> {
-
-        for child in &self.children {
-            if let HtmlNode::Element { name, content, children } = child {
-                return Some(child.clone())
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| {
+            match child {
+                HtmlNode::Element(element) => Some(element),
+                HtmlNode::Text(_) => None
             }
-        }
-        None
+        })
+    }
+    pub fn get_first_element(&self) -> Option<&Element> {
+        self.elements().next()
+    }
+    pub fn first_of_kind(&self, kind:TagKind) -> Option<&Element> {
+        self.elements().find(|element| {element.kind == kind})
     }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub enum HtmlNode {
     Text(String),
-    Element{
-        name:String,
-        content:String,
-        children:ParsedHtml
-    }
-}
-
-#[derive(Clone, PartialEq, Eq)]
-pub enum ParserState {
-    Start,
-    ReadingText,
-    ParsingTagName{name:Vec<char>},
-    SearchingTagNameEnd{tag_name:Vec<char>, total_len:usize, open_counter:usize},
-    SearchingTagEnd{tag_name:Vec<char>, total_start_len:usize, open_counter:usize},
-    ParsingTagClose{tag_name:Vec<char>, total_start_len:usize, open_counter:usize},
-    ParsingInsideTagOpening{tag_name:Vec<char>, total_start_len:usize, open_counter:usize, actual_name:Vec<char>},
-    ParsingTagEnd{tag_name:Vec<char>, actual_name:Vec<char>, total_start_len:usize, open_counter:usize},
-}
-
-impl ParserState {
-    pub fn next_state(self, cur_char:char) -> Self {
-        match self {
-            Self::Start => match cur_char {
-                '<' => Self::ParsingTagName{name:Vec::with_capacity(16)},
-                _ => Self::ReadingText
-            },
-            Self::ReadingText => match cur_char {
-                '<' => Self::ParsingTagName{name:Vec::with_capacity(16)},
-                _ => Self::ReadingText
-            },
-            Self::ParsingTagName{name:current_name} => match cur_char {
-                '>' => Self::SearchingTagEnd{total_start_len:current_name.len(), tag_name:current_name, open_counter:0},
-                ' ' => Self::SearchingTagNameEnd{total_len:current_name.len(), tag_name:current_name, open_counter:0},
-                '\n' => Self::Start,
-                _ => Self::ParsingTagName{name:add_to_vec(current_name, cur_char)}
-            },
-            Self::SearchingTagNameEnd { tag_name, total_len, open_counter } => match cur_char {
-                '>' => Self::SearchingTagEnd { tag_name, total_start_len:total_len, open_counter },
-                '\n' => if open_counter > 0 {
-                    Self::SearchingTagEnd { tag_name, total_start_len:total_len, open_counter }
-                }
-                else {
-                    Self::Start
-                },
-                _ => Self::SearchingTagNameEnd { tag_name, total_len:total_len + 1, open_counter }
+    Element(Element)
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Element {
+    pub kind:TagKind,
+    pub name:String,
+    pub attributes:Vec<(String, String)>,
+    /// Raw text between the opening and the closing tag
+    pub content:String,
+    pub children:ParsedHtml,
+    /// False while the closing tag has not been streamed yet
+    pub closed:bool
+}
+
+impl Element {
+    pub fn attribute(&self, name:&str) -> Option<&str> {
+        self.attributes.iter().find(|(attribute, _)| {attribute == name}).map(|(_, value)| {value.as_str()})
+    }
+    /// Text of the element without its child tags
+    pub fn text(&self) -> String {
+        self.children.children.iter().filter_map(|child| {
+            match child {
+                HtmlNode::Text(text) => Some(text.as_str()),
+                HtmlNode::Element(_) => None
             }
-            Self::SearchingTagEnd { tag_name, total_start_len, open_counter } => match cur_char {
-                '<' => Self::ParsingTagClose { tag_name, total_start_len, open_counter },
-                _ => Self::SearchingTagEnd { tag_name, total_start_len, open_counter }
+        }).collect()
+    }
+}
+
+enum TagScan<T> {
+    Complete(T),
+    /// The input ends inside something that can still become a tag
+    Incomplete,
+    NotATag
+}
+
+struct OpeningTag {
+    name:String,
+    attributes:Vec<(String, String)>,
+    self_closing:bool,
+    len:usize
+}
+
+fn is_name_start(character:char) -> bool {
+    character.is_ascii_alphabetic() || character == '_'
+}
+
+fn is_name_char(character:char) -> bool {
+    character.is_ascii_alphanumeric() || matches!(character, '_' | '-' | ':' | '.')
+}
+
+/// Reads a name starting at `start`, returns its end
+fn scan_name(input:&str, start:usize) -> usize {
+    input[start..].char_indices().find(|(_, character)| {!is_name_char(*character)}).map(|(i, _)| {start + i}).unwrap_or(input.len())
+}
+
+fn skip_whitespace(input:&str, start:usize) -> usize {
+    input[start..].char_indices().find(|(_, character)| {!character.is_whitespace()}).map(|(i, _)| {start + i}).unwrap_or(input.len())
+}
+
+/// `rest` starts with "<"
+fn scan_opening_tag(rest:&str) -> TagScan<OpeningTag> {
+    let mut cut = rest.len().min(MAX_TAG_LEN);
+    while !rest.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let input = &rest[..cut];
+    let truncated = input.len() < rest.len();
+    let incomplete = || {if truncated {TagScan::NotATag} else {TagScan::Incomplete}};
+    match input[1..].chars().next() {
+        Some(character) if is_name_start(character) => (),
+        Some(_) => return TagScan::NotATag,
+        None => return incomplete()
+    }
+    let name_end = scan_name(input, 1);
+    let name = input[1..name_end].to_string();
+    let mut attributes = Vec::new();
+    let mut pos = name_end;
+    loop {
+        let after_space = skip_whitespace(input, pos);
+        let Some(character) = input[after_space..].chars().next() else {
+            return incomplete()
+        };
+        match character {
+            '>' => return TagScan::Complete(OpeningTag { name, attributes, self_closing: false, len: after_space + 1 }),
+            '/' => return match input[after_space + 1..].chars().next() {
+                Some('>') => TagScan::Complete(OpeningTag { name, attributes, self_closing: true, len: after_space + 2 }),
+                Some(_) => TagScan::NotATag,
+                None => incomplete()
             },
-            Self::ParsingTagClose { tag_name, total_start_len, open_counter } => match cur_char {
-                '/' => Self::ParsingTagEnd { tag_name, actual_name: Vec::with_capacity(16), total_start_len, open_counter },
-                _ => Self::ParsingInsideTagOpening { tag_name, total_start_len, open_counter, actual_name: vec![cur_char] }
-            }
-            Self::ParsingTagEnd { tag_name, actual_name, total_start_len, open_counter } => match cur_char {
-                '>' => if tag_name == actual_name {
-                    if open_counter == 0 {
-                        Self::Start
-                    }
-                    else {
-                        Self::SearchingTagEnd { tag_name, total_start_len, open_counter:open_counter-1 }
-                    }
-                    // must also handle newlines
-                }
-                else {
-                    Self::SearchingTagEnd { tag_name, total_start_len, open_counter }
+            // attributes need some space before them
+            _ if after_space == pos => return TagScan::NotATag,
+            _ if !is_name_start(character) => return TagScan::NotATag,
+            _ => ()
+        }
+        let attribute_end = scan_name(input, after_space);
+        let attribute = input[after_space..attribute_end].to_string();
+        let after_attribute = skip_whitespace(input, attribute_end);
+        match input[after_attribute..].chars().next() {
+            Some('=') => {
+                let value_start = skip_whitespace(input, after_attribute + 1);
+                match input[value_start..].chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => match input[value_start + 1..].find(quote) {
+                        Some(length) => {
+                            attributes.push((attribute, input[value_start + 1..value_start + 1 + length].to_string()));
+                            pos = value_start + length + 2;
+                        },
+                        None => return incomplete()
+                    },
+                    Some('>') | Some('<') => return TagScan::NotATag,
+                    Some(_) => {
+                        let value_end = input[value_start..].char_indices().find(|(_, character)| {character.is_whitespace() || *character == '>' || *character == '<'}).map(|(i, _)| {value_start + i}).unwrap_or(input.len());
+                        if value_end == input.len() {
+                            return incomplete()
+                        }
+                        attributes.push((attribute, input[value_start..value_end].to_string()));
+                        pos = value_end;
+                    },
+                    None => return incomplete()
                 }
-                ' ' => Self::SearchingTagEnd { tag_name, total_start_len, open_counter },
-                _ => Self::ParsingTagEnd { tag_name, actual_name:add_to_vec(actual_name, cur_char), total_start_len, open_counter }
             },
-            Self::ParsingInsideTagOpening { tag_name, total_start_len, open_counter, actual_name } => match cur_char {
-                '>' => if tag_name == actual_name {
-                    // something fishy here because of the space
-                    Self::SearchingTagEnd { tag_name, total_start_len, open_counter:open_counter+1 }
-                }
-                else {
-                    Self::SearchingTagEnd { tag_name, total_start_len, open_counter }
-                },
-                ' ' => if tag_name == actual_name {
-                    Self::SearchingTagNameEnd { tag_name, total_len: total_start_len, open_counter:open_counter + 1 }
-                }
-                else {
-                    Self::SearchingTagEnd { tag_name, total_start_len, open_counter }
-                },
-                _ =>  Self::ParsingInsideTagOpening { tag_name, total_start_len, open_counter, actual_name:add_to_vec(actual_name, cur_char) }
-                
-            }
-
+            Some(_) => {
+                attributes.push((attribute, String::new()));
+                pos = attribute_end;
+            },
+            None => return incomplete()
         }
     }
-    pub fn important_change(&self, new_state:&Self) -> bool {
-        match self {
-            Self::Start => false,
-            Self::ReadingText => match new_state {
-                Self::ReadingText => false,
-                _ => true,
-            },
-            Self::ParsingTagEnd { tag_name, actual_name,total_start_len, open_counter } => match new_state {
-                Self::Start => true,
-                _ => false,
-            },
-            _ => false
+}
+
+/// `rest` starts with "</", gives the name and the length of the closing tag
+fn scan_closing_tag(rest:&str) -> TagScan<(String, usize)> {
+    match rest[2..].chars().next() {
+        Some(character) if is_name_start(character) => (),
+        Some(_) => return TagScan::NotATag,
+        None => return TagScan::Incomplete
+    }
+    let name_end = scan_name(rest, 2);
+    let end = skip_whitespace(rest, name_end);
+    match rest[end..].chars().next() {
+        Some('>') => TagScan::Complete((rest[2..name_end].to_string(), end + 1)),
+        Some(_) => TagScan::NotATag,
+        None => TagScan::Incomplete
+    }
+}
+
+/// Length of a fenced code block starting at `rest` and whether it is closed, unclosed ones go to the end of the input
+fn fenced_code_len(rest:&str) -> Option<(usize, bool)> {
+    let indent = rest.len() - rest.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None
+    }
+    let fence_char = rest[indent..].chars().next().filter(|character| {*character == '`' || *character == '~'})?;
+    let fence_len = rest[indent..].len() - rest[indent..].trim_start_matches(fence_char).len();
+    if fence_len < 3 {
+        return None
+    }
+    let mut line_start = match rest.find('\n') {
+        Some(end) => end + 1,
+        None => return Some((rest.len(), false))
+    };
+    while line_start < rest.len() {
+        let line_end = rest[line_start..].find('\n').map(|end| {line_start + end}).unwrap_or(rest.len());
+        let line = rest[line_start..line_end].trim_start_matches(' ');
+        let closing_len = line.len() - line.trim_start_matches(fence_char).len();
+        if closing_len >= fence_len && line[closing_len..].trim().is_empty() && rest[line_start..line_end].len() - line.len() <= 3 {
+            return Some((line_end, true))
         }
+        line_start = line_end + 1;
     }
-    pub fn tag_name(&self) -> (Vec<char>, usize) {
-        match self {
-            Self::ParsingTagClose { tag_name,total_start_len, open_counter } => (tag_name.clone(), *total_start_len),
-            Self::ParsingTagEnd { tag_name, actual_name,total_start_len, open_counter } => (tag_name.clone(), *total_start_len),
-            Self::SearchingTagEnd { tag_name, total_start_len, open_counter } => (tag_name.clone(), *total_start_len),
-            _ => panic!("Impossible")
+    Some((rest.len(), false))
+}
+
+/// Length of an inline code span starting at `rest`, None when its backticks are never closed
+fn inline_code_len(rest:&str) -> Option<usize> {
+    let ticks = rest.len() - rest.trim_start_matches('`').len();
+    let mut search = ticks;
+    while let Some(found) = rest[search..].find('`') {
+        let start = search + found;
+        let run = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        if run == ticks {
+            return Some(start + run)
         }
+        search = start + run;
     }
+    None
 }
 
-fn add_to_vec(mut vector:Vec<char>, new:char) -> Vec<char> {
-    vector.push(new);
-    vector
+struct Parser<'a> {
+    input:&'a str,
+    pos:usize,
+    open:Vec<String>,
+    /// Unknown tags already found to be unclosed, so backtracking over them stays linear
+    unclosed_unknown:HashSet<usize>,
+    /// Start of the first unknown tag, code span or code block left unclosed, more input can still close it
+    /// and change how everything after it is parsed
+    first_unsettled:Option<usize>,
+    /// Where each top level node ends, used to know what an incremental parse can keep
+    top_level_ends:Vec<usize>
 }
 
-fn chars_to_string(chars:&[char]) -> String {
-    let mut str = String::with_capacity(chars.len());
-    for char in chars {
-        str.push(*char);
-    }
-    str
+struct ParsedChildren {
+    nodes:Vec<HtmlNode>,
+    closed:bool,
+    content_end:usize,
+    pending:String
 }
 
-pub fn parse_html(chars:&[char], max_depth:usize) -> ParsedHtml {
-    if max_depth > 0 {
-        let mut start_ind: usize = 0;
-        let mut end_ind = 0;
-        if chars.len() > 0 {
-            let mut children = Vec::with_capacity(4);
-            let mut state = ParserState::Start;
-            while end_ind < chars.len() {
-                let new_state = state.clone().next_state(chars[end_ind]);
-                if state.important_change(&new_state) {
-                    if let ParserState::ReadingText = state.clone() {
-                        let str = chars_to_string(&chars[start_ind..end_ind]);
-                        children.push(HtmlNode::Text(str));
+impl<'a> Parser<'a> {
+    fn at_line_start(&self, content_start:usize, starts_on_new_line:bool) -> bool {
+        (self.pos == content_start && starts_on_new_line) || self.input[..self.pos].ends_with('\n')
+    }
+
+    fn unsettled_from(&mut self, start:usize) {
+        self.first_unsettled = Some(self.first_unsettled.map_or(start, |first| {first.min(start)}));
+    }
+
+    fn push_node(&mut self, nodes:&mut Vec<HtmlNode>, node:HtmlNode) {
+        nodes.push(node);
+        if self.open.is_empty() {
+            self.top_level_ends.push(self.pos);
+        }
+    }
+
+    fn flush_text(&mut self, nodes:&mut Vec<HtmlNode>, text:&mut String, end:usize) {
+        if !text.is_empty() {
+            nodes.push(HtmlNode::Text(std::mem::take(text)));
+            if self.open.is_empty() {
+                self.top_level_ends.push(end);
+            }
+        }
+    }
+
+    fn parse_children(&mut self, starts_on_new_line:bool) -> ParsedChildren {
+        let content_start = self.pos;
+        let mut nodes = Vec::with_capacity(4);
+        let mut text = String::new();
+        let input = self.input;
+        while self.pos < input.len() {
+            let rest = &input[self.pos..];
+            if self.at_line_start(content_start, starts_on_new_line) && let Some((mut length, closed)) = fenced_code_len(rest) {
+                // code blocks are kept as text, tags inside them are only shown
+                if !closed {
+                    self.unsettled_from(self.pos);
+                    if let Some(name) = self.open.last() && let Some(close) = rest.find(&format!("</{name}>")) {
+                        // a fence left open by the model still ends with its element
+                        length = close;
                     }
-                    else {
-                        let (tag_name, start_tag_len) = state.tag_name();
-                        let slice = &chars[(start_ind+start_tag_len+1)..(end_ind-tag_name.len()-2)];
-                        let inside_str = chars_to_string(slice);
-                        let parsed = parse_html(slice, max_depth-1);
-                        children.push(HtmlNode::Element { name: chars_to_string(&tag_name), content: inside_str, children: parsed });
+                }
+                text.push_str(&rest[..length]);
+                self.pos += length;
+            }
+            else if rest.starts_with('`') {
+                let length = match inline_code_len(rest) {
+                    Some(length) => length,
+                    None => {
+                        self.unsettled_from(self.pos);
+                        rest.len() - rest.trim_start_matches('`').len()
+                    }
+                };
+                text.push_str(&rest[..length]);
+                self.pos += length;
+            }
+            else if rest.starts_with("</") {
+                match scan_closing_tag(rest) {
+                    TagScan::Complete((name, length)) => {
+                        if self.open.last() == Some(&name) {
+                            let content_end = self.pos;
+                            self.flush_text(&mut nodes, &mut text, content_end);
+                            self.pos += length;
+                            return ParsedChildren { nodes, closed: true, content_end, pending: String::new() }
+                        }
+                        else if self.open.contains(&name) {
+                            // closes a parent, this element was never closed
+                            let content_end = self.pos;
+                            self.flush_text(&mut nodes, &mut text, content_end);
+                            return ParsedChildren { nodes, closed: false, content_end, pending: String::new() }
+                        }
+                        else {
+                            text.push_str(&rest[..length]);
+                            self.pos += length;
+                        }
+                    },
+                    TagScan::Incomplete => {
+                        let content_end = self.pos;
+                        self.flush_text(&mut nodes, &mut text, content_end);
+                        self.pos = self.input.len();
+                        return ParsedChildren { nodes, closed: false, content_end, pending: rest.to_string() }
+                    },
+                    TagScan::NotATag => {
+                        text.push('<');
+                        self.pos += 1;
                     }
-                    start_ind = end_ind + 1;
                 }
-                state = new_state;
-                end_ind += 1;
             }
-            if end_ind != start_ind {
-                let str = chars_to_string(&chars[start_ind..end_ind]);
-                children.push(HtmlNode::Text(str));
+            else if rest.starts_with('<') {
+                match scan_opening_tag(rest) {
+                    TagScan::Complete(tag) if self.open.len() < MAX_DEPTH && !self.unclosed_unknown.contains(&self.pos) => {
+                        let tag_start = self.pos;
+                        let kind = TagKind::from_name(&tag.name);
+                        if tag.self_closing {
+                            self.flush_text(&mut nodes, &mut text, tag_start);
+                            self.pos += tag.len;
+                            self.push_node(&mut nodes, HtmlNode::Element(Element { kind, name: tag.name, attributes: tag.attributes, content: String::new(), children: ParsedHtml::default(), closed: true }));
+                            continue;
+                        }
+                        self.pos += tag.len;
+                        let element_start = self.pos;
+                        self.open.push(tag.name.clone());
+                        let inner = self.parse_children(true);
+                        self.open.pop();
+                        if !inner.closed && kind == TagKind::Other {
+                            // unknown tags only count once closed, "a <b c> d" in prose stays text
+                            if self.pos == input.len() {
+                                self.unsettled_from(tag_start);
+                            }
+                            self.unclosed_unknown.insert(tag_start);
+                            self.pos = tag_start + 1;
+                            text.push('<');
+                            continue;
+                        }
+                        self.flush_text(&mut nodes, &mut text, tag_start);
+                        let at_end = !inner.pending.is_empty();
+                        self.push_node(&mut nodes, HtmlNode::Element(Element {
+                            kind,
+                            name: tag.name,
+                            attributes: tag.attributes,
+                            content: self.input[element_start..inner.content_end].to_string(),
+                            children: ParsedHtml { children: inner.nodes, pending: inner.pending },
+                            closed: inner.closed
+                        }));
+                        if at_end {
+                            return ParsedChildren { nodes, closed: false, content_end: self.input.len(), pending: String::new() }
+                        }
+                    },
+                    TagScan::Incomplete => {
+                        let content_end = self.pos;
+                        self.flush_text(&mut nodes, &mut text, content_end);
+                        self.pos = self.input.len();
+                        return ParsedChildren { nodes, closed: false, content_end, pending: rest.to_string() }
+                    },
+                    _ => {
+                        text.push('<');
+                        self.pos += 1;
+                    }
+                }
+            }
+            else {
+                let length = rest.find(['<', '`', '\n']).map(|found| {if found == 0 {1} else {found}}).unwrap_or(rest.len());
+                text.push_str(&rest[..length]);
+                self.pos += length;
             }
-            ParsedHtml { children }
+        }
+        let content_end = self.input.len();
+        self.flush_text(&mut nodes, &mut text, content_end);
+        ParsedChildren { nodes, closed: false, content_end, pending: String::new() }
+    }
+}
+
+/// Also gives where each top level node ends and up to where the parse cannot change when more input comes
+fn parse_with_ends(input:&str, starts_on_new_line:bool) -> (ParsedHtml, Vec<usize>, usize) {
+    let mut parser = Parser { input, pos: 0, open: Vec::new(), unclosed_unknown: HashSet::new(), first_unsettled: None, top_level_ends: Vec::new() };
+    let parsed = parser.parse_children(starts_on_new_line);
+    (ParsedHtml { children: parsed.nodes, pending: parsed.pending }, parser.top_level_ends, parser.first_unsettled.unwrap_or(input.len()))
+}
+
+/// Parses the tags of an AI part, unclosed tags at the end of a streamed part are kept open instead of being dropped
+pub fn parse_tags(input:&str) -> ParsedHtml {
+    parse_with_ends(input, true).0
+}
+
+/// Parses a streamed text chunk by chunk, only reparsing what comes after the last finished top level node.
+/// Only that end of the text is kept, so that copying the parser of a long response stays cheap
+#[derive(Clone, PartialEq, Default)]
+pub struct IncrementalParser {
+    committed:Vector<HtmlNode>,
+    /// Whether the text of the finished nodes ends a line, which the text after them starts on
+    committed_ends_line:bool,
+    tail_text:String,
+    tail:ParsedHtml
+}
+
+impl IncrementalParser {
+    pub fn push(&mut self, chunk:&str) {
+        self.tail_text.push_str(chunk);
+        let starts_on_new_line = self.committed.is_empty() || self.committed_ends_line;
+        let (tail, ends, settled) = parse_with_ends(&self.tail_text, starts_on_new_line);
+        // every top level node but the last is followed by something, so it cannot change anymore, unless something
+        // left unclosed before its end may still be closed and take it in
+        let mut finished = ends[..tail.children.len().saturating_sub(1).min(ends.len())].iter().take_while(|end| {**end <= settled}).count();
+        // what comes after may still turn into text and join the text before it
+        if settled < self.tail_text.len() && finished > 0 && matches!(tail.children[finished - 1], HtmlNode::Text(_)) {
+            finished -= 1;
+        }
+        if finished > 0 {
+            let mut children = tail.children;
+            let rest = children.split_off(finished);
+            self.committed.extend(children);
+            let committed_len = ends[finished - 1];
+            self.committed_ends_line = self.tail_text[..committed_len].ends_with('\n');
+            self.tail_text.drain(..committed_len);
+            self.tail = ParsedHtml { children: rest, pending: tail.pending };
         }
         else {
-            ParsedHtml { children: vec![] }
+            self.tail = tail;
+        }
+    }
+    pub fn parsed(&self) -> ParsedHtml {
+        let mut children = Vec::with_capacity(self.committed.len() + self.tail.children.len());
+        children.extend(self.committed.iter().cloned());
+        children.extend(self.tail.children.iter().cloned());
+        ParsedHtml { children, pending: self.tail.pending.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text:&str) -> HtmlNode {
+        HtmlNode::Text(text.to_string())
+    }
+
+    fn depth(parsed:&ParsedHtml) -> usize {
+        parsed.elements().map(|element| {1 + depth(&element.children)}).max().unwrap_or(0)
+    }
+
+    /// Parses chunk by chunk, checking the result against a full parse after every chunk
+    fn check_incremental(chunks:&[&str]) {
+        let mut parser = IncrementalParser::default();
+        let mut streamed = String::new();
+        for chunk in chunks {
+            streamed.push_str(chunk);
+            parser.push(chunk);
+            assert_eq!(parser.parsed(), parse_tags(&streamed), "after {streamed:?}");
         }
     }
-    else {
-        ParsedHtml { children: vec![] }
+
+    #[test]
+    fn unclosed_tags_stay_open_while_streamed() {
+        let parsed = parse_tags("a <think>partial");
+        assert_eq!(parsed.children[0], text("a "));
+        let think = parsed.first_of_kind(TagKind::Think).unwrap();
+        assert!(!think.closed);
+        assert_eq!(think.text(), "partial");
+
+        let parsed = parse_tags("done <thi");
+        assert_eq!(parsed.children, vec![text("done ")]);
+        assert_eq!(parsed.pending, "<thi");
+    }
+
+    #[test]
+    fn unknown_tags_only_count_once_closed() {
+        assert_eq!(parse_tags("a <b c> d").children, vec![text("a <b c> d")]);
+        let parsed = parse_tags("a <b>bold</b>");
+        assert_eq!(parsed.get_first_element().unwrap().kind, TagKind::Other);
+        assert!(parsed.get_first_element().unwrap().closed);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reads_attributes() {
+        let parsed = parse_tags("<call tool=\"search\" id=3 single='x y' flag>q</call>");
+        let call = parsed.first_of_kind(TagKind::Call).unwrap();
+        assert_eq!(call.attribute("tool"), Some("search"));
+        assert_eq!(call.attribute("id"), Some("3"));
+        assert_eq!(call.attribute("single"), Some("x y"));
+        assert_eq!(call.attribute("flag"), Some(""));
+        assert_eq!(call.content, "q");
+        assert_eq!(parse_tags("<a b=>c</a>").children, vec![text("<a b=>c</a>")]);
+    }
+
+    #[test]
+    fn code_keeps_its_tags_as_text() {
+        let parsed = parse_tags("```\n<think>no</think>\n```\n<think>yes</think>");
+        assert_eq!(parsed.elements().count(), 1);
+        assert_eq!(parsed.first_of_kind(TagKind::Think).unwrap().text(), "yes");
+        assert!(!parse_tags("use `<think>` here").has_elements());
+        assert!(!parse_tags("if a < b && c > d").has_elements());
+    }
+
+    #[test]
+    fn open_fence_ends_with_its_element() {
+        let parsed = parse_tags("<outputs>\n```\nx < y\n</outputs>after");
+        let outputs = parsed.first_of_kind(TagKind::Outputs).unwrap();
+        assert!(outputs.closed);
+        assert_eq!(parsed.children.last(), Some(&text("after")));
+    }
+
+    #[test]
+    fn nests_elements() {
+        let parsed = parse_tags("<call><tool>web</tool>query</call>");
+        let call = parsed.first_of_kind(TagKind::Call).unwrap();
+        assert_eq!(call.children.first_of_kind(TagKind::Tool).unwrap().text(), "web");
+        assert_eq!(call.text(), "query");
+        // a closing tag of the parent ends the children left open
+        let parsed = parse_tags("<call><tool>web</call>");
+        let call = parsed.first_of_kind(TagKind::Call).unwrap();
+        assert!(call.closed);
+        assert!(!call.children.first_of_kind(TagKind::Tool).unwrap().closed);
+    }
+
+    #[test]
+    fn depth_and_tag_length_are_bounded() {
+        let nested = "<think>".repeat(MAX_DEPTH + 8) + "x";
+        assert_eq!(depth(&parse_tags(&nested)), MAX_DEPTH);
+
+        let long_tag = format!("<call a=\"{}\">x</call>", "v".repeat(MAX_TAG_LEN));
+        assert!(!parse_tags(&long_tag).has_elements());
+        let long_prose = format!("a <b {}", "word ".repeat(MAX_TAG_LEN));
+        let parsed = parse_tags(&long_prose);
+        assert!(parsed.pending.is_empty());
+        assert!(!parsed.has_elements());
+    }
+
+    #[test]
+    fn incremental_parse_matches_full_parse() {
+        check_incremental(&["a <thi", "nk>b", "</think> c", " <call><tool>x</tool>", "y</call>"]);
+        check_incremental(&["```\n<think>", "</think>\n``", "`\n<response>ok</response>"]);
+        check_incremental(&["text\n", "<outputs>1</outputs>\n", "more"]);
+    }
+
+    #[test]
+    fn incremental_parse_waits_for_unknown_tags() {
+        check_incremental(&["<b>x <think>y</think> z", "</b>"]);
+        check_incremental(&["a <b c> d <think>e</think>", " f"]);
+    }
+
+    /// Small xorshift generator, the sequences only need to be the same on every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, bound:usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn incremental_parse_matches_full_parse_on_random_chunks() {
+        const FRAGMENTS:[&str; 24] = [
+            "<think>", "</think>", "<call>", "</call>", "<tool>", "</tool>", "<outputs>", "</outputs>", "<b>", "</b>", "<x y=\"1\">", "</x>",
+            "<br/>", "<", ">", "</", "`", "```", "\n", "text", " ", "é", "a < b", "<response"
+        ];
+        let mut random = Random(0x2545F4914F6CDD1D);
+        for _ in 0..500 {
+            let input = (0..random.next(20) + 1).map(|_| {FRAGMENTS[random.next(FRAGMENTS.len())]}).collect::<String>();
+            let mut parser = IncrementalParser::default();
+            let mut end = 0;
+            while end < input.len() {
+                let start = end;
+                end = (end + random.next(6) + 1).min(input.len());
+                while !input.is_char_boundary(end) {
+                    end += 1;
+                }
+                parser.push(&input[start..end]);
+                assert_eq!(parser.parsed(), parse_tags(&input[..end]), "after {:?} of {input:?}", &input[..end]);
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+
+use gloo_utils::format::JsValueSerdeExt;
+use proxima_backend::database::{DatabaseItem, DatabaseItemID, ProxDatabase, chats::Chat, devices::DeviceID, context::{ContextData, ContextPart, ContextPosition, WholeContext}};
+use proxima_backend::web_payloads::DBPayload;
+use proxima_frontend_shared::import::{ImportedChat, ImportedRole};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Html, MouseEvent, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::{alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction}, app::{DatabaseAction, DatabaseState, ProximaState, make_db_request, try_invoke}, db_sync::get_delta_for_add, files::pick_file, templates::without_template_marker};
+
+const PREVIEWED_CHATS:usize = 10;
+
+#[derive(Serialize, Deserialize)]
+struct ImportArgs {
+    path:PathBuf
+}
+
+pub fn imported_to_context(imported:&ImportedChat) -> WholeContext {
+    let parts = imported.messages.iter().map(|message| {
+        let position = match message.role {
+            ImportedRole::User => ContextPosition::User,
+            ImportedRole::AI => ContextPosition::AI,
+            ImportedRole::System => ContextPosition::System,
+            ImportedRole::Tool => ContextPosition::Tool(message.tool_name.clone().unwrap_or("tool".to_string())),
+        };
+        // an imported chat is never read as a template
+        let mut part = ContextPart::new(vec![ContextData::Text(without_template_marker(&message.text).to_string())], position);
+        if let Some(date) = message.date {
+            part.set_date(date);
+        }
+        part
+    }).collect();
+    WholeContext::new(parts)
+}
+
+pub fn imported_to_chat(db:&ProxDatabase, imported:&ImportedChat, device_id:DeviceID, tag:Option<usize>, access_mode:usize) -> Chat {
+    let mut chat = db.chats.create_possible_chat(imported_to_context(imported), None, device_id, None);
+    chat.chat_title = imported.title.clone();
+    if let Some(date) = imported.updated_at.or(imported.messages.iter().filter_map(|message| {message.date}).max()).or(imported.created_at) {
+        chat.latest_message = date;
+    }
+    if let Some(tag) = tag {
+        chat.tags.insert(tag);
+    }
+    chat.access_modes.insert(access_mode);
+    chat
+}
+
+#[function_component(ImportPanel)]
+pub fn import_panel() -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let tag_ref = use_node_ref();
+    let parsed = use_state_eq(|| {None::<Vec<ImportedChat>>});
+    let status = use_state_eq(String::new);
+    let chosen_file = use_state_eq(|| {None::<PathBuf>});
+
+    let preview_callback = {
+        let parsed = parsed.clone();
+        let status = status.clone();
+        let chosen_file = chosen_file.clone();
+        let alerts_state = alerts_state.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let parsed = parsed.clone();
+            let status = status.clone();
+            let chosen_file = chosen_file.clone();
+            let alerts_state = alerts_state.clone();
+            spawn_local(async move {
+                let path = match pick_file(&["json", "jsonl"]).await {
+                    Some(path) => path,
+                    None => return
+                };
+                chosen_file.set(Some(path.clone()));
+                let args = serde_wasm_bindgen::to_value(&ImportArgs { path }).unwrap();
+                match try_invoke("parse_chat_import", args).await.map(|chats| {chats.into_serde::<Vec<ImportedChat>>()}) {
+                    Ok(Ok(chats)) => {
+                        status.set(String::new());
+                        parsed.set(Some(chats));
+                    },
+                    error => {
+                        parsed.set(None);
+                        let reason = match error {
+                            Err(error) => error.as_string().unwrap_or_default(),
+                            _ => String::new()
+                        };
+                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Could not read the import file : {reason}"))));
+                    }
+                }
+            });
+        })
+    };
+
+    let import_callback = {
+        let proxima_state = proxima_state.clone();
+        let db_state = db_state.clone();
+        let tag_ref = tag_ref.clone();
+        let parsed = parsed.clone();
+        let status = status.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let chats = match &*parsed {
+                Some(chats) => chats.clone(),
+                None => return
+            };
+            let tag = tag_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse::<usize>().ok();
+            let proxima_state = proxima_state.clone();
+            let db_state = db_state.clone();
+            let parsed = parsed.clone();
+            let status = status.clone();
+            parsed.set(None);
+            spawn_local(async move {
+                let total = chats.len();
+                // the handle does not see the chats added here, so each one takes the id after the one the server gave the last
+                let mut next_id = None;
+                for (i, imported) in chats.iter().enumerate() {
+                    status.set(format!("Importing chat {} / {total}...", i + 1));
+                    let mut chat = imported_to_chat(&db_state.db, imported, proxima_state.device_id, tag, db_state.cursors.chosen_access_mode);
+                    if let Some(id) = next_id {
+                        chat.id = id;
+                    }
+                    let (delta, new_id, new_item) = get_delta_for_add(
+                        DatabaseItemID::Chat(chat.id),
+                        DatabaseItem::Chat(chat),
+                        async |request| {make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request }, proxima_state.chat_url.clone()).await.map(|response| {response.reply})}
+                    ).await;
+                    if let DatabaseItemID::Chat(id) = &new_id {
+                        next_id = Some(id + 1);
+                    }
+                    db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+                }
+                status.set(format!("Imported {total} chat(s)"));
+            });
+        })
+    };
+
+    let tag_options = db_state.db.tags.get_tags().iter().map(|(id, tag)| {
+        html!(<option value={format!("{id}")}>{tag.get_name()}</option>)
+    }).collect::<Html>();
+
+    let preview = match &*parsed {
+        Some(chats) => {
+            let messages = chats.iter().map(|chat| {chat.messages.len()}).sum::<usize>();
+            let titles = chats.iter().take(PREVIEWED_CHATS).map(|chat| {
+                html!(
+                    <li>{format!("{} ({} messages)", chat.title.clone().unwrap_or("Untitled".to_string()), chat.messages.len())}</li>
+                )
+            }).collect::<Html>();
+            html!(
+                <div>
+                    <p>{format!("Dry run : {} chat(s), {messages} message(s) would be imported", chats.len())}</p>
+                    <ul>
+                        {titles}
+                        {if chats.len() > PREVIEWED_CHATS {html!(<li>{format!("... and {} more", chats.len() - PREVIEWED_CHATS)}</li>)} else {html!()}}
+                    </ul>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={import_callback}>{"Import"}</button>
+                </div>
+            )
+        },
+        None => html!()
+    };
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="label-input-combo">
+                <p class="standard-padding-margin-corners">{match &*chosen_file {
+                    Some(path) => format!("{}", path.display()),
+                    None => "No conversations.json or ShareGPT .json/.jsonl file chosen".to_string()
+                }}</p>
+                <select class="standard-padding-margin-corners" ref={tag_ref}>
+                    <option value="none">{"No tag"}</option>
+                    {tag_options}
+                </select>
+                <button class="mainapp-button standard-padding-margin-corners" onclick={preview_callback}>{"Choose file and preview"}</button>
+            </div>
+            {preview}
+            <p>{(*status).clone()}</p>
+        </div>
+    )
+}
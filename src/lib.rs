@@ -0,0 +1,34 @@
+#![feature(iter_intersperse)]
+#![feature(string_remove_matches)]
+pub mod app;
+pub mod db_sync;
+pub mod tabs;
+pub mod html_parsing;
+pub mod alerts;
+pub mod token_reorder;
+pub mod search;
+pub mod find_matches;
+pub mod export;
+pub mod files;
+pub mod dataset;
+pub mod local_store;
+pub mod ratings;
+pub mod context_parts;
+pub mod titles;
+pub mod context_inspector;
+pub mod tokenizer;
+pub mod compaction;
+pub mod import;
+pub mod stream_metrics;
+pub mod prompt_queue;
+pub mod drafts;
+pub mod slash_commands;
+pub mod code_blocks;
+pub mod math;
+pub mod tool_views;
+pub mod memories;
+pub mod render_cache;
+pub mod live_responses;
+pub mod chat_store;
+pub mod compare;
+pub mod templates;
@@ -0,0 +1,294 @@
+use std::ops::Deref;
+use std::rc::Rc;
+
+use chrono::{DateTime, Utc};
+use im_rc::Vector;
+use proxima_backend::database::chats::Chat;
+use proxima_backend::database::context::{ContextData, ContextPart};
+
+use crate::html_parsing::{IncrementalParser, ParsedHtml};
+use crate::stream_metrics::TagTimes;
+use crate::tokenizer::TokenizerKind;
+
+/// Parts streamed into a chat, kept out of the database until the response is over so that a token
+/// does not copy the whole chat
+#[derive(Clone, PartialEq, Default)]
+pub struct LiveResponse {
+    /// Whether the first part replaces the last part of the chat, when tokens go on after the chat was updated
+    continues_last:bool,
+    parts:Vector<LivePart>,
+    tokenizer:TokenizerKind,
+    /// Tokens streamed so far, counted as they arrive so the metrics do not count the whole text again
+    tokens:usize
+}
+
+#[derive(Clone, PartialEq)]
+struct LivePart {
+    start:Rc<ContextPart>,
+    added:Vector<ContextData>,
+    /// Length of the text of the part, where the next streamed text goes
+    text_len:usize,
+    tag_times:TagTimes,
+    /// Tags of the part, parsed as its text arrives
+    parser:IncrementalParser
+}
+
+impl LivePart {
+    fn new(start:ContextPart) -> Self {
+        let text = start.data_to_single_text();
+        let mut parser = IncrementalParser::default();
+        parser.push(&text);
+        Self { start: Rc::new(start), added: Vector::new(), text_len: text.len(), tag_times: TagTimes::default(), parser }
+    }
+    fn to_part(&self) -> ContextPart {
+        let mut part = (*self.start).clone();
+        for data in &self.added {
+            append_data(&mut part, data.clone());
+        }
+        part
+    }
+}
+
+/// A part of a chat as shown, the saved parts are borrowed from their chat instead of being copied
+#[derive(Clone)]
+pub enum ShownPart {
+    Saved(Rc<Chat>, usize),
+    Live(Rc<ContextPart>)
+}
+
+impl Deref for ShownPart {
+    type Target = ContextPart;
+    fn deref(&self) -> &ContextPart {
+        match self {
+            Self::Saved(chat, index) => &chat.context.get_parts()[*index],
+            Self::Live(part) => part
+        }
+    }
+}
+
+impl PartialEq for ShownPart {
+    /// Parts of the same copy of a chat are the same without comparing their content
+    fn eq(&self, other:&Self) -> bool {
+        match (self, other) {
+            (Self::Saved(chat, index), Self::Saved(other_chat, other_index)) if Rc::ptr_eq(chat, other_chat) && index == other_index => true,
+            _ => **self == **other
+        }
+    }
+}
+
+/// Text goes on the text it follows, anything else becomes new data of the part
+fn append_data(part:&mut ContextPart, data:ContextData) {
+    if let ContextData::Text(new_text) = &data && let Some(ContextData::Text(text)) = part.get_data_mut().last_mut() {
+        *text += new_text;
+    }
+    else {
+        part.add_data(data);
+    }
+}
+
+impl LiveResponse {
+    pub fn new(tokenizer:TokenizerKind) -> Self {
+        Self { continues_last: false, parts: Vector::new(), tokenizer, tokens: 0 }
+    }
+    /// Goes on with the last part already in the chat
+    pub fn continuing(chat:&Chat, tokenizer:TokenizerKind) -> Option<Self> {
+        let last = chat.context.get_parts().last()?;
+        Some(Self { continues_last: true, parts: Vector::unit(LivePart::new(last.clone())), tokenizer, tokens: 0 })
+    }
+    pub fn start_part(&mut self, part:ContextPart, now:DateTime<Utc>) {
+        let mut live = LivePart::new(part);
+        let text = live.start.data_to_single_text();
+        live.tag_times.mark(0, &text, now);
+        self.tokens += self.tokenizer.tokenizer().count(&text);
+        self.parts.push_back(live);
+    }
+    /// False when no part was started yet
+    pub fn add_data(&mut self, data:ContextData, now:DateTime<Utc>) -> bool {
+        match self.parts.back_mut() {
+            Some(part) => {
+                if let ContextData::Text(text) = &data {
+                    part.tag_times.mark(part.text_len, text, now);
+                    part.text_len += text.len();
+                    part.parser.push(text);
+                    self.tokens += self.tokenizer.tokenizer().count(text);
+                }
+                part.added.push_back(data);
+                true
+            },
+            None => false
+        }
+    }
+    /// Estimate of the tokens this response streamed, without what the part it goes on with already had
+    pub fn tokens(&self) -> usize {
+        self.tokens
+    }
+    /// Text this response streamed, without what the part it goes on with already had
+    pub fn streamed_text(&self) -> String {
+        self.parts.iter().enumerate().map(|(index, part)| {
+            if index == 0 && self.continues_last {
+                part.added.iter().filter_map(|data| {
+                    match data {
+                        ContextData::Text(text) => Some(text.as_str()),
+                        _ => None
+                    }
+                }).collect::<String>()
+            }
+            else {
+                part.to_part().data_to_single_text()
+            }
+        }).collect::<Vec<String>>().join("\n")
+    }
+    /// Parts the chat has more than in the database
+    pub fn added_parts(&self) -> usize {
+        self.parts.len() - usize::from(self.continues_last)
+    }
+    /// Tag times of the parts of the response, in order, the first one being the last part of the chat when it continues it
+    pub fn tag_times(&self) -> impl Iterator<Item = &TagTimes> {
+        self.parts.iter().map(|part| {&part.tag_times})
+    }
+    /// Index in the chat of the first part of the response, given how many parts the chat has in the database
+    pub fn first_part_index(&self, saved_parts:usize) -> usize {
+        saved_parts - usize::from(self.continues_last)
+    }
+    /// A part of the response, built from where it started and what was streamed into it since
+    pub fn part(&self, index:usize) -> Option<ContextPart> {
+        self.parts.get(index).map(LivePart::to_part)
+    }
+    /// Tags of a part of the response, without parsing its text again
+    pub fn parsed(&self, index:usize) -> Option<ParsedHtml> {
+        self.parts.get(index).map(|part| {part.parser.parsed()})
+    }
+    pub fn apply_to(&self, chat:&mut Chat) {
+        if self.continues_last {
+            chat.context.get_parts_mut().pop();
+        }
+        for part in &self.parts {
+            chat.context.add_part(part.to_part());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use proxima_backend::database::ProxDatabase;
+    use proxima_backend::database::context::{ContextPosition, WholeContext};
+
+    use crate::html_parsing::parse_tags;
+
+    use super::*;
+
+    fn text_part(text:&str, position:ContextPosition) -> ContextPart {
+        ContextPart::new(vec![ContextData::Text(text.to_string())], position)
+    }
+
+    fn chat(texts:&[&str]) -> Chat {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let parts = texts.iter().enumerate().map(|(i, text)| {
+            text_part(text, if i % 2 == 0 {ContextPosition::User} else {ContextPosition::AI})
+        }).collect();
+        db.chats.create_possible_chat(WholeContext::new(parts), None, 0, None)
+    }
+
+    fn texts(chat:&Chat) -> Vec<String> {
+        chat.context.get_parts().iter().map(|part| {part.data_to_single_text()}).collect()
+    }
+
+    #[test]
+    fn data_needs_a_started_part() {
+        let mut live = LiveResponse::default();
+        assert!(!live.add_data(ContextData::Text(String::from("lost")), Utc::now()));
+        assert_eq!(live.added_parts(), 0);
+    }
+
+    #[test]
+    fn started_parts_are_added_to_the_chat() {
+        let now = Utc::now();
+        let mut live = LiveResponse::default();
+        live.start_part(text_part("Hel", ContextPosition::AI), now);
+        assert!(live.add_data(ContextData::Text(String::from("lo")), now));
+        live.start_part(text_part("Second", ContextPosition::AI), now);
+        assert_eq!(live.added_parts(), 2);
+        assert_eq!(live.first_part_index(1), 1);
+        assert_eq!(live.part(0).map(|part| {part.data_to_single_text()}), Some(String::from("Hello")));
+        assert!(live.part(2).is_none());
+        assert_eq!(live.streamed_text(), "Hello\nSecond");
+
+        let mut chat = chat(&["prompt"]);
+        live.apply_to(&mut chat);
+        assert_eq!(texts(&chat), vec!["prompt", "Hello", "Second"]);
+    }
+
+    #[test]
+    fn continuing_replaces_the_last_part() {
+        let now = Utc::now();
+        let mut chat = chat(&["prompt", "Partial ans"]);
+        let mut live = LiveResponse::continuing(&chat, TokenizerKind::default()).unwrap();
+        live.add_data(ContextData::Text(String::from("wer")), now);
+        assert_eq!(live.added_parts(), 0);
+        assert_eq!(live.first_part_index(2), 1);
+        // only what was streamed counts, not what the part already had
+        assert_eq!(live.streamed_text(), "wer");
+        assert_eq!(live.tokens(), 1);
+        live.apply_to(&mut chat);
+        assert_eq!(texts(&chat), vec!["prompt", "Partial answer"]);
+    }
+
+    #[test]
+    fn tokens_are_counted_as_they_arrive() {
+        let now = Utc::now();
+        let mut live = LiveResponse::new(TokenizerKind::CharRatio);
+        live.start_part(text_part("Hello there", ContextPosition::AI), now);
+        assert_eq!(live.tokens(), 3);
+        live.add_data(ContextData::Text(String::from(" friend")), now);
+        assert_eq!(live.tokens(), 5);
+        live.add_data(ContextData::Media(String::from("image")), now);
+        assert_eq!(live.tokens(), 5);
+    }
+
+    #[test]
+    fn continuing_an_empty_chat_is_not_possible() {
+        assert!(LiveResponse::continuing(&chat(&[]), TokenizerKind::default()).is_none());
+    }
+
+    #[test]
+    fn tags_are_timed_where_they_land_in_their_part() {
+        let start = Utc::now();
+        let later = start + TimeDelta::seconds(5);
+        let mut live = LiveResponse::default();
+        live.start_part(text_part("<think>", ContextPosition::AI), start);
+        live.add_data(ContextData::Text(String::from("hmm</think>")), later);
+        let times = live.tag_times().next().unwrap();
+        assert_eq!(times.0, vec![(0, start), (10, later)]);
+        assert_eq!(times.at(9), Some(start));
+        assert_eq!(times.at(12), Some(later));
+    }
+
+    #[test]
+    fn streamed_parts_are_parsed_as_they_arrive() {
+        let now = Utc::now();
+        let mut chat = chat(&["prompt", "<think>plan"]);
+        let mut live = LiveResponse::continuing(&chat, TokenizerKind::default()).unwrap();
+        for token in ["ning</th", "ink>Call", "ing <call><tool>web</tool>", "rust</call>"] {
+            live.add_data(ContextData::Text(token.to_string()), now);
+            assert_eq!(live.parsed(0), Some(parse_tags(&live.part(0).unwrap().data_to_single_text())));
+        }
+        live.start_part(text_part("<outputs>found</outputs>", ContextPosition::Tool(String::from("web"))), now);
+        assert_eq!(live.parsed(1), Some(parse_tags("<outputs>found</outputs>")));
+        assert!(live.parsed(2).is_none());
+        live.apply_to(&mut chat);
+        assert_eq!(chat.context.get_parts().len(), 3);
+    }
+
+    #[test]
+    fn saved_parts_of_the_same_chat_are_equal_by_index() {
+        let saved = Rc::new(chat(&["prompt", "answer"]));
+        let copy = Rc::new((*saved).clone());
+        assert!(ShownPart::Saved(saved.clone(), 1) == ShownPart::Saved(saved.clone(), 1));
+        assert!(ShownPart::Saved(saved.clone(), 0) != ShownPart::Saved(saved.clone(), 1));
+        // another copy of the chat is compared on the parts themselves
+        assert!(ShownPart::Saved(saved.clone(), 1) == ShownPart::Saved(copy, 1));
+        assert!(ShownPart::Saved(saved.clone(), 1) == ShownPart::Live(Rc::new(text_part("answer", ContextPosition::AI))));
+        assert_eq!(ShownPart::Saved(saved, 1).data_to_single_text(), "answer");
+    }
+}
@@ -0,0 +1,83 @@
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use gloo_utils::format::JsValueSerdeExt;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::app::{print, try_invoke};
+
+#[derive(Serialize, Deserialize)]
+struct LoadStoreArgs {
+    name:String
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveStoreArgs {
+    name:String,
+    content:String
+}
+
+/// Loads data kept on this device by the Tauri side, missing or unreadable stores give the default value
+pub async fn load_local<T:DeserializeOwned + Default>(name:&str) -> T {
+    let args = serde_wasm_bindgen::to_value(&LoadStoreArgs { name: name.to_string() }).unwrap();
+    match try_invoke("load_local_store", args).await.map(|content| {content.into_serde::<Option<String>>()}) {
+        Ok(Ok(Some(content))) => serde_json::from_str(&content).unwrap_or_default(),
+        _ => T::default()
+    }
+}
+
+pub async fn save_local<T:Serialize>(name:&str, value:&T) {
+    match serde_json::to_string(value) {
+        Ok(content) => {
+            let args = serde_wasm_bindgen::to_value(&SaveStoreArgs { name: name.to_string(), content }).unwrap();
+            if let Err(error) = try_invoke("save_local_store", args).await {
+                print(format!("Could not save local store {name} : {}", error.as_string().unwrap_or_default())).await
+            }
+        },
+        Err(error) => print(format!("Could not serialize local store {name} : {error}")).await
+    }
+}
+
+pub trait MergeSaved {
+    /// Adds what was saved to what changed before it got loaded, the changes win
+    fn merge_saved(&mut self, saved:Self);
+}
+
+/// Data of the reduced state kept on this device, it is only saved once what was saved before got loaded,
+/// so that starting the app does not overwrite it
+#[derive(Clone, PartialEq, Default)]
+pub struct Stored<T> {
+    value:T,
+    loaded:bool
+}
+
+impl<T:MergeSaved> Stored<T> {
+    pub fn load(&mut self, saved:T) {
+        self.value.merge_saved(saved);
+        self.loaded = true;
+    }
+}
+
+impl<T> Deref for Stored<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Stored<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Meant for an effect on the store, so that it is saved from the state every action produced
+pub fn save_stored<T:Serialize + 'static>(name:&'static str, stored:&Rc<Stored<T>>) {
+    if stored.loaded {
+        let stored = stored.clone();
+        spawn_local(async move {
+            save_local(name, &stored.value).await;
+        });
+    }
+}
@@ -1,12 +1,4 @@
-#![feature(iter_intersperse)]
-#![feature(string_remove_matches)]
-mod app;
-mod db_sync;
-mod tabs;
-mod html_parsing;
-mod alerts;
-
-use app::App;
+use proxima_frontend_ui::app::App;
 
 fn main() {
     console_error_panic_hook::set_once();
@@ -0,0 +1,155 @@
+use latex2mathml::{DisplayStyle, latex_to_mathml};
+
+use crate::code_blocks::escape_html;
+
+/// Stands for the nth expression while the markdown around it is converted, made of characters markdown leaves alone
+fn placeholder(index:usize) -> String {
+    format!("PROXIMAMATH{index}X")
+}
+
+const DELIMITERS:&[(&str, &str, DisplayStyle)] = &[
+    ("$$", "$$", DisplayStyle::Block),
+    ("\\[", "\\]", DisplayStyle::Block),
+    ("\\(", "\\)", DisplayStyle::Inline),
+    ("$", "$", DisplayStyle::Inline),
+];
+
+/// Finds where an expression opened at the start of `rest` ends, None when it is not math
+fn math_end(rest:&str, open:&str, close:&str) -> Option<usize> {
+    let body_start = open.len();
+    if open == "$" {
+        // "$5 and $10" is money, inline math has no space right inside its dollars and never ends before a digit
+        let first = rest[body_start..].chars().next()?;
+        if first.is_whitespace() || first == '$' {
+            return None
+        }
+        let mut search = body_start;
+        while let Some(found) = rest[search..].find('$') {
+            let end = search + found;
+            let escaped = rest[..end].ends_with('\\');
+            let before_space = rest[..end].ends_with(char::is_whitespace);
+            let before_digit = rest[end + 1..].starts_with(|next:char| {next.is_ascii_digit()});
+            if rest[body_start..end].contains("\n\n") {
+                return None
+            }
+            if !escaped && !before_space && !before_digit {
+                return Some(end + 1)
+            }
+            search = end + 1;
+        }
+        None
+    }
+    else {
+        let end = body_start + rest[body_start..].find(close)?;
+        if end == body_start {
+            return None
+        }
+        Some(end + close.len())
+    }
+}
+
+/// Replaces the math of a markdown text by placeholders, giving the HTML each of them stands for
+pub fn extract_math(text:&str) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(text.len());
+    let mut expressions = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        if rest.starts_with('`') {
+            // inline code keeps its dollars
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let length = rest[ticks..].find(&rest[..ticks]).map(|found| {found + 2 * ticks}).unwrap_or(ticks);
+            out.push_str(&rest[..length]);
+            pos += length;
+            continue;
+        }
+        if rest.starts_with("\\$") {
+            out.push_str("\\$");
+            pos += 2;
+            continue;
+        }
+        let found = DELIMITERS.iter().find_map(|(open, close, style)| {
+            if rest.starts_with(open) {
+                math_end(rest, open, close).map(|end| {(end, open.len(), close.len(), *style)})
+            }
+            else {
+                None
+            }
+        });
+        match found {
+            Some((end, open_len, close_len, style)) => {
+                let source = &rest[..end];
+                let html = match latex_to_mathml(rest[open_len..end - close_len].trim(), style) {
+                    Ok(mathml) => mathml,
+                    Err(_) => format!("<code class=\"math-error\">{}</code>", escape_html(source))
+                };
+                out.push_str(&placeholder(expressions.len()));
+                expressions.push(html);
+                pos += end;
+            },
+            None => {
+                let character = rest.chars().next().unwrap();
+                out.push(character);
+                pos += character.len_utf8();
+            }
+        }
+    }
+    (out, expressions)
+}
+
+/// Puts the converted math back in the HTML made from the text given by `extract_math`
+pub fn restore_math(html:&str, expressions:&[String]) -> String {
+    let mut restored = html.to_string();
+    for (index, expression) in expressions.iter().enumerate() {
+        restored = restored.replace(&placeholder(index), expression);
+    }
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_math(text:&str) -> usize {
+        extract_math(text).1.len()
+    }
+
+    #[test]
+    fn replaces_every_delimiter() {
+        let (text, expressions) = extract_math("a $x$ b $$y$$ c \\(z\\) d \\[w\\]");
+        assert_eq!(text, format!("a {} b {} c {} d {}", placeholder(0), placeholder(1), placeholder(2), placeholder(3)));
+        assert_eq!(expressions.len(), 4);
+        assert!(expressions.iter().all(|expression| {expression.starts_with("<math")}));
+    }
+
+    #[test]
+    fn money_is_not_math() {
+        assert_eq!(count_math("it costs $5 and $10"), 0);
+        assert_eq!(count_math("between $ 1 and 2 $"), 0);
+        assert_eq!(count_math("$x $ and $y$"), 1);
+        assert_eq!(count_math("paid $x$5"), 0);
+    }
+
+    #[test]
+    fn escapes_and_code_keep_dollars() {
+        assert_eq!(extract_math("\\$x\\$"), ("\\$x\\$".to_string(), vec![]));
+        assert_eq!(extract_math("`$x$` and ``a`$y$`b``"), ("`$x$` and ``a`$y$`b``".to_string(), vec![]));
+        assert_eq!(count_math("`$x$` then $y$"), 1);
+    }
+
+    #[test]
+    fn unclosed_or_empty_math_stays_text() {
+        assert_eq!(extract_math("$x"), ("$x".to_string(), vec![]));
+        assert_eq!(count_math("$$$$"), 0);
+        assert_eq!(count_math("\\(\\)"), 0);
+        assert_eq!(count_math("$a\n\nb$"), 0);
+    }
+
+    #[test]
+    fn restores_in_order() {
+        let (text, expressions) = extract_math("$a$ and $b$");
+        let restored = restore_math(&format!("<p>{text}</p>"), &expressions);
+        assert_eq!(restored, format!("<p>{} and {}</p>", expressions[0], expressions[1]));
+        assert_eq!(restore_math("<p>none</p>", &[]), "<p>none</p>");
+    }
+}
@@ -0,0 +1,74 @@
+use proxima_backend::database::memories::Memory;
+use proxima_backend::database::{DatabaseItem, DatabaseItemID, DatabaseReplyVariant, DatabaseRequestVariant, ProxDatabase};
+use proxima_backend::web_payloads::{DBPayload, DBResponse};
+use serde_json::Value;
+use yew::UseReducerHandle;
+
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, make_db_request};
+use crate::html_parsing::Element;
+use crate::tool_views::ToolText;
+
+/// Memory items of the database, oldest first
+pub fn sorted_memories(db:&ProxDatabase) -> Vec<(u64, &Memory)> {
+    let mut memories = db.memories.get_memories().iter().map(|(id, memory)| {(*id, memory)}).collect::<Vec<(u64, &Memory)>>();
+    memories.sort_by_key(|(id, _)| {*id});
+    memories
+}
+
+pub fn visible_in(memory:&Memory, access_mode:usize) -> bool {
+    memory.access_modes.contains(&access_mode)
+}
+
+fn parse_ids(text:&str) -> impl Iterator<Item = u64> + '_ {
+    text.split(|character:char| {character == ',' || character.is_whitespace()}).filter_map(|id| {id.trim_start_matches('#').parse::<u64>().ok()})
+}
+
+/// Ids of the memory items an automatic memory block was made from, as the memory tool gives them : in an id attribute,
+/// in id tags or in the id fields of its JSON
+pub fn memory_ids(block:&Element) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for name in ["id", "ids", "memory_id", "memory_ids"] {
+        if let Some(value) = block.attribute(name) {
+            ids.extend(parse_ids(value));
+        }
+    }
+    let text = ToolText::parse(&block.content);
+    for (name, value) in &text.fields {
+        if matches!(name.as_str(), "id" | "ids" | "memory_id" | "memory_ids") {
+            ids.extend(parse_ids(value));
+        }
+    }
+    let json_id = |item:&Value| {item.get("id").or(item.get("memory_id")).and_then(|id| {id.as_u64()})};
+    match text.json() {
+        Some(Value::Array(items)) => ids.extend(items.iter().filter_map(json_id)),
+        Some(item) => ids.extend(json_id(&item)),
+        None => ()
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Edits a memory as the server has it and saves it, so that what the server keeps with it is sent back untouched
+pub async fn update_memory(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, id:u64, change:impl FnOnce(&mut Memory)) -> Result<(), String> {
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Get(DatabaseItemID::Memory(id)) };
+    let mut item = match make_db_request(json_request, proxima_state.chat_url.clone()).await {
+        Ok(DBResponse { reply:DatabaseReplyVariant::ReturnedItem(item) }) => item,
+        _ => return Err("the server did not give the memory".to_string())
+    };
+    match &mut item {
+        DatabaseItem::Memory(memory, _) => change(memory),
+        _ => return Err("the server gave something else than a memory".to_string())
+    }
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Update(item.clone()) };
+    make_db_request(json_request, proxima_state.chat_url.clone()).await.map_err(|_| {"the server did not save the memory".to_string()})?;
+    db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Memory(id), item)]));
+    Ok(())
+}
+
+pub async fn delete_memory(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, id:u64) -> Result<(), String> {
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Remove(DatabaseItemID::Memory(id)) };
+    make_db_request(json_request, proxima_state.chat_url.clone()).await.map_err(|_| {"the server did not delete the memory".to_string()})?;
+    db_state.dispatch(DatabaseAction::RemoveItem(DatabaseItemID::Memory(id)));
+    Ok(())
+}
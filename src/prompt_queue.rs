@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use proxima_backend::ai_interaction::endpoint_api::EndpointRequestVariant;
+use proxima_backend::database::chats::{Chat, ChatID, SessionType};
+use proxima_backend::database::configuration::ChatConfiguration;
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_backend::database::{DatabaseItem, DatabaseItemID};
+use proxima_backend::web_payloads::AIPayload;
+use yew::{Callback, Html, MouseEvent, NodeRef, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref};
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, fetch_chat, make_ai_request};
+use crate::compaction::auto_compact_if_needed;
+use crate::db_sync::UserCursors;
+use crate::files::{attach_media, upload_prompt_files};
+use crate::stream_metrics::NO_CONFIG;
+use crate::titles::auto_title_if_needed;
+
+/// What a prompt is sent with, taken when it is written so that a queued prompt does not follow later changes of the cursors
+#[derive(Clone, PartialEq)]
+pub struct PromptSettings {
+    pub config:Option<usize>,
+    pub access_mode:usize
+}
+
+impl PromptSettings {
+    pub fn current(cursors:&UserCursors) -> Self {
+        Self { config: cursors.chosen_config, access_mode: cursors.chosen_access_mode }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct QueuedPrompt {
+    pub id:u64,
+    pub text:String,
+    pub queued_at:DateTime<Utc>,
+    pub settings:PromptSettings,
+    pub files:Vec<PathBuf>
+}
+
+/// Prompts waiting for the current response of their chat, and the chats that are busy sending one
+#[derive(Clone, PartialEq, Default)]
+pub struct PromptQueues {
+    queues:HashMap<ChatID, VecDeque<QueuedPrompt>>,
+    sending:HashSet<ChatID>,
+    next_id:u64
+}
+
+impl PromptQueues {
+    pub fn push(&mut self, chat_id:ChatID, text:String, settings:PromptSettings, files:Vec<PathBuf>) {
+        self.next_id += 1;
+        self.queues.entry(chat_id).or_default().push_back(QueuedPrompt { id: self.next_id, text, queued_at: Utc::now(), settings, files });
+    }
+    pub fn edit(&mut self, chat_id:ChatID, id:u64, text:String) {
+        if let Some(prompt) = self.queues.get_mut(&chat_id).and_then(|queue| {queue.iter_mut().find(|prompt| {prompt.id == id})}) {
+            prompt.text = text;
+        }
+    }
+    pub fn remove(&mut self, chat_id:ChatID, id:u64) {
+        if let Some(queue) = self.queues.get_mut(&chat_id) {
+            queue.retain(|prompt| {prompt.id != id});
+            if queue.is_empty() {
+                self.queues.remove(&chat_id);
+            }
+        }
+    }
+    pub fn take_next(&mut self, chat_id:ChatID) -> Option<QueuedPrompt> {
+        let queue = self.queues.get_mut(&chat_id)?;
+        let prompt = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&chat_id);
+        }
+        if prompt.is_some() {
+            self.sending.insert(chat_id);
+        }
+        prompt
+    }
+    pub fn for_chat(&self, chat_id:ChatID) -> Vec<QueuedPrompt> {
+        self.queues.get(&chat_id).map(|queue| {queue.iter().cloned().collect()}).unwrap_or_default()
+    }
+    pub fn start_sending(&mut self, chat_id:ChatID) {
+        self.sending.insert(chat_id);
+    }
+    pub fn finish_sending(&mut self, chat_id:ChatID) {
+        self.sending.remove(&chat_id);
+    }
+    pub fn is_busy(&self, chat_id:ChatID, ongoing_chats:&im_rc::HashSet<ChatID>) -> bool {
+        self.sending.contains(&chat_id) || ongoing_chats.contains(&chat_id)
+    }
+    /// Chats with queued prompts whose previous response is completely over
+    pub fn ready_chats(&self, ongoing_chats:&im_rc::HashSet<ChatID>) -> Vec<(ChatID, QueuedPrompt)> {
+        self.queues.iter().filter(|(chat_id, _)| {!self.is_busy(**chat_id, ongoing_chats)}).filter_map(|(chat_id, queue)| {
+            queue.front().map(|prompt| {(*chat_id, prompt.clone())})
+        }).collect()
+    }
+}
+
+/// Adds a user prompt to an existing chat with the chosen configuration, the returned chat's context is what gets sent
+pub fn add_prompt_to_chat(db_state:&UseReducerHandle<DatabaseState>, chat:Chat, prompt_text:String) -> Result<(Chat, Option<ChatConfiguration>), String> {
+    add_prompt_with_config(db_state, chat, prompt_text, db_state.cursors.chosen_config)
+}
+
+/// Fails when the configuration was deleted, by the server or on another device
+pub fn add_prompt_with_config(db_state:&UseReducerHandle<DatabaseState>, mut chat:Chat, prompt_text:String, config:Option<usize>) -> Result<(Chat, Option<ChatConfiguration>), String> {
+    let (context_part, config_opt) = match config {
+        Some(config) => {
+            chat.config = Some(config);
+            let config_clone = db_state.db.configs.get_configs().get(&config).ok_or(format!("configuration {config} does not exist anymore"))?;
+            chat.latest_used_config = Some(config_clone.clone());
+            match &config_clone.tools {
+                Some(tools) => {
+                    (ContextPart::new_user_prompt_with_tools(vec![ContextData::Text(prompt_text)]), Some(config_clone.clone()))
+                },
+                None => (ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User), Some(config_clone.clone()))
+            }
+        },
+        None => {
+            chat.config = None;
+            chat.latest_used_config = None;
+            (ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User), None)
+        }
+    };
+    chat.add_to_context(context_part);
+    match &config_opt {
+        Some(configuration) => chat.context.add_per_turn_settings(configuration),
+        None => ()
+    }
+    Ok((chat, config_opt))
+}
+
+/// Context of a new chat starting with a prompt, fails when the configuration was deleted
+pub fn starting_context_with_config(db_state:&UseReducerHandle<DatabaseState>, prompt_text:String, config:Option<usize>) -> Result<(WholeContext, Option<ChatConfiguration>), String> {
+    match config {
+        Some(config) => {
+            let config_clone = db_state.db.configs.get_configs().get(&config).ok_or(format!("configuration {config} does not exist anymore"))?;
+            match config_clone.tools.clone() {
+                Some(tools) => {
+                    Ok((WholeContext::new_with_all_settings(vec![ContextPart::new_user_prompt_with_tools(vec![ContextData::Text(prompt_text)])], &config_clone), Some(config_clone.clone())))
+                },
+                None => Ok((WholeContext::new_with_all_settings(vec![ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User)], &config_clone), Some(config_clone.clone())))
+            }
+        },
+        None => {
+            Ok((WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User)]), None))
+        }
+    }
+}
+
+/// Asks the AI to respond to a chat already saved with its latest prompt, then frees the chat for its queued prompts
+pub async fn request_response(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, chat_id:ChatID, context:WholeContext, config_opt:Option<ChatConfiguration>, settings:PromptSettings) {
+    db_state.dispatch(DatabaseAction::AddToOngoingChats { chat: chat_id, config: config_opt.as_ref().map(|conf| {conf.name.clone()}).unwrap_or(NO_CONFIG.to_string()), tokenizer: db_state.tokenizer_settings.kind_for(settings.config) });
+
+    let streaming = config_opt.as_ref().is_some_and(|conf| {conf.is_streaming()});
+
+    let json_request = AIPayload::new(proxima_state.auth_token.clone(), EndpointRequestVariant::RespondToFullPrompt { whole_context: context, streaming, session_type: SessionType::Chat, chat_settings:config_opt, chat_id:Some(chat_id), access_mode:settings.access_mode });
+
+    let value = make_ai_request(json_request, proxima_state.chat_url.clone(), chat_id).await;
+    db_state.dispatch(DatabaseAction::RemoveFromOngoingChats { chat: chat_id });
+    if value.is_err() {
+        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("No response for chat {chat_id}, its queued prompts are still sent"))));
+    }
+    auto_title_if_needed(db_state, proxima_state, chat_id).await;
+    auto_compact_if_needed(db_state, proxima_state, alerts_state, chat_id, &settings).await;
+    db_state.dispatch(DatabaseAction::FinishSending(chat_id));
+}
+
+/// The prompt is added to the server's copy of the chat, the local one may miss the end of the previous response.
+/// It is sent with the configuration, access mode and files it was queued with
+pub async fn send_queued_prompt(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, chat_id:ChatID, prompt:QueuedPrompt) {
+    let added = match fetch_chat(proxima_state, chat_id).await {
+        Some(chat) => add_prompt_with_config(db_state, chat, prompt.text.clone(), prompt.settings.config),
+        None => Err("the chat could not be fetched".to_string())
+    };
+    match added {
+        Ok((mut chat, config_opt)) => {
+            if !prompt.files.is_empty() {
+                attach_media(&mut chat.context, upload_prompt_files(db_state, proxima_state, prompt.files).await);
+            }
+            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat.clone()))]));
+            request_response(db_state, proxima_state, alerts_state, chat_id, chat.get_context().clone(), config_opt, prompt.settings).await;
+        },
+        Err(error) => {
+            // putting it back would retry it in a loop while the server is unreachable
+            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("A queued prompt of chat {chat_id} was not sent, {error} : {}", prompt.text))));
+            db_state.dispatch(DatabaseAction::FinishSending(chat_id));
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct QueuedPromptsProp {
+    pub chat_id:ChatID
+}
+
+#[function_component(QueuedPrompts)]
+pub fn queued_prompts(prop:&QueuedPromptsProp) -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let prompts = db_state.prompt_queues.for_chat(prop.chat_id);
+    if prompts.is_empty() {
+        return html!()
+    }
+    let prompt_htmls = prompts.into_iter().enumerate().map(|(i, prompt)| {
+        let config = prompt.settings.config.and_then(|config| {db_state.db.configs.get_configs().get(&config)}).map(|config| {config.name.clone()}).unwrap_or(NO_CONFIG.to_string());
+        let details = if prompt.files.is_empty() {config} else {format!("{config}, {} file(s)", prompt.files.len())};
+        html!(<QueuedPromptShow chat_id={prop.chat_id} prompt_id={prompt.id} text={prompt.text.clone()} details={details} position={i + 1}/>)
+    }).collect::<Html>();
+    html!(
+        <div>
+            <h3>{"Queued prompts, sent once the current response is over"}</h3>
+            {prompt_htmls}
+        </div>
+    )
+}
+
+#[derive(Properties, PartialEq)]
+struct QueuedPromptShowProp {
+    chat_id:ChatID,
+    prompt_id:u64,
+    text:String,
+    /// Configuration and files the prompt is sent with
+    details:String,
+    position:usize
+}
+
+#[function_component(QueuedPromptShow)]
+fn queued_prompt_show(prop:&QueuedPromptShowProp) -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let text_ref:NodeRef = use_node_ref();
+
+    let save_callback = {
+        let db_state = db_state.clone();
+        let text_ref = text_ref.clone();
+        let chat_id = prop.chat_id;
+        let id = prop.prompt_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let text = text_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            if text.trim().is_empty() {
+                db_state.dispatch(DatabaseAction::RemoveQueuedPrompt { chat: chat_id, id });
+            }
+            else {
+                db_state.dispatch(DatabaseAction::EditQueuedPrompt { chat: chat_id, id, text });
+            }
+        })
+    };
+    let remove_callback = {
+        let db_state = db_state.clone();
+        let chat_id = prop.chat_id;
+        let id = prop.prompt_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            db_state.dispatch(DatabaseAction::RemoveQueuedPrompt { chat: chat_id, id });
+        })
+    };
+
+    html!(
+        <div class="standard-padding-margin-corners queued-prompt">
+            <div class="chat-title-display">
+                <p>{format!("Pending prompt {} ({})", prop.position, prop.details)}</p>
+                <div>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={save_callback}>{"Save edit"}</button>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={remove_callback}>{"Remove"}</button>
+                </div>
+            </div>
+            <textarea class="standard-padding-margin-corners most-horizontal-space-no-flex" ref={text_ref} value={prop.text.clone()}/>
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> PromptSettings {
+        PromptSettings { config: None, access_mode: 0 }
+    }
+
+    fn texts(queues:&PromptQueues, chat_id:ChatID) -> Vec<String> {
+        queues.for_chat(chat_id).into_iter().map(|prompt| {prompt.text}).collect()
+    }
+
+    #[test]
+    fn prompts_are_sent_in_the_order_they_were_queued() {
+        let mut queues = PromptQueues::default();
+        queues.push(1, String::from("first"), settings(), Vec::new());
+        queues.push(1, String::from("second"), settings(), Vec::new());
+        queues.push(2, String::from("other chat"), settings(), Vec::new());
+        assert_eq!(texts(&queues, 1), vec!["first", "second"]);
+        assert_eq!(queues.take_next(1).map(|prompt| {prompt.text}), Some(String::from("first")));
+        assert_eq!(texts(&queues, 1), vec!["second"]);
+        assert_eq!(texts(&queues, 2), vec!["other chat"]);
+    }
+
+    #[test]
+    fn queued_prompts_can_be_edited_and_removed() {
+        let mut queues = PromptQueues::default();
+        queues.push(1, String::from("first"), settings(), Vec::new());
+        queues.push(1, String::from("second"), settings(), Vec::new());
+        let ids = queues.for_chat(1).into_iter().map(|prompt| {prompt.id}).collect::<Vec<u64>>();
+        assert_ne!(ids[0], ids[1]);
+        queues.edit(1, ids[1], String::from("edited"));
+        queues.remove(1, ids[0]);
+        assert_eq!(texts(&queues, 1), vec!["edited"]);
+        queues.remove(1, ids[1]);
+        assert!(queues.for_chat(1).is_empty());
+        assert!(queues.take_next(1).is_none());
+    }
+
+    #[test]
+    fn a_chat_is_busy_while_sending_or_generating() {
+        let mut queues = PromptQueues::default();
+        let mut ongoing = im_rc::HashSet::new();
+        assert!(!queues.is_busy(1, &ongoing));
+        queues.start_sending(1);
+        assert!(queues.is_busy(1, &ongoing));
+        // the response can be over before the prompt is done being sent, the chat stays busy until then
+        ongoing.insert(1);
+        queues.finish_sending(1);
+        assert!(queues.is_busy(1, &ongoing));
+        ongoing.remove(&1);
+        assert!(!queues.is_busy(1, &ongoing));
+    }
+
+    #[test]
+    fn taking_a_prompt_marks_the_chat_as_sending() {
+        let mut queues = PromptQueues::default();
+        assert!(queues.take_next(1).is_none());
+        // nothing was taken so nothing is being sent
+        assert!(!queues.is_busy(1, &im_rc::HashSet::new()));
+        queues.push(1, String::from("first"), settings(), Vec::new());
+        queues.take_next(1);
+        assert!(queues.is_busy(1, &im_rc::HashSet::new()));
+    }
+
+    #[test]
+    fn only_idle_chats_are_ready() {
+        let mut queues = PromptQueues::default();
+        queues.push(1, String::from("for one"), settings(), Vec::new());
+        queues.push(2, String::from("for two"), settings(), Vec::new());
+        queues.push(3, String::from("for three"), settings(), Vec::new());
+        queues.start_sending(2);
+        let ongoing = im_rc::HashSet::unit(3);
+        let ready = queues.ready_chats(&ongoing);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, 1);
+        assert_eq!(ready[0].1.text, "for one");
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use proxima_backend::database::chats::Chat;
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition};
+use serde::{Deserialize, Serialize};
+
+use crate::templates::is_template;
+
+/// Ratings are kept in a system part in front of their chat, so that they are saved and synced with it
+pub const RATINGS_MARKER:&str = "<proxima_ratings>";
+const RATINGS_END:&str = "</proxima_ratings>";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct ResponseRating {
+    pub thumb:Option<bool>,
+    pub score:Option<u8>,
+    pub notes:String
+}
+
+impl ResponseRating {
+    pub fn is_empty(&self) -> bool {
+        self.thumb.is_none() && self.score.is_none() && self.notes.trim().is_empty()
+    }
+}
+
+/// Ratings of a chat by index of the rated part in the chat
+pub type ChatRatings = BTreeMap<usize, ResponseRating>;
+
+pub fn is_ratings_part(part:&ContextPart) -> bool {
+    matches!(part.get_position(), ContextPosition::System) && matches!(part.get_data().first(), Some(ContextData::Text(text)) if text.starts_with(RATINGS_MARKER))
+}
+
+fn has_ratings_part(chat:&Chat) -> bool {
+    chat.context.get_parts().first().is_some_and(is_ratings_part)
+}
+
+pub fn chat_ratings(chat:&Chat) -> ChatRatings {
+    match chat.context.get_parts().first().filter(|part| {is_ratings_part(part)}).map(|part| {part.data_to_single_text()}) {
+        Some(text) => text.strip_prefix(RATINGS_MARKER).and_then(|text| {text.strip_suffix(RATINGS_END)}).and_then(|json| {serde_json::from_str(json).ok()}).unwrap_or_default(),
+        None => ChatRatings::new()
+    }
+}
+
+pub fn rating_of(chat:&Chat, part_index:usize) -> Option<ResponseRating> {
+    chat_ratings(chat).remove(&part_index)
+}
+
+/// The ratings part goes away with the last rating, which moves the parts after it back by one
+fn write_ratings(chat:&mut Chat, ratings:&ChatRatings) {
+    let had_part = has_ratings_part(chat);
+    let parts = chat.context.get_parts_mut();
+    if ratings.is_empty() {
+        if had_part {
+            parts.remove(0);
+        }
+        return
+    }
+    let json = serde_json::to_string(ratings).unwrap_or_default();
+    let part = ContextPart::new(vec![ContextData::Text(format!("{RATINGS_MARKER}{json}{RATINGS_END}"))], ContextPosition::System);
+    if had_part {
+        parts[0] = part;
+    }
+    else {
+        parts.insert(0, part);
+    }
+}
+
+/// Rates a part of the chat by its index, as it is before the rating
+pub fn set_rating(chat:&mut Chat, part_index:usize, rating:ResponseRating) {
+    // a ratings part would make a template a chat again
+    if is_template(chat) {
+        return
+    }
+    let mut ratings = chat_ratings(chat);
+    let had_part = has_ratings_part(chat);
+    if !rating.is_empty() {
+        // the ratings part added in front moves every part by one, the ratings already there included
+        let part_index = if had_part {part_index} else {part_index + 1};
+        ratings.insert(part_index, rating);
+        write_ratings(chat, &ratings);
+    }
+    else if ratings.remove(&part_index).is_some() {
+        // without ratings left the parts move back, their ratings go with them
+        write_ratings(chat, &ratings);
+    }
+}
+
+/// Replaces some parts of the chat with others, the ratings of the removed parts are dropped and those of the parts
+/// after them follow them
+pub fn splice_parts(chat:&mut Chat, removed:Range<usize>, added:Vec<ContextPart>) {
+    let added_len = added.len();
+    let removed_len = removed.len();
+    let ratings = chat_ratings(chat).into_iter().filter(|(index, _)| {!removed.contains(index)}).map(|(index, rating)| {
+        if index >= removed.end {(index - removed_len + added_len, rating)} else {(index, rating)}
+    }).collect::<ChatRatings>();
+    chat.context.get_parts_mut().splice(removed, added);
+    if has_ratings_part(chat) {
+        write_ratings(chat, &ratings);
+    }
+}
+
+pub fn remove_part(chat:&mut Chat, part_index:usize) {
+    splice_parts(chat, part_index..(part_index + 1), Vec::new());
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RatingFilter {
+    Any,
+    Rated,
+    ThumbsUp,
+    ThumbsDown,
+    MinScore(u8)
+}
+
+impl RatingFilter {
+    pub fn from_value(value:&str) -> Self {
+        match value {
+            "rated" => Self::Rated,
+            "up" => Self::ThumbsUp,
+            "down" => Self::ThumbsDown,
+            other => match other.strip_prefix("score:").and_then(|score| {score.parse().ok()}) {
+                Some(score) => Self::MinScore(score),
+                None => Self::Any
+            }
+        }
+    }
+    pub fn matches_rating(&self, rating:&ResponseRating) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Rated => !rating.is_empty(),
+            Self::ThumbsUp => rating.thumb == Some(true),
+            Self::ThumbsDown => rating.thumb == Some(false),
+            Self::MinScore(min) => rating.score.is_some_and(|score| {score >= *min})
+        }
+    }
+    /// A chat matches if any of its responses does
+    pub fn matches_chat(&self, chat:&Chat) -> bool {
+        match self {
+            Self::Any => true,
+            _ => chat_ratings(chat).values().any(|rating| {self.matches_rating(rating)})
+        }
+    }
+    pub fn options() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("any", "Any rating"),
+            ("rated", "Rated"),
+            ("up", "Thumbs up"),
+            ("down", "Thumbs down"),
+            ("score:3", "Score 3+"),
+            ("score:4", "Score 4+"),
+            ("score:5", "Score 5"),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::ProxDatabase;
+    use proxima_backend::database::context::WholeContext;
+
+    use crate::templates::template_context;
+
+    use super::*;
+
+    fn chat(texts:&[&str]) -> Chat {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let parts = texts.iter().enumerate().map(|(i, text)| {
+            ContextPart::new(vec![ContextData::Text(text.to_string())], if i % 2 == 0 {ContextPosition::User} else {ContextPosition::AI})
+        }).collect();
+        db.chats.create_possible_chat(WholeContext::new(parts), None, 0, None)
+    }
+
+    fn texts(chat:&Chat) -> Vec<String> {
+        chat.context.get_parts().iter().filter(|part| {!is_ratings_part(part)}).map(|part| {part.data_to_single_text()}).collect()
+    }
+
+    fn thumb(up:bool) -> ResponseRating {
+        ResponseRating { thumb: Some(up), ..Default::default() }
+    }
+
+    fn score(score:u8) -> ResponseRating {
+        ResponseRating { score: Some(score), ..Default::default() }
+    }
+
+    #[test]
+    fn the_first_rating_adds_the_ratings_part_in_front() {
+        let mut rated = chat(&["prompt", "answer"]);
+        set_rating(&mut rated, 1, thumb(true));
+        assert!(is_ratings_part(&rated.context.get_parts()[0]));
+        // the answer moved behind the ratings part and its rating with it
+        assert_eq!(rating_of(&rated, 2), Some(thumb(true)));
+        assert_eq!(texts(&rated), vec!["prompt", "answer"]);
+        set_rating(&mut rated, 2, score(4));
+        assert_eq!(chat_ratings(&rated).len(), 1);
+        assert_eq!(rating_of(&rated, 2), Some(score(4)));
+    }
+
+    #[test]
+    fn templates_are_not_rated() {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let mut template = db.chats.create_possible_chat(template_context("Review {{code}}"), None, 0, None);
+        set_rating(&mut template, 0, thumb(true));
+        assert!(is_template(&template));
+        assert!(chat_ratings(&template).is_empty());
+    }
+
+    #[test]
+    fn the_last_rating_removes_the_ratings_part() {
+        let mut rated = chat(&["prompt", "answer"]);
+        set_rating(&mut rated, 1, thumb(false));
+        set_rating(&mut rated, 2, ResponseRating::default());
+        assert_eq!(rated.context.get_parts().len(), 2);
+        assert!(chat_ratings(&rated).is_empty());
+    }
+
+    #[test]
+    fn ratings_follow_their_part_when_parts_before_it_go() {
+        let mut rated = chat(&["prompt", "first", "again", "second"]);
+        set_rating(&mut rated, 1, thumb(false));
+        set_rating(&mut rated, 4, thumb(true));
+        // removing the first answer drops its rating and moves the second one back
+        remove_part(&mut rated, 2);
+        assert_eq!(texts(&rated), vec!["prompt", "again", "second"]);
+        assert_eq!(chat_ratings(&rated), ChatRatings::from([(3, thumb(true))]));
+    }
+
+    #[test]
+    fn spliced_parts_move_the_ratings_after_them() {
+        let mut rated = chat(&["prompt", "first", "again", "second"]);
+        set_rating(&mut rated, 3, thumb(true));
+        let summary = ContextPart::new(vec![ContextData::Text(String::from("summary"))], ContextPosition::System);
+        splice_parts(&mut rated, 1..3, vec![summary]);
+        assert_eq!(texts(&rated), vec!["summary", "again", "second"]);
+        assert_eq!(chat_ratings(&rated), ChatRatings::from([(3, thumb(true))]));
+    }
+
+    #[test]
+    fn filters_are_read_from_their_value() {
+        assert_eq!(RatingFilter::from_value("rated"), RatingFilter::Rated);
+        assert_eq!(RatingFilter::from_value("up"), RatingFilter::ThumbsUp);
+        assert_eq!(RatingFilter::from_value("down"), RatingFilter::ThumbsDown);
+        assert_eq!(RatingFilter::from_value("score:4"), RatingFilter::MinScore(4));
+        assert_eq!(RatingFilter::from_value("score:many"), RatingFilter::Any);
+        assert_eq!(RatingFilter::from_value("any"), RatingFilter::Any);
+        assert!(RatingFilter::options().iter().all(|(value, _)| {*value == "any" || RatingFilter::from_value(value) != RatingFilter::Any}));
+    }
+
+    #[test]
+    fn chats_match_when_any_response_does() {
+        let mut rated = chat(&["prompt", "first", "again", "second"]);
+        assert!(RatingFilter::Any.matches_chat(&rated));
+        assert!(!RatingFilter::Rated.matches_chat(&rated));
+        set_rating(&mut rated, 1, thumb(false));
+        set_rating(&mut rated, 4, score(3));
+        assert!(RatingFilter::Rated.matches_chat(&rated));
+        assert!(RatingFilter::ThumbsDown.matches_chat(&rated));
+        assert!(!RatingFilter::ThumbsUp.matches_chat(&rated));
+        assert!(RatingFilter::MinScore(3).matches_chat(&rated));
+        assert!(!RatingFilter::MinScore(4).matches_chat(&rated));
+    }
+}
@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::rc::Rc;
+
+use proxima_backend::database::context::ContextPart;
+
+use crate::html_parsing::{ParsedHtml, parse_tags};
+use crate::tokenizer::{Tokenizer, TokenizerKind};
+
+/// Entries kept by each cache before it is emptied, enough for every part of a few long chats
+const MAX_ENTRIES:usize = 8192;
+
+pub fn content_hash<T:Hash + ?Sized>(content:&T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Results of a costly function of a text, keyed by the hash of what they were made from
+struct Memo<T> {
+    entries:HashMap<u64, T>
+}
+
+impl<T:Clone> Memo<T> {
+    fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+    fn get_or_insert_with(&mut self, key:u64, make:impl FnOnce() -> T) -> T {
+        if let Some(found) = self.entries.get(&key) {
+            return found.clone()
+        }
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.clear();
+        }
+        let made = make();
+        self.entries.insert(key, made.clone());
+        made
+    }
+}
+
+thread_local! {
+    static PARSED:RefCell<Memo<Rc<ParsedHtml>>> = RefCell::new(Memo::new());
+    static MARKDOWN:RefCell<Memo<Rc<str>>> = RefCell::new(Memo::new());
+    static TOKEN_COUNTS:RefCell<Memo<usize>> = RefCell::new(Memo::new());
+}
+
+/// Tags of a finished part, parsed once for every part with the same text
+pub fn cached_parse_tags(text:&str) -> Rc<ParsedHtml> {
+    PARSED.with_borrow_mut(|memo| {memo.get_or_insert_with(content_hash(text), || {Rc::new(parse_tags(text))})})
+}
+
+/// HTML made from a markdown chunk, the flag tells apart the renderings of the same text
+pub fn cached_markdown(markdown:&str, split_lines:bool, render:impl FnOnce() -> String) -> Rc<str> {
+    MARKDOWN.with_borrow_mut(|memo| {memo.get_or_insert_with(content_hash(&(markdown, split_lines)), || {Rc::from(render())})})
+}
+
+pub fn cached_token_count(kind:TokenizerKind, part:&ContextPart) -> usize {
+    let text = part.data_to_single_text();
+    TOKEN_COUNTS.with_borrow_mut(|memo| {memo.get_or_insert_with(content_hash(&(kind, text.as_str())), || {kind.tokenizer().count(&text)})})
+}
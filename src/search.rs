@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+
+use proxima_backend::database::{DatabaseItem, DatabaseItemID, ProxDatabase, chats::{Chat, ChatID}, context::ContextData, media::{Media, MediaType}, notifications::Notification};
+
+use crate::chat_store::ChatStore;
+use crate::ratings::is_ratings_part;
+use crate::templates::is_template;
+
+const SNIPPET_CHARS_BEFORE:usize = 50;
+const SNIPPET_CHARS_AFTER:usize = 110;
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum SearchTarget {
+    ChatTitle(ChatID),
+    ChatPart{chat_id:ChatID, part_index:usize},
+    MediaText(String),
+    Notification(usize)
+}
+
+#[derive(Clone, PartialEq)]
+struct SearchDocument {
+    text:String,
+    access_modes:HashSet<usize>,
+    // where a media item was first referenced, so results can jump to it
+    location:Option<(ChatID, usize)>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SnippetSegment {
+    Plain(String),
+    Match(String)
+}
+
+#[derive(Clone, PartialEq)]
+pub struct SearchResult {
+    pub target:SearchTarget,
+    pub location:Option<(ChatID, usize)>,
+    pub snippet:Vec<SnippetSegment>,
+    pub score:usize
+}
+
+/// Persistent maps, so the copy an action makes shares everything it does not change
+#[derive(Clone, PartialEq, Default)]
+pub struct SearchIndex {
+    documents:im_rc::HashMap<SearchTarget, SearchDocument>,
+    words:im_rc::OrdMap<String, im_rc::HashSet<SearchTarget>>,
+    chat_parts:im_rc::HashMap<ChatID, usize>,
+    /// Media referenced by indexed chats whose text was not fetched yet, with where they were first referenced
+    unindexed_media:im_rc::HashMap<String, (ChatID, usize)>
+}
+
+impl SearchIndex {
+    pub fn from_database(db:&ProxDatabase, chats:&ChatStore) -> Self {
+        let mut index = Self::default();
+        for (_, chat) in chats.iter() {
+            index.index_chat(chat);
+        }
+        for (id, notification) in db.notifications.get_notifications() {
+            index.index_notification(*id, notification);
+        }
+        index
+    }
+    pub fn index_chat(&mut self, chat:&Chat) {
+        self.remove_chat(chat.get_id());
+        if is_template(chat) {
+            return
+        }
+        if let Some(title) = &chat.chat_title {
+            self.insert_document(SearchTarget::ChatTitle(chat.get_id()), SearchDocument { text: title.clone(), access_modes: chat.access_modes.clone(), location:None });
+        }
+        for (part_index, part) in chat.context.get_parts().iter().enumerate() {
+            if part.in_visible_position() && !is_ratings_part(part) {
+                self.insert_document(SearchTarget::ChatPart { chat_id: chat.get_id(), part_index }, SearchDocument { text: part.data_to_single_text(), access_modes: chat.access_modes.clone(), location:None });
+            }
+            for data in part.get_data() {
+                if let ContextData::Media(hash) = data && !self.is_media_indexed(hash) && !self.unindexed_media.contains_key(hash) {
+                    self.unindexed_media.insert(hash.clone(), (chat.get_id(), part_index));
+                }
+            }
+        }
+        self.chat_parts.insert(chat.get_id(), chat.context.get_parts().len());
+    }
+    pub fn remove_chat(&mut self, chat_id:ChatID) {
+        self.remove_target(&SearchTarget::ChatTitle(chat_id));
+        if let Some(parts) = self.chat_parts.remove(&chat_id) {
+            for part_index in 0..parts {
+                self.remove_target(&SearchTarget::ChatPart { chat_id, part_index });
+            }
+        }
+        self.unindexed_media.retain(|_, (location_chat, _)| {*location_chat != chat_id});
+    }
+    pub fn index_notification(&mut self, id:usize, notification:&Notification) {
+        self.remove_target(&SearchTarget::Notification(id));
+        if let Some(text) = &notification.text {
+            self.insert_document(SearchTarget::Notification(id), SearchDocument { text: text.clone(), access_modes: notification.access_modes.clone(), location:None });
+        }
+    }
+    pub fn index_media_text(&mut self, media:&Media, text:String, location:Option<(ChatID, usize)>) {
+        let target = SearchTarget::MediaText(media.hash.clone());
+        self.unindexed_media.remove(&media.hash);
+        self.remove_target(&target);
+        self.insert_document(target, SearchDocument { text: format!("{}\n{}", media.file_name, text), access_modes: media.access_modes.clone(), location });
+    }
+    pub fn is_media_indexed(&self, hash:&String) -> bool {
+        self.documents.contains_key(&SearchTarget::MediaText(hash.clone()))
+    }
+    /// Text media to fetch and index, with where they were first referenced
+    pub fn unindexed_media(&self) -> &im_rc::HashMap<String, (ChatID, usize)> {
+        &self.unindexed_media
+    }
+    /// Media that turned out to have no text, or that could not be fetched
+    pub fn skip_media(&mut self, hash:&String) {
+        self.unindexed_media.remove(hash);
+    }
+    pub fn apply_item(&mut self, id:&DatabaseItemID, item:&DatabaseItem) {
+        match (id, item) {
+            (_, DatabaseItem::Chat(chat)) => self.index_chat(chat),
+            (DatabaseItemID::Notification(notif_id), DatabaseItem::Notification(notification)) => self.index_notification(*notif_id, notification),
+            (_, DatabaseItem::Media(media, data)) => if matches!(media.media_type, MediaType::Text) && let Ok(text) = String::from_utf8(data.get_data()) && !text.is_empty() {
+                let location = self.documents.get(&SearchTarget::MediaText(media.hash.clone())).and_then(|document| {document.location});
+                self.index_media_text(media, text, location);
+            },
+            _ => ()
+        }
+    }
+    pub fn remove_item(&mut self, id:&DatabaseItemID) {
+        match id {
+            DatabaseItemID::Chat(chat_id) => self.remove_chat(*chat_id),
+            DatabaseItemID::Notification(notif_id) => self.remove_target(&SearchTarget::Notification(*notif_id)),
+            DatabaseItemID::Media(hash) => {
+                self.unindexed_media.remove(hash);
+                self.remove_target(&SearchTarget::MediaText(hash.clone()));
+            },
+            _ => ()
+        }
+    }
+    fn insert_document(&mut self, target:SearchTarget, document:SearchDocument) {
+        for word in tokenize(&document.text) {
+            self.words.entry(word).or_default().insert(target.clone());
+        }
+        self.documents.insert(target, document);
+    }
+    fn remove_target(&mut self, target:&SearchTarget) {
+        if let Some(document) = self.documents.remove(target) {
+            for word in tokenize(&document.text) {
+                if let Some(targets) = self.words.get_mut(&word) {
+                    targets.remove(target);
+                    if targets.is_empty() {
+                        self.words.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+    fn targets_for_prefix(&self, prefix:&String) -> HashSet<SearchTarget> {
+        let mut targets = HashSet::new();
+        for (word, word_targets) in self.words.range(prefix.clone()..) {
+            if !word.starts_with(prefix.as_str()) {
+                break;
+            }
+            targets.extend(word_targets.iter().cloned());
+        }
+        targets
+    }
+    pub fn search(&self, query:&str, access_mode:usize, max_results:usize) -> Vec<SearchResult> {
+        let terms = tokenize(query);
+        if terms.is_empty() {
+            return Vec::new()
+        }
+        let mut candidates = self.targets_for_prefix(&terms[0]);
+        for term in &terms[1..] {
+            let term_targets = self.targets_for_prefix(term);
+            candidates.retain(|target| {term_targets.contains(target)});
+        }
+        let mut results:Vec<SearchResult> = candidates.into_iter().filter_map(|target| {
+            let document = self.documents.get(&target)?;
+            if !document.access_modes.contains(&access_mode) {
+                return None
+            }
+            let (snippet, score) = build_snippet(&document.text, &terms);
+            Some(SearchResult { location: document.location, target, snippet, score })
+        }).collect();
+        results.sort_by(|result1, result2| {result2.score.cmp(&result1.score).then(result1.target.cmp(&result2.target))});
+        results.truncate(max_results);
+        results
+    }
+}
+
+pub fn fold_char(character:char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+pub fn tokenize(text:&str) -> Vec<String> {
+    text.split(|character:char| {!character.is_alphanumeric()})
+        .filter(|word| {!word.is_empty()})
+        .map(|word| {word.chars().map(fold_char).collect()})
+        .collect()
+}
+
+fn match_ranges(folded:&[char], terms:&[String]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::with_capacity(8);
+    let term_chars = terms.iter().map(|term| {term.chars().collect::<Vec<char>>()}).collect::<Vec<Vec<char>>>();
+    let mut i = 0;
+    while i < folded.len() {
+        let at_word_start = i == 0 || !folded[i - 1].is_alphanumeric();
+        let found = if at_word_start {
+            term_chars.iter().filter(|term| {folded[i..].starts_with(term)}).map(|term| {term.len()}).max()
+        }
+        else {
+            None
+        };
+        match found {
+            Some(len) => {
+                ranges.push((i, i + len));
+                i += len;
+            },
+            None => i += 1
+        }
+    }
+    ranges
+}
+
+/// Cuts a window of the text around the first match, with every match inside it marked
+pub fn build_snippet(text:&str, terms:&[String]) -> (Vec<SnippetSegment>, usize) {
+    let chars = text.chars().map(|character| {if character.is_whitespace() {' '} else {character}}).collect::<Vec<char>>();
+    let folded = chars.iter().map(|character| {fold_char(*character)}).collect::<Vec<char>>();
+    let ranges = match_ranges(&folded, terms);
+    let first = ranges.first().map(|(start, _)| {*start}).unwrap_or(0);
+    let window_start = first.saturating_sub(SNIPPET_CHARS_BEFORE);
+    let window_end = (first + SNIPPET_CHARS_AFTER).min(chars.len());
+    let mut segments = Vec::with_capacity(ranges.len() * 2 + 3);
+    if window_start > 0 {
+        segments.push(SnippetSegment::Plain("...".to_string()));
+    }
+    let mut cursor = window_start;
+    for (start, end) in &ranges {
+        if *start < window_start {
+            continue;
+        }
+        if *end > window_end {
+            break;
+        }
+        if *start > cursor {
+            segments.push(SnippetSegment::Plain(chars[cursor..*start].iter().collect()));
+        }
+        segments.push(SnippetSegment::Match(chars[*start..*end].iter().collect()));
+        cursor = *end;
+    }
+    if cursor < window_end {
+        segments.push(SnippetSegment::Plain(chars[cursor..window_end].iter().collect()));
+    }
+    if window_end < chars.len() {
+        segments.push(SnippetSegment::Plain("...".to_string()));
+    }
+    (segments, ranges.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::Utc;
+    use proxima_backend::database::ProxDatabase;
+    use proxima_backend::database::chats::Chat;
+    use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+    use proxima_backend::database::media::{Media, MediaType};
+
+    use super::*;
+
+    fn chat(texts:&[&str], access_mode:usize) -> Chat {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let parts = texts.iter().enumerate().map(|(i, text)| {
+            ContextPart::new(vec![ContextData::Text(text.to_string())], if i % 2 == 0 {ContextPosition::User} else {ContextPosition::AI})
+        }).collect();
+        let mut chat = db.chats.create_possible_chat(WholeContext::new(parts), None, 0, None);
+        chat.access_modes = HashSet::from([access_mode]);
+        chat
+    }
+
+    fn targets(results:&[SearchResult]) -> Vec<SearchTarget> {
+        results.iter().map(|result| {result.target.clone()}).collect()
+    }
+
+    #[test]
+    fn tokenize_folds_case_and_splits_on_punctuation() {
+        assert_eq!(tokenize("Hello, WORLD! it's"), vec!["hello", "world", "it", "s"]);
+        assert!(tokenize(" ... ").is_empty());
+    }
+
+    #[test]
+    fn search_matches_prefixes_of_every_term() {
+        let mut index = SearchIndex::default();
+        let chat = chat(&["how do I parse json", "Use serde_json to parse it", "thanks"], 0);
+        let chat_id = chat.get_id();
+        index.index_chat(&chat);
+        assert_eq!(targets(&index.search("pars", 0, 10)).len(), 2);
+        assert_eq!(targets(&index.search("serde pars", 0, 10)), vec![SearchTarget::ChatPart { chat_id, part_index: 1 }]);
+        assert!(index.search("parse missing", 0, 10).is_empty());
+        assert!(index.search("", 0, 10).is_empty());
+    }
+
+    #[test]
+    fn search_respects_access_modes() {
+        let mut index = SearchIndex::default();
+        index.index_chat(&chat(&["secret plans"], 1));
+        assert!(index.search("secret", 0, 10).is_empty());
+        assert_eq!(index.search("secret", 1, 10).len(), 1);
+    }
+
+    #[test]
+    fn reindexing_a_chat_forgets_its_old_parts() {
+        let mut index = SearchIndex::default();
+        let mut indexed = chat(&["first version", "reply"], 0);
+        index.index_chat(&indexed);
+        indexed.context = WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(String::from("second version"))], ContextPosition::User)]);
+        index.index_chat(&indexed);
+        assert!(index.search("first", 0, 10).is_empty());
+        assert!(index.search("reply", 0, 10).is_empty());
+        assert_eq!(index.search("second", 0, 10).len(), 1);
+        index.remove_chat(indexed.get_id());
+        assert!(index.search("version", 0, 10).is_empty());
+        assert!(index.words.is_empty());
+    }
+
+    #[test]
+    fn referenced_media_wait_to_be_indexed() {
+        let mut index = SearchIndex::default();
+        let mut with_media = chat(&["see the file"], 0);
+        with_media.context.get_parts_mut()[0].add_data(ContextData::Media(String::from("hash")));
+        index.index_chat(&with_media);
+        assert_eq!(index.unindexed_media().get("hash"), Some(&(with_media.get_id(), 0)));
+
+        let media = Media { hash: String::from("hash"), media_type: MediaType::Text, file_name: String::from("notes.txt"), tags: HashSet::new(), access_modes: HashSet::from([0]), added_at: Utc::now() };
+        index.index_media_text(&media, String::from("quarterly numbers"), Some((with_media.get_id(), 0)));
+        assert!(index.unindexed_media().is_empty());
+        assert_eq!(targets(&index.search("quarterly", 0, 10)), vec![SearchTarget::MediaText(String::from("hash"))]);
+        assert_eq!(index.search("notes", 0, 10).len(), 1);
+
+        // indexing the chat again does not ask for the media a second time
+        index.index_chat(&with_media);
+        assert!(index.unindexed_media().is_empty());
+    }
+
+    #[test]
+    fn skipped_media_are_not_asked_for_again() {
+        let mut index = SearchIndex::default();
+        let mut with_media = chat(&["a picture"], 0);
+        with_media.context.get_parts_mut()[0].add_data(ContextData::Media(String::from("image")));
+        index.index_chat(&with_media);
+        index.skip_media(&String::from("image"));
+        assert!(index.unindexed_media().is_empty());
+    }
+
+    #[test]
+    fn snippets_mark_matches_around_the_first_one() {
+        let text = format!("{} needle in the middle {}", "a ".repeat(60), "b ".repeat(100));
+        let (segments, score) = build_snippet(&text, &[String::from("needle")]);
+        assert_eq!(score, 1);
+        assert_eq!(segments.first(), Some(&SnippetSegment::Plain(String::from("..."))));
+        assert_eq!(segments.last(), Some(&SnippetSegment::Plain(String::from("..."))));
+        assert!(segments.contains(&SnippetSegment::Match(String::from("needle"))));
+    }
+
+    #[test]
+    fn snippets_only_match_at_word_starts() {
+        let (segments, score) = build_snippet("Cats and concatenation", &[String::from("cat")]);
+        assert_eq!(score, 1);
+        assert_eq!(segments[0], SnippetSegment::Match(String::from("Cat")));
+    }
+}
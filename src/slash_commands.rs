@@ -0,0 +1,100 @@
+use proxima_backend::database::chats::Chat;
+use proxima_backend::database::{DatabaseItem, DatabaseItemID};
+use yew::UseReducerHandle;
+
+use crate::alerts::Alerts;
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, update_chat};
+use crate::prompt_queue::{PromptSettings, request_response};
+use crate::ratings::splice_parts;
+use crate::titles::set_chat_title;
+
+pub const COMMANDS_HELP:&str = "/config <name>, /am <access mode>, /tag <tag>, /regen, /title <text>";
+
+pub enum SlashCommand {
+    Config(String),
+    AccessMode(String),
+    Tag(String),
+    Regen,
+    Title(String)
+}
+
+/// None when the prompt is not a command, prompts starting with "//" are sent with a single "/"
+pub fn parse_command(prompt:&str) -> Option<Result<SlashCommand, String>> {
+    let trimmed = prompt.trim();
+    if !trimmed.starts_with('/') || trimmed.starts_with("//") {
+        return None
+    }
+    let (name, argument) = match trimmed[1..].split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim().to_string()),
+        None => (&trimmed[1..], String::new())
+    };
+    let needs_argument = |command:fn(String) -> SlashCommand| {
+        if argument.is_empty() {
+            Err(format!("/{name} needs an argument"))
+        }
+        else {
+            Ok(command(argument.clone()))
+        }
+    };
+    Some(match name {
+        "config" => needs_argument(SlashCommand::Config),
+        "am" => needs_argument(SlashCommand::AccessMode),
+        "tag" => needs_argument(SlashCommand::Tag),
+        "title" => needs_argument(SlashCommand::Title),
+        "regen" => Ok(SlashCommand::Regen),
+        _ => Err(format!("Unknown command /{name}, available commands : {COMMANDS_HELP}"))
+    })
+}
+
+fn chosen_chat(db_state:&UseReducerHandle<DatabaseState>) -> Result<Chat, String> {
+    db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.chat_with_live(chat_id)}).ok_or("no chat is chosen".to_string())
+}
+
+pub async fn run_command(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, command:SlashCommand) -> Result<(), String> {
+    match command {
+        SlashCommand::Config(name) => {
+            if name.eq_ignore_ascii_case("none") {
+                db_state.dispatch(DatabaseAction::ChangeUsedChatConfig(None));
+                return Ok(())
+            }
+            let (id, _) = db_state.db.configs.get_configs().iter().find(|(_, config)| {config.name.eq_ignore_ascii_case(&name)}).ok_or(format!("no configuration named {name}"))?;
+            db_state.dispatch(DatabaseAction::ChangeUsedChatConfig(Some(*id)));
+        },
+        SlashCommand::AccessMode(name) => {
+            let (id, _) = db_state.db.access_modes.get_modes().iter().find(|(_, access_mode)| {access_mode.get_name().eq_ignore_ascii_case(&name)}).ok_or(format!("no access mode named {name}"))?;
+            // same as choosing it in the top bar, the chosen chat may not be visible in the new mode
+            if db_state.cursors.chosen_access_mode != *id {
+                db_state.dispatch(DatabaseAction::SetChat(None));
+                db_state.dispatch(DatabaseAction::SetModifiedTag(None));
+                db_state.dispatch(DatabaseAction::SetParentTag(None));
+            }
+            db_state.dispatch(DatabaseAction::SetGlobalAM(*id));
+        },
+        SlashCommand::Tag(name) => {
+            let chat = chosen_chat(db_state)?;
+            let (id, _) = db_state.db.tags.get_tags().iter().find(|(_, tag)| {tag.get_name().eq_ignore_ascii_case(&name)}).ok_or(format!("no tag named {name}"))?;
+            let id = *id;
+            update_chat(db_state, proxima_state, chat.get_id(), |chat| {chat.tags.insert(id);}).await?;
+        },
+        SlashCommand::Title(title) => {
+            let chat = chosen_chat(db_state)?;
+            set_chat_title(db_state, proxima_state, chat.get_id(), Some(title)).await?;
+        },
+        SlashCommand::Regen => {
+            let mut chat = chosen_chat(db_state)?;
+            let chat_id = chat.get_id();
+            if db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats) {
+                return Err("the chat is still generating".to_string())
+            }
+            let parts = chat.context.get_parts().clone();
+            let cut = parts.iter().rposition(|part| {part.is_user()}).ok_or("the chat has no prompt to respond to".to_string())? + 1;
+            splice_parts(&mut chat, cut..parts.len(), Vec::new());
+            let config_opt = db_state.cursors.chosen_config.and_then(|config| {db_state.db.configs.get_configs().get(&config).cloned()});
+            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat.clone()))]));
+            db_state.dispatch(DatabaseAction::PrunePartData(chat_id));
+            db_state.dispatch(DatabaseAction::StartSending(chat_id));
+            request_response(db_state, proxima_state, alerts_state, chat_id, chat.context.clone(), config_opt, PromptSettings::current(&db_state.cursors)).await;
+        }
+    }
+    Ok(())
+}
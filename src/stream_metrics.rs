@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use proxima_backend::database::chats::{Chat, ChatID};
+use serde::{Deserialize, Serialize};
+
+use crate::local_store::MergeSaved;
+use crate::context_parts::part_key;
+
+pub const METRICS_STORE:&str = "stream_metrics";
+/// Gaps between two streamed tokens longer than this count as stalls
+pub const STALL_MILLIS:i64 = 2000;
+pub const NO_CONFIG:&str = "No configuration";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResponseMetrics {
+    pub config:String,
+    pub started_at:DateTime<Utc>,
+    pub first_token_at:Option<DateTime<Utc>>,
+    pub finished_at:DateTime<Utc>,
+    pub tokens:usize,
+    pub stalls:usize,
+    pub longest_gap_ms:i64
+}
+
+impl ResponseMetrics {
+    /// `token_times` are the arrival times of the stream events, in order, they give the timings but an event can
+    /// carry any number of tokens so `tokens` is counted on the streamed text by the tokenizer of the configuration
+    pub fn from_token_times(config:String, started_at:DateTime<Utc>, token_times:&[DateTime<Utc>], tokens:usize, finished_at:DateTime<Utc>) -> Self {
+        let mut stalls = 0;
+        let mut longest_gap_ms = 0;
+        for pair in token_times.windows(2) {
+            let gap = pair[1].signed_duration_since(pair[0]).num_milliseconds();
+            longest_gap_ms = longest_gap_ms.max(gap);
+            if gap > STALL_MILLIS {
+                stalls += 1;
+            }
+        }
+        Self { config, started_at, first_token_at: token_times.first().cloned(), finished_at, tokens, stalls, longest_gap_ms }
+    }
+    pub fn ttft_ms(&self) -> Option<i64> {
+        self.first_token_at.map(|first| {first.signed_duration_since(self.started_at).num_milliseconds()})
+    }
+    pub fn generation_ms(&self) -> i64 {
+        self.finished_at.signed_duration_since(self.started_at).num_milliseconds()
+    }
+    /// Milliseconds spent streaming, from the first token to the end of the response
+    pub fn streaming_ms(&self) -> Option<i64> {
+        self.first_token_at.map(|first| {self.finished_at.signed_duration_since(first).num_milliseconds()})
+    }
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let streaming_ms = self.streaming_ms()?;
+        if self.tokens > 0 && streaming_ms > 0 {
+            Some(self.tokens as f64 * 1000.0 / streaming_ms as f64)
+        }
+        else {
+            None
+        }
+    }
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::with_capacity(5);
+        if let Some(ttft) = self.ttft_ms() {
+            parts.push(format!("first token {}", format_millis(ttft)));
+        }
+        parts.push(format!("~{} tokens", self.tokens));
+        if let Some(speed) = self.tokens_per_second() {
+            parts.push(format!("{speed:.1} tok/s"));
+        }
+        parts.push(format!("total {}", format_millis(self.generation_ms())));
+        if self.stalls > 0 {
+            parts.push(format!("{} stall(s), longest {}", self.stalls, format_millis(self.longest_gap_ms)));
+        }
+        parts.join(" - ")
+    }
+}
+
+pub fn format_millis(millis:i64) -> String {
+    if millis < 1000 {
+        format!("{millis} ms")
+    }
+    else {
+        format!("{:.1} s", millis as f64 / 1000.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ConfigAggregate {
+    pub responses:usize,
+    pub streamed_responses:usize,
+    pub tokens:usize,
+    pub total_generation_ms:i64,
+    pub total_ttft_ms:i64,
+    pub total_streaming_ms:i64,
+    pub stalls:usize
+}
+
+impl ConfigAggregate {
+    fn add(&mut self, metrics:&ResponseMetrics) {
+        self.responses += 1;
+        self.total_generation_ms += metrics.generation_ms();
+        if let (Some(ttft), Some(streaming)) = (metrics.ttft_ms(), metrics.streaming_ms()) {
+            self.streamed_responses += 1;
+            self.tokens += metrics.tokens;
+            self.total_ttft_ms += ttft;
+            self.total_streaming_ms += streaming;
+            self.stalls += metrics.stalls;
+        }
+    }
+    fn merge(&mut self, other:Self) {
+        self.responses += other.responses;
+        self.streamed_responses += other.streamed_responses;
+        self.tokens += other.tokens;
+        self.total_generation_ms += other.total_generation_ms;
+        self.total_ttft_ms += other.total_ttft_ms;
+        self.total_streaming_ms += other.total_streaming_ms;
+        self.stalls += other.stalls;
+    }
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{} responses", self.responses), format!("average total {}", format_millis(self.total_generation_ms / self.responses.max(1) as i64))];
+        if self.streamed_responses > 0 {
+            parts.push(format!("average first token {}", format_millis(self.total_ttft_ms / self.streamed_responses as i64)));
+            if self.total_streaming_ms > 0 {
+                parts.push(format!("{:.1} tok/s", self.tokens as f64 * 1000.0 / self.total_streaming_ms as f64));
+            }
+            parts.push(format!("{} stall(s)", self.stalls));
+        }
+        parts.join(" - ")
+    }
+}
+
+/// When the tags of a streamed part arrived, by the byte offset of their `<` in the text of the part, so that
+/// calls and sub-agents made within a single part can be timed
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TagTimes(pub Vec<(usize, DateTime<Utc>)>);
+
+impl TagTimes {
+    /// Marks the tags starting in text added at `offset` of the part
+    pub fn mark(&mut self, offset:usize, text:&str, now:DateTime<Utc>) {
+        self.0.extend(text.match_indices('<').map(|(index, _)| {(offset + index, now)}));
+    }
+    /// When the text at this offset arrived, as told by the last tag starting at or before it
+    pub fn at(&self, offset:usize) -> Option<DateTime<Utc>> {
+        let after = self.0.partition_point(|(marked, _)| {*marked <= offset});
+        self.0[..after].last().map(|(_, time)| {*time})
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Metrics of a part are kept under its part_key, so that they stay with it when parts before it are removed
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct MetricsStore {
+    pub messages:HashMap<ChatID, BTreeMap<String, ResponseMetrics>>,
+    pub configs:HashMap<String, ConfigAggregate>,
+    /// Tag times of every streamed part, under their part_key
+    #[serde(default)]
+    pub tag_times:HashMap<ChatID, BTreeMap<String, TagTimes>>
+}
+
+impl MetricsStore {
+    /// Streamed responses are kept for their last part, every response counts towards its configuration
+    pub fn record(&mut self, chat_id:ChatID, part:Option<String>, metrics:ResponseMetrics) {
+        self.configs.entry(metrics.config.clone()).or_default().add(&metrics);
+        if let Some(part) = part {
+            self.messages.entry(chat_id).or_default().insert(part, metrics);
+        }
+    }
+    pub fn record_tag_times(&mut self, chat_id:ChatID, part:String, times:TagTimes) {
+        if !times.is_empty() {
+            self.tag_times.entry(chat_id).or_default().insert(part, times);
+        }
+    }
+    pub fn get(&self, chat_id:ChatID, part:&str) -> Option<&ResponseMetrics> {
+        self.messages.get(&chat_id)?.get(part)
+    }
+    pub fn get_tag_times(&self, chat_id:ChatID, part:&str) -> Option<&TagTimes> {
+        self.tag_times.get(&chat_id)?.get(part)
+    }
+    pub fn has_chat(&self, chat_id:ChatID) -> bool {
+        self.messages.contains_key(&chat_id) || self.tag_times.contains_key(&chat_id)
+    }
+    /// Drops the metrics of parts the chat does not have anymore, or of the whole chat once it is removed
+    pub fn prune_chat(&mut self, chat_id:ChatID, chat:Option<&Chat>) {
+        let parts = chat.map(|chat| {chat.context.get_parts().iter().map(part_key).collect::<HashSet<String>>()});
+        prune_parts(&mut self.messages, chat_id, parts.as_ref());
+        prune_parts(&mut self.tag_times, chat_id, parts.as_ref());
+    }
+}
+
+fn prune_parts<T>(by_chat:&mut HashMap<ChatID, BTreeMap<String, T>>, chat_id:ChatID, parts:Option<&HashSet<String>>) {
+    let Some(measured) = by_chat.get_mut(&chat_id) else {
+        return
+    };
+    if let Some(parts) = parts {
+        measured.retain(|part, _| {parts.contains(part)});
+    }
+    if parts.is_none() || measured.is_empty() {
+        by_chat.remove(&chat_id);
+    }
+}
+
+impl MergeSaved for MetricsStore {
+    /// Responses that finished before loading are added to the saved ones
+    fn merge_saved(&mut self, saved:Self) {
+        for (chat_id, parts) in saved.messages {
+            let measured = self.messages.entry(chat_id).or_default();
+            for (part, metrics) in parts {
+                measured.entry(part).or_insert(metrics);
+            }
+        }
+        for (chat_id, parts) in saved.tag_times {
+            let timed = self.tag_times.entry(chat_id).or_default();
+            for (part, times) in parts {
+                timed.entry(part).or_insert(times);
+            }
+        }
+        for (config, aggregate) in saved.configs {
+            self.configs.entry(config).or_default().merge(aggregate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn second(second:i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + second, 0).unwrap()
+    }
+
+    #[test]
+    fn tags_are_marked_where_they_start_in_the_part() {
+        let mut times = TagTimes::default();
+        times.mark(0, "<think>", second(0));
+        times.mark(7, "plan</think>", second(1));
+        assert_eq!(times.0, vec![(0, second(0)), (11, second(1))]);
+        times.mark(19, "no tag", second(2));
+        assert_eq!(times.0.len(), 2);
+    }
+
+    #[test]
+    fn offsets_between_marks_take_the_mark_before_them() {
+        let times = TagTimes(vec![(4, second(0)), (10, second(1)), (20, second(2))]);
+        assert_eq!(times.at(4), Some(second(0)));
+        assert_eq!(times.at(9), Some(second(0)));
+        assert_eq!(times.at(10), Some(second(1)));
+        assert_eq!(times.at(15), Some(second(1)));
+        assert_eq!(times.at(1000), Some(second(2)));
+        // before the first tag there is nothing to tell
+        assert_eq!(times.at(3), None);
+        assert_eq!(TagTimes::default().at(0), None);
+    }
+}
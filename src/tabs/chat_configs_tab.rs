@@ -16,6 +16,8 @@ use yew::{AttrValue, Callback, Event, Html, MouseEvent, UseReducerHandle, functi
 
 use crate::app::{DatabaseAction, DatabaseState, PrintArgs, ProximaState, invoke, make_ai_request, make_db_request};
 use crate::db_sync::{get_delta_for_add, get_next_id_for_category};
+use crate::tokenizer::TokenizerKind;
+use crate::stream_metrics::ConfigAggregate;
 
 #[function_component(ChatConfigsTab)]
 pub fn chat_configs_tab() -> Html {
@@ -25,6 +27,7 @@ pub fn chat_configs_tab() -> Html {
 
     let cc_name_ref = use_node_ref();
     let cc_setting_ref = use_node_ref();
+    let tokenizer_ref = use_node_ref();
     let cc_setting_value_ref = use_node_ref();
     let cc_second_setting_value_ref = use_node_ref();
     let cc_third_setting_value_ref = use_node_ref();
@@ -645,6 +648,18 @@ pub fn chat_configs_tab() -> Html {
             }
         }
     };
+    let tokenizer_callback = {
+        let db_state = db_state.clone();
+        let tokenizer_ref = tokenizer_ref.clone();
+        Callback::from(move |evt:Event| {
+            if let Some(config_id) = db_state.cursors.config_for_modification {
+                let chosen = tokenizer_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+                if let Some(kind) = TokenizerKind::all().into_iter().find(|kind| {kind.name() == chosen}) {
+                    db_state.dispatch(DatabaseAction::SetTokenizer { config: config_id, kind });
+                }
+            }
+        })
+    };
     html!(
         <div class="chat-part">
             <div class="vertical-flex standard-padding-margin-corners first-level at-most-a-sixth-width max-height-of-container">
@@ -665,6 +680,31 @@ pub fn chat_configs_tab() -> Html {
                         }
                     }
                 </div>
+                {
+                    if db_state.stream_metrics.configs.is_empty() {
+                        html!()
+                    }
+                    else {
+                        let mut aggregates = db_state.stream_metrics.configs.iter().collect::<Vec<(&String, &ConfigAggregate)>>();
+                        aggregates.sort_by(|(name_a, _), (name_b, _)| {name_a.cmp(name_b)});
+                        html!(
+                            <div>
+                                <hr/>
+                                <h2>{"Response metrics"}</h2>
+                                {
+                                    aggregates.into_iter().map(|(name, aggregate)| {
+                                        html!(
+                                            <div class="third-level standard-padding-margin-corners">
+                                                <b>{name.clone()}</b>
+                                                <p class="stream-metrics">{aggregate.summary()}</p>
+                                            </div>
+                                        )
+                                    }).collect::<Html>()
+                                }
+                            </div>
+                        )
+                    }
+                }
             </div>
             <div class="vertical-flex standard-padding-margin-corners first-level at-most-a-sixth-width max-height-of-container">
                 <div>
@@ -679,6 +719,26 @@ pub fn chat_configs_tab() -> Html {
                         }
                     }
                     </h2>
+                    {
+                        match db_state.cursors.config_for_modification {
+                            Some(config_id) => {
+                                let current = db_state.tokenizer_settings.kind_for(Some(config_id));
+                                html!(
+                                    <div class="label-input-combo">
+                                        <p>{"Tokenizer : "}</p>
+                                        <select class="standard-padding-margin-corners" ref={tokenizer_ref} onchange={tokenizer_callback}>
+                                        {
+                                            TokenizerKind::all().into_iter().map(|kind| {
+                                                html!(<option value={kind.name()} selected={kind == current}>{kind.name()}</option>)
+                                            }).collect::<Html>()
+                                        }
+                                        </select>
+                                    </div>
+                                )
+                            },
+                            None => html!()
+                        }
+                    }
                     <select class="most-horizontal-space-no-flex standard-padding-margin-corners" ref={cc_setting_ref} onchange={select_settings_callback}>
                         <option value={"Temperature"}>{"Temperature"}</option>
                         <option value={"TopP"}>{"Top P"}</option>
@@ -1,38 +1,59 @@
-use std::collections::HashSet;
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::StreamExt;
 use gloo_events::EventListener;
-use gloo_utils::format::JsValueSerdeExt;
 use html_parser::{Dom, Node};
 use markdown::to_html;
-use proxima_backend::ai_interaction::endpoint_api::{EndpointRequestVariant, EndpointResponseVariant};
+use proxima_backend::ai_interaction::endpoint_api::EndpointResponseVariant;
 use proxima_backend::database::access_modes::AMSetting;
-use proxima_backend::database::chats::{Chat, ChatID, SessionType};
-use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
-use proxima_backend::database::media::{Base64EncodedString, Media, MediaType};
+use proxima_backend::database::chats::{Chat, ChatID};
+use proxima_backend::database::context::{ContextData, ContextPosition};
+use proxima_backend::database::media::{Base64EncodedString, MediaType};
 use proxima_backend::database::{DatabaseItem, DatabaseItemID, DatabaseReplyVariant, DatabaseRequestVariant};
 use proxima_backend::web_payloads::{DBPayload, DBResponse};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tauri_sys::dpi::PhysicalPosition;
 use tauri_sys::window::DragDropEvent;
 use wasm_bindgen_futures::spawn_local;
-use web_sys::HtmlElement;
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlElement};
 use yew::virtual_dom::VNode;
-use yew::{AttrValue, Callback, Event, Html, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_effect_with, use_node_ref, use_state_eq};
+use yew::{AttrValue, Callback, ContextProvider, Event, Html, InputEvent, KeyboardEvent, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_effect_with, use_mut_ref, use_node_ref, use_state_eq};
 
-use crate::alerts::Alerts;
-use crate::app::{DatabaseAction, DatabaseState, PrintArgs, ProximaState, invoke, make_ai_request, make_db_request, print};
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, PrintArgs, ProximaState, make_db_request, print, update_chat};
 use crate::db_sync::get_delta_for_add;
-use crate::html_parsing::{HtmlNode, parse_html};
+use crate::export::ExportPanel;
+use crate::import::ImportPanel;
+use crate::context_parts::part_key;
+use crate::ratings::{RatingFilter, ResponseRating, is_ratings_part, rating_of, remove_part, set_rating};
+use crate::titles::TitlePanel;
+use crate::context_inspector::{ContextInspector, effective_context};
+use crate::tokenizer::{count_parts, format_estimate, max_context_length};
+use crate::stream_metrics::TagTimes;
+use crate::compaction::CompactPanel;
+use crate::prompt_queue::{PromptSettings, QueuedPrompts, add_prompt_to_chat, request_response, starting_context_with_config};
+use crate::drafts::Draft;
+use crate::files::{attach_media, confirm, upload_prompt_files};
+use crate::slash_commands::{parse_command, run_command};
+use crate::code_blocks::{CodeBlock, MarkdownChunk, split_code_blocks};
+use crate::tool_views::{CallInfo, TextTiming, ToolCallView, ToolOutputView, element_span};
+use crate::html_parsing::{HtmlNode, ParsedHtml, TagKind};
+use crate::live_responses::ShownPart;
+use crate::render_cache::{cached_markdown, cached_parse_tags, cached_token_count};
+use crate::math::{extract_math, restore_math};
+use crate::memories::memory_ids;
+use crate::find_matches::{count_matches, highlight_in_html};
+use crate::templates::TemplatePanel;
 
-#[derive(Serialize, Deserialize)]
-pub struct FileArgs {
-    test1: PathBuf,
-    test2:String, 
-    test3:String
-}
+/// Parts of a chat mounted at once, the others are only mounted when scrolled to
+const PART_WINDOW:usize = 40;
+/// Parts the window moves by when an edge of the chat is reached
+const PART_WINDOW_STEP:usize = 20;
+/// Distance to an edge of the chat, in pixels, at which the window moves
+const SCROLL_EDGE:i32 = 300;
 
 #[derive(Deserialize, Clone)]
 pub struct SpecialDragDrop {
@@ -87,6 +108,191 @@ pub fn chat_tab() -> Html {
     let file_ref = use_node_ref();
     let files_state = use_state_eq(Vec::<PathBuf>::default);
     let sort_state = use_state_eq(|| {SortingMode::None});
+    let find_ref = use_node_ref();
+    let chat_list_ref = use_node_ref();
+    let find_query = use_state_eq(String::new);
+    let find_position = use_state_eq(|| {0_usize});
+    let find_total = use_state_eq(|| {0_usize});
+    // the query, position and chat last scrolled to, so new tokens only recount the matches
+    let find_scrolled_to = use_mut_ref(|| {None::<(String, usize, Option<usize>)>});
+    let show_export = use_state_eq(|| {false});
+    let show_import = use_state_eq(|| {false});
+    let show_title = use_state_eq(|| {false});
+    let show_compact = use_state_eq(|| {false});
+    let show_inspector = use_state_eq(|| {false});
+    let show_templates = use_state_eq(|| {false});
+    let draft_loaded_for = use_state_eq(|| {None::<Option<usize>>});
+    // how far back in the chat's prompts Up went, and what was typed before
+    let history_position = use_state_eq(|| {None::<usize>});
+    let history_stash = use_state_eq(String::new);
+    let rating_filter = use_state_eq(|| {RatingFilter::Any});
+    let rating_filter_ref = use_node_ref();
+    // parts of the chosen chat that are mounted, None follows the latest ones
+    let part_window = use_state_eq(|| {None::<(usize, usize)>});
+    // a part and where it was on screen before the window moved, to keep it there afterwards
+    let scroll_anchor = use_mut_ref(|| {None::<(usize, f64)>});
+
+    let part_count = db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.part_count(chat_id)}).unwrap_or(0);
+    // matches are looked for in the page, so the whole chat is mounted while finding
+    let (window_start, window_end) = if !find_query.is_empty() {
+        (0, part_count)
+    }
+    else {
+        match *part_window {
+            Some((start, end)) => (start.min(part_count), end.min(part_count)),
+            None => (part_count.saturating_sub(PART_WINDOW), part_count)
+        }
+    };
+
+    use_effect_with(
+        (window_start, window_end),
+        {
+            let chat_list_ref = chat_list_ref.clone();
+            let scroll_anchor = scroll_anchor.clone();
+            move |_:&(usize, usize)| {
+                if let Some((index, old_top)) = scroll_anchor.borrow_mut().take() && let Some(list) = chat_list_ref.cast::<Element>() && let Ok(Some(part)) = list.query_selector(&format!("[data-part-index=\"{index}\"]")) {
+                    let moved = part.get_bounding_client_rect().top() - old_top;
+                    list.set_scroll_top(list.scroll_top() + moved as i32);
+                }
+            }
+        }
+    );
+
+    use_effect_with(
+        (db_state.cursors.focused_part, db_state.cursors.chosen_chat),
+        {
+            let part_window = part_window.clone();
+            move |(focused, _):&(Option<usize>, Option<usize>)| {
+                // a new chat starts on its latest parts, unless a part of it was picked
+                match focused {
+                    Some(index) if *index < window_start || *index >= window_end => part_window.set(Some((index.saturating_sub(PART_WINDOW / 2), (index + PART_WINDOW / 2).min(part_count)))),
+                    Some(_) => (),
+                    None => part_window.set(None)
+                }
+            }
+        }
+    );
+
+    let part_scroll_callback = {
+        let chat_list_ref = chat_list_ref.clone();
+        let part_window = part_window.clone();
+        let scroll_anchor = scroll_anchor.clone();
+        let finding = !find_query.is_empty();
+        Callback::from(move |evt:Event| {
+            if finding {
+                return;
+            }
+            if let Some(list) = chat_list_ref.cast::<Element>() {
+                let to_top = list.scroll_top();
+                let to_bottom = list.scroll_height() - list.client_height() - to_top;
+                let (anchor, new_window) = if to_top < SCROLL_EDGE && window_start > 0 {
+                    let start = window_start.saturating_sub(PART_WINDOW_STEP);
+                    (window_start, Some((start, (start + PART_WINDOW).min(window_end))))
+                }
+                else if to_bottom < SCROLL_EDGE && window_end < part_count {
+                    let end = (window_end + PART_WINDOW_STEP).min(part_count);
+                    (window_end.saturating_sub(1), if end == part_count {None} else {Some((end.saturating_sub(PART_WINDOW).max(window_start), end))})
+                }
+                else {
+                    return;
+                };
+                if let Ok(Some(part)) = list.query_selector(&format!("[data-part-index=\"{anchor}\"]")) {
+                    *scroll_anchor.borrow_mut() = Some((anchor, part.get_bounding_client_rect().top()));
+                }
+                part_window.set(new_window);
+            }
+        })
+    };
+    let latest_parts_callback = {
+        let part_window = part_window.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            part_window.set(None);
+        })
+    };
+
+    use_effect_with(
+        ((*find_query).clone(), *find_position, db_state.cursors.chosen_chat, db_state.update_flipper),
+        {
+            let chat_list_ref = chat_list_ref.clone();
+            let find_total = find_total.clone();
+            let find_scrolled_to = find_scrolled_to.clone();
+            move |(query, position, chat, _):&(String, usize, Option<usize>, bool)| {
+                if let Some(list) = chat_list_ref.cast::<Element>() && let Ok(marks) = list.query_selector_all("mark.find-match") {
+                    let total = marks.length() as usize;
+                    find_total.set(total);
+                    let scroll_target = (query.clone(), *position, *chat);
+                    let should_scroll = find_scrolled_to.borrow().as_ref() != Some(&scroll_target);
+                    for i in 0..total {
+                        if let Some(node) = marks.item(i as u32) && let Ok(mark) = node.dyn_into::<Element>() {
+                            if i == position % total {
+                                mark.set_class_name("find-match current-find-match");
+                                if should_scroll {
+                                    mark.scroll_into_view();
+                                    *find_scrolled_to.borrow_mut() = Some(scroll_target.clone());
+                                }
+                            }
+                            else {
+                                mark.set_class_name("find-match");
+                            }
+                        }
+                    }
+                }
+                else if query.is_empty() {
+                    find_total.set(0);
+                }
+            }
+        }
+    );
+
+    use_effect_with(
+        db_state.cursors.chosen_chat,
+        {
+            let prompt_node_ref = prompt_node_ref.clone();
+            let files_state = files_state.clone();
+            let draft_loaded_for = draft_loaded_for.clone();
+            let history_position = history_position.clone();
+            let db_state = db_state.clone();
+            move |chat_id:&Option<usize>| {
+                history_position.set(None);
+                // every chat keeps its own unsent prompt and attachments
+                let draft = db_state.drafts.get(*chat_id);
+                if let Some(input) = prompt_node_ref.cast::<web_sys::HtmlInputElement>() {
+                    input.set_value(&draft.text);
+                }
+                files_state.set(draft.files);
+                draft_loaded_for.set(Some(*chat_id));
+            }
+        }
+    );
+
+    use_effect_with(
+        (*files_state).clone(),
+        {
+            let prompt_node_ref = prompt_node_ref.clone();
+            let draft_loaded_for = draft_loaded_for.clone();
+            let db_state = db_state.clone();
+            move |files:&Vec<PathBuf>| {
+                let chat_id = db_state.cursors.chosen_chat;
+                // until the chat's draft is restored the attachments shown belong to another chat
+                if *draft_loaded_for != Some(chat_id) {
+                    return;
+                }
+                let text = prompt_node_ref.cast::<web_sys::HtmlInputElement>().map(|input| {input.value()}).unwrap_or_default();
+                db_state.dispatch(DatabaseAction::SetDraft { chat: chat_id, draft: Draft { text, files: files.clone() } });
+            }
+        }
+    );
+
+    let draft_text_callback = {
+        let prompt_node_ref = prompt_node_ref.clone();
+        let files_state = files_state.clone();
+        let db_state = db_state.clone();
+        Callback::from(move |evt:Event| {
+            let chat_id = db_state.cursors.chosen_chat;
+            let draft = Draft { text: prompt_node_ref.cast::<web_sys::HtmlInputElement>().unwrap().value(), files: (*files_state).clone() };
+            db_state.dispatch(DatabaseAction::SetDraft { chat: chat_id, draft });
+        })
+    };
 
     use_effect_with(
         files_state.clone(),
@@ -155,6 +361,7 @@ pub fn chat_tab() -> Html {
             spawn_local(async move {
                 if let Some(chat_id) = db_state.cursors.chosen_chat {
                     db_state.dispatch(DatabaseAction::SetChat(None));
+                    db_state.dispatch(DatabaseAction::SetDraft { chat: Some(chat_id), draft: Draft::default() });
                     let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Remove(DatabaseItemID::Chat(chat_id)) };
                     match make_db_request(json_request, proxima_state.chat_url.clone()).await {
                         Ok(response) => {
@@ -168,109 +375,51 @@ pub fn chat_tab() -> Html {
         })
     };
 
-    let prompt_send_callback = {
+    // sends a prompt that is not a command and that the chat is free to answer
+    let send_prompt = {
         let prompt = prompt_node_ref.clone();
         let proxima_state = proxima_state.clone();
         let db_state = db_state.clone();
         let files_state = files_state.clone();
-        Callback::from(move |mouse_evt:MouseEvent| {
-            let prompt_text = prompt.cast::<web_sys::HtmlInputElement>()
-            .unwrap()
-            .value();
-            prompt.cast::<web_sys::HtmlInputElement>()
-            .unwrap().set_value("");
-            let (mut local_id, mut starting_context, created, mut start_chat, config_opt) = match (db_state.cursors.chosen_chat.clone()) {
-                Some(chatid) => {
-                    let mut chat = db_state.db.chats.get_chats().get(&chatid).unwrap().clone();
-                    let (context_part, config_opt) = match db_state.cursors.chosen_config {
-                        Some(config) => {
-                            chat.config = Some(config);
-                            if let Some(config_clone) = db_state.db.configs.get_configs().get(&config) {
-                                chat.latest_used_config = Some(config_clone.clone());
-                                match &config_clone.tools {
-                                    Some(tools) => {
-                                        (ContextPart::new_user_prompt_with_tools(vec![ContextData::Text(prompt_text)]), Some(config_clone.clone()))
-                                    },
-                                    None => (ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User), Some(config_clone.clone()))
-                                }
-                            }
-                            else {
-                                panic!("Impossible, config should still exist here (except if server deleted it)")
-                            }
-                            
-                        },
-                        None => {
-                            chat.config = None;
-                            chat.latest_used_config = None;
-                            (ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User), None)
-                        }
-                    };
-                    chat.add_to_context(context_part);
-                    match &config_opt {
-                        Some(configuration) => chat.context.add_per_turn_settings(configuration),
-                        None => ()
-                    }
-                    (chatid, chat.get_context().clone(), false, chat.clone(), config_opt)
-                },
-                None => {
-                    let (starting_context, config_opt) = match db_state.cursors.chosen_config {
-                        Some(config) => {
-                            if let Some(config_clone) = db_state.db.configs.get_configs().get(&config) {
-                                match config_clone.tools.clone() {
-                                    Some(tools) => {
-                                        (WholeContext::new_with_all_settings(vec![ContextPart::new_user_prompt_with_tools(vec![ContextData::Text(prompt_text)])], &config_clone), Some(config_clone.clone()))
-                                    },
-                                    None => (WholeContext::new_with_all_settings(vec![ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User)], &config_clone), Some(config_clone.clone()))
-                                }
-                            }
-                            else {
-                                panic!("Impossible, config should still exist here (except if server deleted it)")
-                            }
-                        },
-                        None => {
-                            (WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(prompt_text)], ContextPosition::User)]), None)
-                        }
-                    };
+        let alerts_state = alerts_state.clone();
+        Callback::from(move |prompt_text:String| {
+            let settings = PromptSettings::current(&db_state.cursors);
+            let added = match db_state.cursors.chosen_chat {
+                Some(chatid) => add_prompt_to_chat(&db_state, db_state.chat(chatid).unwrap().clone(), prompt_text).map(|(chat, config_opt)| {
+                    (chatid, chat.get_context().clone(), false, chat, config_opt)
+                }),
+                None => starting_context_with_config(&db_state, prompt_text, settings.config).map(|(starting_context, config_opt)| {
                     let mut new_chat = db_state.db.chats.create_possible_chat(starting_context.clone(), None, proxima_state.device_id, config_opt.clone());
-                    let new_chatid = new_chat.id;
-                    new_chat.access_modes.insert(db_state.cursors.chosen_access_mode);
-                    db_state.dispatch(DatabaseAction::SetChat(Some(new_chatid)));
-                    (new_chatid, starting_context, true, new_chat, config_opt)
+                    new_chat.access_modes.insert(settings.access_mode);
+                    (new_chat.id, starting_context, true, new_chat, config_opt)
+                })
+            };
+            let (mut local_id, mut starting_context, created, mut start_chat, config_opt) = match added {
+                Ok(added) => added,
+                Err(error) => {
+                    alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Prompt not sent, {error}"))));
+                    return;
                 }
             };
+            if created {
+                db_state.dispatch(DatabaseAction::SetChat(Some(local_id)));
+            }
+            else {
+                db_state.dispatch(DatabaseAction::StartSending(local_id));
+            }
+            prompt.cast::<web_sys::HtmlInputElement>()
+            .unwrap().set_value("");
+            db_state.dispatch(DatabaseAction::SetDraft { chat: db_state.cursors.chosen_chat, draft: Draft::default() });
             db_state.dispatch(DatabaseAction::SetTab(1));
             let proxima_state = proxima_state.clone();
             let db_state = db_state.clone();
             let files_state = files_state.clone();
+            let alerts_state = alerts_state.clone();
             spawn_local(async move {
                 let files = (*files_state).clone();
-                if files.len() > 0 {
-                    for file in files {
-                        let args = serde_wasm_bindgen::to_value(&FileArgs {test1:file.clone(), test2:format!("{}/db", proxima_state.chat_url.clone()), test3:proxima_state.auth_token.clone()}).unwrap();
-
-                        let return_val = invoke("add_media_from_file_if_exists", args).await;
-                        
-                        let value =
-                        return_val
-                        .into_serde::<(String, String, MediaType)>();
-
-                        if let Ok((hash, file_name, media_type)) = value {
-                            let mut last_user = None;
-                            for (i,part) in starting_context.get_parts().iter().enumerate() {
-                                if let ContextPosition::User = part.get_position() {
-                                    last_user = Some(i);
-                                }
-                            }
-                            if let Some(i) = last_user {
-                                starting_context.get_parts_mut()[i].add_data(ContextData::Media(hash.clone()));
-                            }
-                            else {
-                                starting_context.add_part(ContextPart::new(vec![ContextData::Media(hash.clone())], ContextPosition::User));
-                            }
-                            start_chat.context = starting_context.clone();
-                            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Media(hash.clone()), DatabaseItem::Media(Media {hash, media_type, file_name, tags:HashSet::new(), access_modes:HashSet::from([0]), added_at:Utc::now()}, Base64EncodedString::new(vec![])))]));
-                        }
-                    }
+                if !files.is_empty() {
+                    attach_media(&mut starting_context, upload_prompt_files(&db_state, &proxima_state, files).await);
+                    start_chat.context = starting_context.clone();
                     files_state.set(Vec::new());
                 }
                 if created {
@@ -287,26 +436,156 @@ pub fn chat_tab() -> Html {
                     }
 
                     db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+                    db_state.dispatch(DatabaseAction::StartSending(local_id));
                 }
                 else {
                     db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(local_id), DatabaseItem::Chat(start_chat.clone()))]));
                 }
-                db_state.dispatch(DatabaseAction::AddToOngoingChats { chat: local_id });
+                request_response(&db_state, &proxima_state, &alerts_state, local_id, starting_context, config_opt, settings).await;
+            });
+        })
+    };
 
-                let streaming = config_opt.as_ref().is_some_and(|conf| {conf.is_streaming()});
+    let prompt_send_callback = {
+        let prompt = prompt_node_ref.clone();
+        let proxima_state = proxima_state.clone();
+        let db_state = db_state.clone();
+        let files_state = files_state.clone();
+        let alerts_state = alerts_state.clone();
+        let history_position = history_position.clone();
+        let send_prompt = send_prompt.clone();
+        Callback::from(move |_:()| {
+            let mut prompt_text = prompt.cast::<web_sys::HtmlInputElement>()
+            .unwrap()
+            .value();
+            history_position.set(None);
+            match parse_command(&prompt_text) {
+                Some(Ok(command)) => {
+                    prompt.cast::<web_sys::HtmlInputElement>().unwrap().set_value("");
+                    let db_state = db_state.clone();
+                    let proxima_state = proxima_state.clone();
+                    let alerts_state = alerts_state.clone();
+                    db_state.dispatch(DatabaseAction::SetDraft { chat: db_state.cursors.chosen_chat, draft: Draft { text: String::new(), files: (*files_state).clone() } });
+                    spawn_local(async move {
+                        if let Err(error) = run_command(&db_state, &proxima_state, &alerts_state, command).await {
+                            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Command failed : {error}"))));
+                        }
+                    });
+                    return;
+                },
+                Some(Err(error)) => {
+                    alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, error)));
+                    return;
+                },
+                None => if prompt_text.trim_start().starts_with("//") {
+                    // "//" escapes prompts that really start with a slash
+                    prompt_text = prompt_text.replacen("//", "/", 1);
+                }
+            }
+            if let Some(chat_id) = db_state.cursors.chosen_chat && db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats) {
+                if !prompt_text.trim().is_empty() {
+                    // the files go with the queued prompt
+                    db_state.dispatch(DatabaseAction::QueuePrompt { chat: chat_id, text: prompt_text, settings: PromptSettings::current(&db_state.cursors), files: (*files_state).clone() });
+                    prompt.cast::<web_sys::HtmlInputElement>().unwrap().set_value("");
+                    files_state.set(Vec::new());
+                    db_state.dispatch(DatabaseAction::SetDraft { chat: Some(chat_id), draft: Draft::default() });
+                }
+                return;
+            }
+            let config = db_state.cursors.chosen_config.and_then(|config| {db_state.db.configs.get_configs().get(&config)});
+            if let Some(max) = max_context_length(config) {
+                let chat = db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.chat(chat_id)});
+                let tokenizer = db_state.tokenizer_settings.kind_for(db_state.cursors.chosen_config).tokenizer();
+                let used = count_parts(tokenizer.as_ref(), effective_context(chat, config, &prompt_text).get_parts());
+                if used > max {
+                    // it is only sent once the warning is confirmed
+                    let send_prompt = send_prompt.clone();
+                    spawn_local(async move {
+                        if confirm("Context too long", &format!("This chat and prompt take about {} tokens, more than the {max} of the configuration, so the start of the chat may be cut off. Send anyway ?", format_estimate(used)), "Send anyway").await {
+                            send_prompt.emit(prompt_text);
+                        }
+                    });
+                    return;
+                }
+            }
+            send_prompt.emit(prompt_text);
+        })
+    };
 
-                let json_request = proxima_backend::web_payloads::AIPayload::new(proxima_state.auth_token.clone(), EndpointRequestVariant::RespondToFullPrompt { whole_context: starting_context, streaming, session_type: SessionType::Chat, chat_settings:config_opt, chat_id:Some(local_id), access_mode:db_state.cursors.chosen_access_mode });
+    let find_input_callback = {
+        let find_ref = find_ref.clone();
+        let find_query = find_query.clone();
+        let find_position = find_position.clone();
+        Callback::from(move |input_evt:InputEvent| {
+            find_query.set(find_ref.cast::<web_sys::HtmlInputElement>().unwrap().value());
+            find_position.set(0);
+        })
+    };
+    let find_next_callback = {
+        let find_position = find_position.clone();
+        let find_total = find_total.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            if *find_total > 0 {
+                find_position.set((*find_position + 1) % *find_total);
+            }
+        })
+    };
+    let find_previous_callback = {
+        let find_position = find_position.clone();
+        let find_total = find_total.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            if *find_total > 0 {
+                find_position.set((*find_position + *find_total - 1) % *find_total);
+            }
+        })
+    };
 
+    let export_toggle_callback = {
+        let show_export = show_export.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_export.set(!*show_export);
+        })
+    };
 
-                let value = make_ai_request(json_request, proxima_state.chat_url.clone(), local_id).await;
-                db_state.dispatch(DatabaseAction::RemoveFromOngoingChats { chat: local_id });
-                match value {
-                    Ok(response) => {
-                        
-                    },
-                    Err(_) => ()
-                }
-            });
+    let inspector_toggle_callback = {
+        let show_inspector = show_inspector.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_inspector.set(!*show_inspector);
+        })
+    };
+
+    let templates_toggle_callback = {
+        let show_templates = show_templates.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_templates.set(!*show_templates);
+        })
+    };
+    let compact_toggle_callback = {
+        let show_compact = show_compact.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_compact.set(!*show_compact);
+        })
+    };
+
+    let title_toggle_callback = {
+        let show_title = show_title.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_title.set(!*show_title);
+        })
+    };
+
+    let import_toggle_callback = {
+        let show_import = show_import.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_import.set(!*show_import);
+        })
+    };
+
+    let rating_filter_callback = {
+        let rating_filter = rating_filter.clone();
+        let rating_filter_ref = rating_filter_ref.clone();
+        Callback::from(move |evt:Event| {
+            rating_filter.set(RatingFilter::from_value(&rating_filter_ref.cast::<web_sys::HtmlInputElement>().unwrap().value()));
         })
     };
 
@@ -323,7 +602,7 @@ pub fn chat_tab() -> Html {
         })
     };
     let sort_title = sort_state.get_title();
-    let mut chat_refs = db_state.db.chats.get_chats().iter().collect::<Vec<(&ChatID, &Chat)>>();
+    let mut chat_refs = db_state.chats.iter().collect::<Vec<(&ChatID, &Chat)>>();
     match *sort_state {
         SortingMode::None => (),
         SortingMode::AscendingTime => chat_refs.sort_by(|(_,chat1), (_,chat2)| {chat1.latest_message.cmp(&chat2.latest_message)}),
@@ -343,7 +622,7 @@ pub fn chat_tab() -> Html {
             })
         };
 
-        if !chat.access_modes.contains(&db_state.cursors.chosen_access_mode) {
+        if !chat.access_modes.contains(&db_state.cursors.chosen_access_mode) || !rating_filter.matches_chat(chat) {
             html!()
         }
         else if let Some(chosen_id) = db_state.cursors.chosen_chat && chosen_id == **id {
@@ -360,13 +639,64 @@ pub fn chat_tab() -> Html {
         }
     }).collect::<Html>();
 
-    let chosen_chat_by_id = db_state.db.chats.get_chats().get(&(db_state.cursors.chosen_chat.unwrap_or(1000000)));
+    // saved chat, the parts being streamed are only built for the parts shown
+    let chosen_chat_by_id = db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.chat(chat_id)});
     let config_htmls:Vec<Html> = db_state.db.configs.get_configs().iter().map(|(id, config)| {
         html!(
-            <option value={config.name.clone()}>{config.name.clone()}</option>
+            <option value={config.name.clone()} selected={db_state.cursors.chosen_config == Some(*id)}>{config.name.clone()}</option>
         )
     }).collect();
 
+    let prompt_key_callback = {
+        let prompt = prompt_node_ref.clone();
+        let prompt_send_callback = prompt_send_callback.clone();
+        let history_position = history_position.clone();
+        let history_stash = history_stash.clone();
+        let history = chosen_chat_by_id.map(prompt_history).unwrap_or_default();
+        Callback::from(move |key_evt:KeyboardEvent| {
+            if key_evt.is_composing() {
+                return;
+            }
+            let input = prompt.cast::<web_sys::HtmlInputElement>().unwrap();
+            let text = input.value();
+            let caret = caret_byte_offset(&text, input.selection_start().ok().flatten().unwrap_or(0) as usize);
+            let (before_caret, after_caret) = text.split_at(caret);
+            match key_evt.key().as_str() {
+                "Enter" if !key_evt.shift_key() => {
+                    key_evt.prevent_default();
+                    prompt_send_callback.emit(());
+                },
+                // history is only recalled from the first or last line, so moving inside a multiline prompt still works
+                "ArrowUp" if !before_caret.contains('\n') && !history.is_empty() => {
+                    let position = match *history_position {
+                        Some(position) => (position + 1).min(history.len() - 1),
+                        None => {
+                            history_stash.set(text.clone());
+                            0
+                        }
+                    };
+                    key_evt.prevent_default();
+                    input.set_value(&history[history.len() - 1 - position]);
+                    history_position.set(Some(position));
+                },
+                "ArrowDown" if !after_caret.contains('\n') && history_position.is_some() => {
+                    key_evt.prevent_default();
+                    match *history_position {
+                        Some(0) | None => {
+                            input.set_value(&history_stash);
+                            history_position.set(None);
+                        },
+                        Some(position) => {
+                            input.set_value(&history[history.len() - position]);
+                            history_position.set(Some(position - 1));
+                        }
+                    }
+                },
+                _ => ()
+            }
+        })
+    };
+
     let cc_select_callback = {
         let select_node = cc_select_ref.clone();
         let db_state = db_state.clone();
@@ -409,31 +739,49 @@ pub fn chat_tab() -> Html {
             <div><button onclick={callback} class="chat-option chosen-chat text-left">{shorten_title_to_x_chars_from_end(chat.to_string_lossy().to_string(), 20) }</button></div>
         )
     }).collect::<Html>();
-    let (disabled, button_style) = if let Some(chat_id) = db_state.cursors.chosen_chat {
-        if db_state.ongoing_chats.contains(&chat_id) {
-            (true, "mainapp-unused-button standard-padding-margin-corners")
-        }
-        else {
-            (false, "mainapp-button standard-padding-margin-corners")
-        }
-    } else {
-        (false, "mainapp-button standard-padding-margin-corners")
+    // prompts sent while the chat is busy are queued instead
+    let queueing = db_state.cursors.chosen_chat.is_some_and(|chat_id| {db_state.prompt_queues.is_busy(chat_id, &db_state.ongoing_chats)});
+    let send_label = if queueing {
+        "Queue prompt"
+    }
+    else {
+        "Send"
     };
 
-    let ui_settings = if let Some(access_mode) = db_state.db.access_modes.get_modes().get(&db_state.cursors.chosen_access_mode) {
-        ChatUISettings {
-            hide_time_tool: if let Some(AMSetting::Bool(val)) = access_mode.am_settings.get(&"Hide time tool".to_string()) {
-                *val
-            }
-            else {
-                false
+    let live_metrics = db_state.cursors.chosen_chat.and_then(|chat_id| {db_state.live_metrics(chat_id)});
+    let usage_meter = match chosen_chat_by_id {
+        Some(chat) => {
+            let config = db_state.cursors.chosen_config.and_then(|config| {db_state.db.configs.get_configs().get(&config)});
+            let kind = db_state.tokenizer_settings.kind_for(db_state.cursors.chosen_config);
+            // what is being streamed is already counted by the live metrics
+            let streamed = live_metrics.as_ref().map(|metrics| {metrics.tokens}).unwrap_or(0);
+            let used = effective_context(Some(chat), config, "").get_parts().iter().map(|part| {cached_token_count(kind, part)}).sum::<usize>() + streamed;
+            match max_context_length(config) {
+                Some(max) => {
+                    let percent = (used * 100 / max.max(1)).min(100);
+                    html!(
+                        <div class="horizontal-flex">
+                            <div class="usage-bar most-horizontal-space standard-padding-margin-corners">
+                                <div class={if used > max {"usage-bar-fill usage-bar-over"} else {"usage-bar-fill"}} style={format!("width: {percent}%;")}></div>
+                            </div>
+                            <p class="standard-padding-margin-corners">{format!("{} / {max} tokens (estimate)", format_estimate(used))}</p>
+                        </div>
+                    )
+                },
+                None => html!(<p class="standard-padding-margin-corners">{format!("{} tokens, estimated (no max context length in this configuration)", format_estimate(used))}</p>)
             }
-        }
-    }
-    else {
-        ChatUISettings { hide_time_tool: false }
+        },
+        None => html!()
     };
 
+    let live_metrics = match live_metrics {
+        Some(metrics) if metrics.tokens == 0 => html!(<p class="stream-metrics standard-padding-margin-corners">{format!("Waiting for the first token ({})", metrics.config)}</p>),
+        Some(metrics) => html!(<p class="stream-metrics standard-padding-margin-corners">{format!("Generating ({}) : {}", metrics.config, metrics.summary())}</p>),
+        None => html!()
+    };
+
+    let ui_settings = ChatUISettings::for_state(&db_state);
+
     html!{
         <div class="chat-part">
             <div class="standard-padding-margin-corners first-level vertical-flex max-height-of-container">
@@ -445,12 +793,25 @@ pub fn chat_tab() -> Html {
                     <div class="horizontal-flex">
                         <button class="mainapp-button most-horizontal-space standard-padding-margin-corners" onclick={sort_callback}>{format!("Sort by : {sort_title}")}</button>
                     </div>
+                    <div class="horizontal-flex">
+                        <select class="most-horizontal-space standard-padding-margin-corners" ref={rating_filter_ref} onchange={rating_filter_callback}>
+                        {
+                            RatingFilter::options().into_iter().map(|(value, name)| {
+                                html!(<option value={value}>{name}</option>)
+                            }).collect::<Html>()
+                        }
+                        </select>
+                    </div>
+                    <div class="horizontal-flex">
+                        <button class="mainapp-button most-horizontal-space standard-padding-margin-corners" onclick={import_toggle_callback}>{if *show_import {"Hide import"} else {"Import chats..."}}</button>
+                    </div>
+                    {if *show_import {html!(<ImportPanel/>)} else {html!()}}
 
                     <hr/>
                 </div>
                 <div class="list-holder">
                     {
-                        if db_state.db.chats.get_chats().len() > 0 {
+                        if chat_refs.len() > 0 {
                             chat_htmls
                         }
                         else {
@@ -491,34 +852,113 @@ pub fn chat_tab() -> Html {
                                 }
                             }
                             </h1>
+                            <div>
+                            <button class="mainapp-button standard-padding-margin-corners" onclick={title_toggle_callback}>{if *show_title {"Hide title"} else {"Title..."}}</button>
+                            <button class="mainapp-button standard-padding-margin-corners" onclick={compact_toggle_callback}>{if *show_compact {"Hide compaction"} else {"Compact..."}}</button>
+                            <button class="mainapp-button standard-padding-margin-corners" onclick={export_toggle_callback}>{if *show_export {"Hide export"} else {"Export..."}}</button>
                             <button class="mainapp-button standard-padding-margin-corners align-right" onclick={chat_remove_callback}>{"Delete Chat"}</button>
                             </div>
+                            </div>
                         ),
                         None => html!(<h1>{"Please select a chat or start one :)"}</h1>)
                     }}
+                    {usage_meter}
+                    {live_metrics}
+                    {
+                    match chosen_chat_by_id {
+                        Some(chat) => html!(
+                            <div class="label-input-combo third-level">
+                                <input class="standard-padding-margin-corners" placeholder="Find in chat..." ref={find_ref} oninput={find_input_callback}/>
+                                <p class="standard-padding-margin-corners">
+                                {
+                                    if find_query.is_empty() {
+                                        String::new()
+                                    }
+                                    else if *find_total == 0 {
+                                        "No matches".to_string()
+                                    }
+                                    else {
+                                        format!("{} / {}", (*find_position % *find_total) + 1, *find_total)
+                                    }
+                                }
+                                </p>
+                                <button class="mainapp-button standard-padding-margin-corners" onclick={find_previous_callback}>{"Previous"}</button>
+                                <button class="mainapp-button standard-padding-margin-corners" onclick={find_next_callback}>{"Next"}</button>
+                            </div>
+                        ),
+                        None => html!()
+                    }}
+                    {
+                    match (*show_title, db_state.cursors.chosen_chat) {
+                        (true, Some(chat_id)) => html!(<TitlePanel chat_id={chat_id}/>),
+                        _ => html!()
+                    }
+                    }
+                    {
+                    match (*show_compact, db_state.cursors.chosen_chat) {
+                        (true, Some(chat_id)) => html!(<CompactPanel chat_id={chat_id}/>),
+                        _ => html!()
+                    }
+                    }
+                    {
+                    if *show_export {
+                        html!(<ExportPanel chat_id={db_state.cursors.chosen_chat}/>)
+                    }
+                    else {
+                        html!()
+                    }
+                    }
                 </div>
-                <div class="list-holder">
+                <div class="list-holder" ref={chat_list_ref} onscroll={part_scroll_callback}>
+                {
+                    if window_start > 0 {
+                        html!(<p class="stream-metrics">{format!("{window_start} earlier part(s), scroll up to show them")}</p>)
+                    }
+                    else {
+                        html!()
+                    }
+                }
+                <ContextProvider<FindInChat> context={FindInChat { query:(*find_query).clone() }}>
                 {
                     match chosen_chat_by_id {
                         Some(chat) => {
-                            chat.context.get_parts().iter().enumerate().map(|(i, context_part)| {
-                                if context_part.in_visible_position() {
-                                    html!(
-                                        <ContextPartShow context_part={context_part.clone()} context_part_index={i} chat_id={chat.get_id()} deletable={!db_state.ongoing_chats.contains(&chat.get_id())} ui_settings={ui_settings.clone()}/>
-                                        
-                                    )
-                                }
-                                else {
-                                    html!()
-                                }
+                            let ongoing = db_state.ongoing_chats.contains(&chat.get_id());
+                            let last_index = part_count.saturating_sub(1);
+                            db_state.shown_parts(chat.get_id(), window_start..window_end).into_iter().filter(|(_, context_part)| {context_part.in_visible_position() && !is_ratings_part(context_part)}).map(|(i, context_part)| {
+                                html!(
+                                    <div key={i} data-part-index={i.to_string()}>
+                                        <ContextPartShow context_part={context_part} context_part_index={i} chat_id={chat.get_id()} deletable={!ongoing} ui_settings={ui_settings.clone()} focused={db_state.cursors.focused_part == Some(i)} streaming={ongoing && i == last_index}/>
+                                    </div>
+                                )
                             }).collect()
                         },
                         None => Vec::new()
                     }
                 }
+                </ContextProvider<FindInChat>>
+                {
+                    if window_end < part_count {
+                        html!(
+                            <div class="horizontal-flex">
+                                <p class="stream-metrics">{format!("{} later part(s), scroll down to show them", part_count - window_end)}</p>
+                                <button class="mainapp-button standard-padding-margin-corners" onclick={latest_parts_callback}>{"Go to the latest parts"}</button>
+                            </div>
+                        )
+                    }
+                    else {
+                        html!()
+                    }
+                }
+                {
+                    match chosen_chat_by_id {
+                        Some(chat) => html!(<QueuedPrompts chat_id={chat.get_id()}/>),
+                        None => html!()
+                    }
+                }
                 {
                     match chosen_chat_by_id{
-                        Some(chat) => if chat.last_response_is_user() {
+                        // once a part was streamed in the saved chat's last part is not the last one shown
+                        Some(chat) => if chat.last_response_is_user() && !db_state.live_responses.contains_key(&chat.get_id()) {
                             html!(<h2>{"Waiting on the AI to respond..."}</h2>)
                         }
                         else {
@@ -528,13 +968,31 @@ pub fn chat_tab() -> Html {
                     }
                 }
                 </div>
+                {
+                    if *show_inspector {
+                        html!(<ContextInspector prompt_ref={prompt_node_ref.clone()}/>)
+                    }
+                    else {
+                        html!()
+                    }
+                }
+                {
+                    if *show_templates {
+                        html!(<TemplatePanel prompt_ref={Some(prompt_node_ref.clone())}/>)
+                    }
+                    else {
+                        html!()
+                    }
+                }
                 <div class="label-input-combo bottom-bar most-horizontal-space-no-flex third-level standard-padding-margin-corners">
-                    <textarea placeholder="Have a prompt ?" ref={prompt_node_ref} class="standard-padding-margin-corners"/>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={inspector_toggle_callback}>{if *show_inspector {"Hide context"} else {"Inspect context"}}</button>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={templates_toggle_callback}>{if *show_templates {"Hide templates"} else {"Templates..."}}</button>
+                    <textarea placeholder="Have a prompt ? (Enter to send, Shift+Enter for a new line, / for commands)" ref={prompt_node_ref} class="standard-padding-margin-corners" onchange={draft_text_callback} onkeydown={prompt_key_callback}/>
                     <select class="standard-padding-margin-corners" ref={cc_select_ref} onchange={cc_select_callback}>
                         <option value="NO CHAT CONFIG WHATSOEVER (please do not use this magic name for a real chat config)">{"None"}</option>
                         {config_htmls}
                     </select>
-                    <button class={button_style} onclick={prompt_send_callback} disabled={disabled}>{"Send"}</button>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={prompt_send_callback.reform(|_:MouseEvent| {()})}>{send_label}</button>
                     
                 </div>
 
@@ -545,6 +1003,29 @@ pub fn chat_tab() -> Html {
     }
 }
 
+/// Text of the user's previous prompts in this chat, oldest first
+fn prompt_history(chat:&Chat) -> Vec<String> {
+    chat.context.get_parts().iter().filter(|part| {part.is_user()}).map(|part| {
+        let text = part.data_to_single_text();
+        match (text.find("<user_prompt>"), text.rfind("</user_prompt>")) {
+            (Some(start), Some(end)) if start + "<user_prompt>".len() <= end => text[start + "<user_prompt>".len()..end].trim().to_string(),
+            _ => text.trim().to_string()
+        }
+    }).filter(|prompt| {!prompt.is_empty()}).collect()
+}
+
+/// Inputs give their caret in UTF-16 code units, this is the byte offset in the text it stands at
+pub fn caret_byte_offset(text:&str, caret:usize) -> usize {
+    let mut units = 0;
+    for (byte, character) in text.char_indices() {
+        if units >= caret {
+            return byte
+        }
+        units += character.len_utf16();
+    }
+    text.len()
+}
+
 fn shorten_title_to_x_chars(title:String, max_chars:usize) -> String {
     let mut out = String::with_capacity(max_chars + 3);
     let mut chars_in_out = 0;
@@ -579,26 +1060,42 @@ fn shorten_title_to_x_chars_from_end(title:String, max_chars:usize) -> String {
 
 #[derive(Clone, Properties, PartialEq)]
 pub struct ContextPartProp {
-    context_part:ContextPart,
-    chat_id:ChatID,
-    context_part_index:usize,
-    deletable:bool,
-    ui_settings:ChatUISettings
+    pub context_part:ShownPart,
+    pub chat_id:ChatID,
+    pub context_part_index:usize,
+    pub deletable:bool,
+    pub ui_settings:ChatUISettings,
+    pub focused:bool,
+    /// Whether this part is the response still being streamed
+    pub streaming:bool
 }
 
 #[function_component(ContextPartShow)]
-fn context_part(prop:&ContextPartProp) -> Html {
-
+pub fn context_part(prop:&ContextPartProp) -> Html {
     let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
     let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let part_ref = use_node_ref();
+    use_effect_with(
+        prop.focused,
+        {
+            let part_ref = part_ref.clone();
+            move |focused:&bool| {
+                if *focused && let Some(element) = part_ref.cast::<HtmlElement>() {
+                    element.scroll_into_view();
+                }
+            }
+        }
+    );
+    let focus_class = if prop.focused {" focused-part"} else {""};
     let delete_part_callback = {
         let db_state = db_state.clone();
         let proxima_state = proxima_state.clone();
         let prop = prop.clone();
         Callback::from(move |mouse_evt:MouseEvent| {
-            let mut new_chat = db_state.db.chats.get_chats().get(&prop.chat_id).unwrap().clone();
-            new_chat.context.get_parts_mut().remove(prop.context_part_index);
+            let mut new_chat = db_state.chat(prop.chat_id).unwrap().clone();
+            remove_part(&mut new_chat, prop.context_part_index);
             db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(new_chat.get_id()), DatabaseItem::Chat(new_chat.clone()))]));
+            db_state.dispatch(DatabaseAction::PrunePartData(prop.chat_id));
             let proxima_state = proxima_state.clone();
             spawn_local(async move {
                 make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Update(DatabaseItem::Chat(new_chat)) }, proxima_state.chat_url.clone()).await;
@@ -628,6 +1125,7 @@ fn context_part(prop:&ContextPartProp) -> Html {
         html!(
             <div class="chat-title-display">
                 <div>{pos_add}</div>
+                <div>{format!("{} tokens", format_estimate(cached_token_count(db_state.tokenizer_settings.kind_for(db_state.cursors.chosen_config), &prop.context_part)))}</div>
                 <div>{if let Some(date) = prop.context_part.get_date() {
 
                     let date_chars = &format!("{date}").chars().collect::<Vec<char>>()[..19];
@@ -642,6 +1140,105 @@ fn context_part(prop:&ContextPartProp) -> Html {
                 <button class={button_style} disabled={disabled} onclick={delete_part_callback}>{"Delete part"}</button>
             </div>
         );
+    let rating_add = match prop.context_part.get_position() {
+        ContextPosition::AI => html!(
+            <>
+            {
+                match db_state.stream_metrics.get(prop.chat_id, &part_key(&prop.context_part)) {
+                    Some(metrics) => html!(<p class="stream-metrics">{metrics.summary()}</p>),
+                    None => html!()
+                }
+            }
+            <RatingBar chat_id={prop.chat_id} part_index={prop.context_part_index}/>
+            </>
+        ),
+        _ => html!()
+    };
+    // everything above follows the database state, the body is only rendered again when the part itself changes
+    let tag_times = if prop.context_part.is_user() {None} else {part_tag_times(&db_state, prop)};
+    let body = html!(
+        <ContextPartBody context_part={prop.context_part.clone()} live_parsed={if prop.streaming {db_state.live_parsed(prop.chat_id, prop.context_part_index).map(Rc::new)} else {None}} streaming={prop.streaming} ui_settings={prop.ui_settings.clone()} previous_call={if prop.context_part.is_user() {None} else {outputs_call(&db_state, prop)}} tag_times={tag_times}/>
+    );
+    if prop.context_part.is_user() {
+        html!(
+            <div class={format!("standard-padding-margin-corners{focus_class}")} ref={part_ref}>
+            <>{part_title_add}</>
+            {body}
+            </div>
+        )
+    }
+    else if prop.streaming || shows_anything(&cached_parse_tags(&prop.context_part.data_to_single_text()), &prop.ui_settings) {
+        let turn_class = if let ContextPosition::Tool(_) = prop.context_part.get_position() {
+            "standard-padding-margin-corners tool-turn"
+        }
+        else {
+            "standard-padding-margin-corners ai-turn"
+        };
+        html!(
+            <div class={format!("{turn_class}{focus_class}")} ref={part_ref}>
+            <>{part_title_add}</>
+            {body}
+            <>{rating_add}</>
+            </div>
+        )
+    }
+    else {
+        html!(
+
+        )
+    }
+}
+
+/// Call answered by outputs at the start of a part, only looked for when the part has outputs
+fn outputs_call(db_state:&UseReducerHandle<DatabaseState>, prop:&ContextPartProp) -> Option<CallInfo> {
+    if prop.context_part.data_to_single_text().contains("<outputs") {
+        last_call_before(db_state, prop.chat_id, prop.context_part_index)
+    }
+    else {
+        None
+    }
+}
+
+/// When the tags of a part arrived, only looked for when the part has calls
+fn part_tag_times(db_state:&UseReducerHandle<DatabaseState>, prop:&ContextPartProp) -> Option<Rc<TagTimes>> {
+    if prop.context_part.data_to_single_text().contains("<call") {
+        db_state.part_tag_times(prop.chat_id, prop.context_part_index, &prop.context_part).map(Rc::new)
+    }
+    else {
+        None
+    }
+}
+
+/// Whether a finished AI or tool part has anything to show once parsed
+fn shows_anything(parsed:&ParsedHtml, ui_settings:&ChatUISettings) -> bool {
+    parsed.pending.trim().len() > 0 || parsed.children.iter().any(|child| {
+        match child {
+            HtmlNode::Element(element) => match element.kind {
+                TagKind::Think => element.content.trim().len() > 2,
+                TagKind::CurrentTime => !ui_settings.hide_time_tool,
+                TagKind::Call | TagKind::Outputs | TagKind::AutomaticMemory | TagKind::Response => true,
+                _ => element.content.trim().len() > 0
+            },
+            HtmlNode::Text(txt) => txt.trim().len() > 0
+        }
+    })
+}
+
+#[derive(Properties, PartialEq)]
+struct ContextPartBodyProp {
+    context_part:ShownPart,
+    /// Tags of the part while it is streamed, parsed as it arrived
+    live_parsed:Option<Rc<ParsedHtml>>,
+    streaming:bool,
+    ui_settings:ChatUISettings,
+    /// Call answered by outputs coming before any call of this part
+    previous_call:Option<CallInfo>,
+    tag_times:Option<Rc<TagTimes>>
+}
+
+#[function_component(ContextPartBody)]
+fn context_part_body(prop:&ContextPartBodyProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let mut all_text = prop.context_part.data_to_single_text();
     if prop.context_part.is_user() {
         all_text = all_text.trim().to_string();
@@ -656,66 +1253,70 @@ fn context_part(prop:&ContextPartProp) -> Html {
             }
         }
         html!(
-            <div class="standard-padding-margin-corners">
-            <>{part_title_add}</>
-            <div> {VNode::from_html_unchecked(AttrValue::from(to_html(&all_text.lines().intersperse("\n\n").collect::<Vec<&str>>().concat())))}</div>
+            <>
+            <div> {find.render_lines(&all_text)}</div>
             <>{media}</>
-            </div>
+            </>
         )
     }
     else {
-        let turn_class = if let ContextPosition::Tool(_) = prop.context_part.get_position() {
-            "standard-padding-margin-corners tool-turn"
-        }
-        else {
-            "standard-padding-margin-corners ai-turn"
+        // the streamed part was parsed as it arrived instead of from its start on every token
+        let parsed = match &prop.live_parsed {
+            Some(parsed) => parsed.clone(),
+            None => cached_parse_tags(&all_text)
         };
-        let parsed = parse_html(&all_text.chars().collect::<Vec<char>>(), 2);
+        // the parsed end may be the start of a tag still being streamed, once the response is over it is plain text
+        let streaming = prop.streaming;
         if parsed.has_elements() {
             let mut htmls = Vec::with_capacity(parsed.children.len());
-            for child in parsed.children {
-                if let HtmlNode::Element { name, content, children } = child {
-                    if name == "think" {
-                        if content.trim().len() > 2 {
+            // outputs answer the latest call, which can be in an earlier part of the chat
+            let mut last_call = None;
+            let part_date = prop.context_part.get_date().map(|date| {date.clone()});
+            // calls and outputs are timed by when their tags arrived, the part date is all there is otherwise
+            let time_at = |offset:usize| {prop.tag_times.as_ref().and_then(|times| {times.at(offset)}).or(part_date)};
+            let mut cursor = 0;
+            for child in &parsed.children {
+                if let HtmlNode::Element(element) = child {
+                    let finished = element.closed || !streaming;
+                    let (start, content_start, end) = element_span(&all_text, cursor, element).unwrap_or((cursor, cursor, cursor));
+                    cursor = end;
+                    match element.kind {
+                        TagKind::Think => if element.content.trim().len() > 2 || !finished {
                             htmls.push(
                                 html!(
-                                    <ThinkingPartShow txt={content} finished=true/>
+                                    <ThinkingPartShow txt={element.content.clone()} finished={finished}/>
                                 )
                             );
-                        }
-                    }
-                    else if name == "call" && children.children.len() >= 3 && let Some(HtmlNode::Element { name:child_name, content:child_content, children:tool_children }) = &children.get_first_element() && child_name.trim() == "tool" {
-                        let child_content = child_content.clone();
-                        htmls.push(
-                            
-                            html!(
-                                <CallPartShow txt={content.clone()} tool_name={child_content.trim().to_string()} finished=true/>
-                            )
-                        );
-                    }
-                    else if name == "outputs" {
-                        htmls.push(
-                            html!(
-                                <CallOutputPartShow txt={content}/>
+                        },
+                        TagKind::Call => {
+                            // the tool name is known as soon as its own tag is closed, the rest of the call can still be streaming
+                            let tool_name = element.children.first_of_kind(TagKind::Tool).map(|tool| {tool.text().trim().to_string()}).unwrap_or("...".to_string());
+                            last_call = Some(CallInfo::from_element(element, time_at(start)));
+                            htmls.push(
+                                html!(
+                                    <CallPartShow txt={element.content.clone()} tool_name={tool_name} finished={finished}/>
+                                )
+                            );
+                        },
+                        TagKind::Outputs => {
+                            let call = last_call.clone().or_else(|| {prop.previous_call.clone()});
+                            htmls.push(
+                                html!(
+                                    <CallOutputPartShow txt={element.content.clone()} call={call} date={time_at(end)} timing={prop.tag_times.clone().map(|times| {TextTiming { times, offset: content_start }})}/>
+                                )
                             )
-                        );
-                    }
-                    else if name == "automatic_memory" {
-                        htmls.push(
+                        },
+                        TagKind::AutomaticMemory => htmls.push(
                             html!(
-                                <MemoryPartShow txt={content}/>
+                                <MemoryPartShow txt={element.content.clone()} memory_ids={memory_ids(element)}/>
                             )
-                        );
-                    }
-                    else if name == "response" {
-                        htmls.push(
+                        ),
+                        TagKind::Response => htmls.push(
                             html!(
-                                <ResponsePartShow children={children.children}/>
+                                <ResponsePartShow children={element.children.children.clone()} finished={finished}/>
                             )
-                        );
-                    }
-                    else if name == "current_time" {
-                        if !prop.ui_settings.hide_time_tool {
+                        ),
+                        TagKind::CurrentTime => if !prop.ui_settings.hide_time_tool {
                             htmls.push(
                                 html!(
                                     <>
@@ -723,68 +1324,191 @@ fn context_part(prop:&ContextPartProp) -> Html {
                                     </>
                                 )
                             )
+                        },
+                        _ => if element.content.trim().len() > 0 {
+                            htmls.push(
+                                html!(
+                                    <div>
+                                    <>{element.name.clone()}</>
+                                    <div>{find.render_markdown(element.content.trim())}</div>
+                                    </div>
+                                )
+                            );
                         }
                     }
-                    else if content.trim().len() > 0 {
-                        htmls.push(
-                            html!(
-                                <div>
-                                <>{name}</>
-                                <div>{VNode::from_html_unchecked(AttrValue::from(to_html(content.trim())))}</div>
-                                </div>
-                            )
-                            
-                        );
-                    }
                 }
                 else if let HtmlNode::Text(txt) = child && txt.trim().len() > 0 {
                     htmls.push(
                         html!(
                             <div> 
-                                <div>{VNode::from_html_unchecked(AttrValue::from(to_html(&txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat())))}</div>
+                                <div>{find.render_lines(txt)}</div>
                             </div>
                         )
                         
                     );
                 }
             }
-            if htmls.len() > 0 {
-                html!(
-                    <div class={turn_class}>
-                    <>{part_title_add}</>
-                    <div>{htmls}</div>
-                    </div>
-                )
-            }
-            else {
-                html!()
+            if !streaming && parsed.pending.trim().len() > 0 {
+                htmls.push(html!(<div>{find.render_markdown(parsed.pending.trim())}</div>));
             }
-        }
-        else if all_text.contains("<think>") {
             html!(
-                <div class={turn_class}>
-                <>{part_title_add}</>
-                <ThinkingPartShow txt={all_text} finished=false/>
-                </div>
-            )
-        } 
-        else if all_text.trim().len() > 0 {
-
-            html!(
-                <div class={turn_class}>
-                <>{part_title_add}</>
-                <div> {VNode::from_html_unchecked(AttrValue::from(to_html(all_text.trim())))}</div>
-                </div>
+                <div>{htmls}</div>
             )
         }
         else {
             html!(
-
+                <div> {find.render_markdown(all_text.trim())}</div>
             )
         }
     }
 }
 
+/// Last call made before a part of a chat
+fn last_call_before(db_state:&UseReducerHandle<DatabaseState>, chat_id:ChatID, part_index:usize) -> Option<CallInfo> {
+    (0..part_index).rev().filter_map(|index| {db_state.part(chat_id, index)}).filter(|part| {!part.is_user()}).find_map(|part| {
+        cached_parse_tags(&part.data_to_single_text()).elements().filter(|element| {element.kind == TagKind::Call}).last().map(|call| {
+            CallInfo::from_element(call, part.get_date().map(|date| {date.clone()}))
+        })
+    })
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RatingBarProp {
+    chat_id:ChatID,
+    part_index:usize
+}
+
+#[function_component(RatingBar)]
+fn rating_bar(prop:&RatingBarProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let score_ref = use_node_ref();
+    let notes_ref = use_node_ref();
+    let show_notes = use_state_eq(|| {false});
+    let rating = db_state.chat(prop.chat_id).and_then(|chat| {rating_of(chat, prop.part_index)}).unwrap_or_default();
+
+    // ratings are saved in the chat, so they reach the other devices with it
+    let update = {
+        let db_state = db_state.clone();
+        let chat_id = prop.chat_id;
+        let part_index = prop.part_index;
+        move |new_rating:ResponseRating| {
+            let db_state = db_state.clone();
+            let proxima_state = proxima_state.clone();
+            let alerts_state = alerts_state.clone();
+            spawn_local(async move {
+                if let Err(error) = update_chat(&db_state, &proxima_state, chat_id, |chat| {set_rating(chat, part_index, new_rating)}).await {
+                    alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("The rating was not saved, {error}"))));
+                }
+            });
+        }
+    };
+    let thumb_callback = |thumb:bool| {
+        let update = update.clone();
+        let rating = rating.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let mut new_rating = rating.clone();
+            new_rating.thumb = if rating.thumb == Some(thumb) {None} else {Some(thumb)};
+            update(new_rating);
+        })
+    };
+    let score_callback = {
+        let update = update.clone();
+        let rating = rating.clone();
+        let score_ref = score_ref.clone();
+        Callback::from(move |evt:Event| {
+            let mut new_rating = rating.clone();
+            new_rating.score = score_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse().ok();
+            update(new_rating);
+        })
+    };
+    let notes_callback = {
+        let update = update.clone();
+        let rating = rating.clone();
+        let notes_ref = notes_ref.clone();
+        Callback::from(move |evt:Event| {
+            let mut new_rating = rating.clone();
+            new_rating.notes = notes_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            update(new_rating);
+        })
+    };
+    let notes_toggle_callback = {
+        let show_notes = show_notes.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            show_notes.set(!*show_notes);
+        })
+    };
+    let thumb_class = |thumb:bool| {
+        if rating.thumb == Some(thumb) {"mainapp-button chosen-rating standard-padding-margin-corners"} else {"mainapp-button standard-padding-margin-corners"}
+    };
+    let score_value = rating.score.map(|score| {score.to_string()}).unwrap_or("none".to_string());
+
+    html!(
+        <div>
+            <div class="horizontal-flex">
+                <button class={thumb_class(true)} onclick={thumb_callback(true)}>{"👍"}</button>
+                <button class={thumb_class(false)} onclick={thumb_callback(false)}>{"👎"}</button>
+                <select class="standard-padding-margin-corners" ref={score_ref} onchange={score_callback}>
+                {
+                    [("none", "No score"), ("1", "1"), ("2", "2"), ("3", "3"), ("4", "4"), ("5", "5")].into_iter().map(|(value, name)| {
+                        html!(<option value={value} selected={value == score_value}>{name}</option>)
+                    }).collect::<Html>()
+                }
+                </select>
+                <button class="mainapp-button standard-padding-margin-corners" onclick={notes_toggle_callback}>{if rating.notes.trim().is_empty() {"Add notes"} else {"Notes"}}</button>
+            </div>
+            {
+                if *show_notes {
+                    html!(<div class="label-input-combo"><textarea class="standard-padding-margin-corners" rows="3" placeholder="Notes on this response..." ref={notes_ref} onchange={notes_callback} value={rating.notes.clone()}/></div>)
+                }
+                else {
+                    html!()
+                }
+            }
+        </div>
+    )
+}
+
+#[derive(Clone, PartialEq, Default)]
+pub struct FindInChat {
+    query:String
+}
+
+impl FindInChat {
+    pub fn query(&self) -> Option<&str> {
+        if self.query.is_empty() {None} else {Some(&self.query)}
+    }
+    pub fn matches(&self, text:&str) -> bool {
+        !self.query.is_empty() && count_matches(text, &self.query) > 0
+    }
+    pub fn render_markdown(&self, text:&str) -> Html {
+        self.render_chunks(text, false)
+    }
+    /// Renders every line of the text as its own paragraph, except inside code blocks
+    pub fn render_lines(&self, text:&str) -> Html {
+        self.render_chunks(text, true)
+    }
+    fn render_chunks(&self, text:&str, split_lines:bool) -> Html {
+        split_code_blocks(text).into_iter().map(|chunk| {
+            match chunk {
+                MarkdownChunk::Markdown(markdown) => {
+                    let rendered = cached_markdown(&markdown, split_lines, || {
+                        // math is taken out first so that markdown does not eat its underscores and stars
+                        let (markdown, expressions) = extract_math(&markdown);
+                        let markdown = if split_lines {markdown.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat()} else {markdown};
+                        restore_math(&to_html(&markdown), &expressions)
+                    });
+                    VNode::from_html_unchecked(AttrValue::from(highlight_in_html(&rendered, &self.query)))
+                },
+                MarkdownChunk::Code { language, code } => html!(
+                    <CodeBlock code={code} language={language} highlighted={self.query().map(|query| {query.to_string()})}/>
+                )
+            }
+        }).collect::<Html>()
+    }
+}
+
 #[derive(Properties, PartialEq)]
 struct ThinkingPartProp {
     txt:String,
@@ -796,9 +1520,30 @@ pub struct ChatUISettings {
     hide_time_tool:bool
 }
 
+impl ChatUISettings {
+    pub fn for_state(db_state:&DatabaseState) -> Self {
+        if let Some(access_mode) = db_state.db.access_modes.get_modes().get(&db_state.cursors.chosen_access_mode) {
+            ChatUISettings {
+                hide_time_tool: if let Some(AMSetting::Bool(val)) = access_mode.am_settings.get(&"Hide time tool".to_string()) {
+                    *val
+                }
+                else {
+                    false
+                }
+            }
+        }
+        else {
+            ChatUISettings { hide_time_tool: false }
+        }
+    }
+}
+
 #[function_component(ThinkingPartShow)]
 fn thinking_part(prop:&ThinkingPartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let should_show = use_state_eq(|| {false});
+    // sections holding a find-in-chat match are kept open
+    let shown = *should_show || find.matches(&prop.txt);
     let callback = {
         let should_show = should_show.clone();
         Callback::from(move |mouse_evt:MouseEvent| {
@@ -811,7 +1556,7 @@ fn thinking_part(prop:&ThinkingPartProp) -> Html {
         })
     };
     let name = if prop.finished {
-        if *should_show {
+        if shown {
             format!("Thought process (click to hide)")
         }
         else {
@@ -819,7 +1564,7 @@ fn thinking_part(prop:&ThinkingPartProp) -> Html {
         }
     }
     else {
-        if *should_show {
+        if shown {
             format!("Thinking... (click to hide)")
         }
         else {
@@ -827,11 +1572,11 @@ fn thinking_part(prop:&ThinkingPartProp) -> Html {
         }
     };
     if prop.txt.trim().len() > 0 {
-        if *should_show {
+        if shown {
             html!(
                 <div>
                 <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
-                <div>{VNode::from_html_unchecked(AttrValue::from(to_html(prop.txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat().trim())))}</div>
+                <div>{find.render_lines(&prop.txt)}</div>
                 </div>
             )
         }
@@ -860,7 +1605,9 @@ struct CallPartProp {
 
 #[function_component(CallPartShow)]
 fn call_part(prop:&CallPartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let should_show = use_state_eq(|| {false});
+    let shown = *should_show || find.matches(&prop.txt);
     let callback = {
         let should_show = should_show.clone();
         Callback::from(move |mouse_evt:MouseEvent| {
@@ -873,25 +1620,25 @@ fn call_part(prop:&CallPartProp) -> Html {
         })
     };
     let name = if prop.finished {
-        if *should_show {
+        if shown {
             format!("Tool call : {} (click to hide)", prop.tool_name)
         }
         else {format!("Tool call : {} (click to show)", prop.tool_name)
         }
     }
     else {
-        if *should_show {
+        if shown {
             format!("Calling tool : {}... (click to hide)", prop.tool_name)
         }
         else {
             format!("Calling tool : {}... (click to show)", prop.tool_name)
         }
     };
-    if *should_show {
+    if shown {
         html!(
             <div>
             <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
-            <div>{VNode::from_html_unchecked(AttrValue::from(to_html(prop.txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat().trim())))}</div>
+            <ToolCallView tool_name={prop.tool_name.clone()} txt={prop.txt.clone()}/>
             </div>
         )
     }
@@ -907,11 +1654,17 @@ fn call_part(prop:&CallPartProp) -> Html {
 #[derive(Properties, PartialEq)]
 struct CallOutputPartProp {
     txt:String,
+    /// Call answered by these outputs
+    call:Option<CallInfo>,
+    date:Option<DateTime<Utc>>,
+    timing:Option<TextTiming>
 }
 
 #[function_component(CallOutputPartShow)]
 fn call_output_part(prop:&CallOutputPartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let should_show = use_state_eq(|| {false});
+    let shown = *should_show || find.matches(&prop.txt);
     let callback = {
         let should_show = should_show.clone();
         Callback::from(move |mouse_evt:MouseEvent| {
@@ -923,17 +1676,21 @@ fn call_output_part(prop:&CallOutputPartProp) -> Html {
             }
         })
     };
-    let name = if *should_show {
-        format!("Tool call outputs (click to hide)")
+    let tool_add = match &prop.call {
+        Some(call) => format!(" : {}", call.tool_name),
+        None => String::new()
+    };
+    let name = if shown {
+        format!("Tool call outputs{tool_add} (click to hide)")
     }
     else {
-        format!("Tool call outputs (click to show)")
+        format!("Tool call outputs{tool_add} (click to show)")
     };
-    if *should_show {
+    if shown {
         html!(
             <div>
             <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
-            <div>{VNode::from_html_unchecked(AttrValue::from(to_html(prop.txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat().trim())))}</div>
+            <ToolOutputView call={prop.call.clone()} date={prop.date} txt={prop.txt.clone()} timing={prop.timing.clone()}/>
             </div>
         )
     }
@@ -949,22 +1706,24 @@ fn call_output_part(prop:&CallOutputPartProp) -> Html {
 #[derive(Properties, PartialEq)]
 struct ResponsePartProp {
     children:Vec<HtmlNode>,
+    finished:bool
 }
 #[function_component(ResponsePartShow)]
 fn response_part(prop:&ResponsePartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let mut final_htmls = Vec::with_capacity(prop.children.len());
     for child in &prop.children {
-        if let HtmlNode::Element { name, content, children } = child && name == "think" {
+        if let HtmlNode::Element(element) = child && element.kind == TagKind::Think {
             final_htmls.push(
                 html!(
-                    <ThinkingPartShow txt={content.clone()} finished=true/>
+                    <ThinkingPartShow txt={element.content.clone()} finished={element.closed || prop.finished}/>
                 )
             );
         }
         else if let HtmlNode::Text(txt) = child {
             final_htmls.push(html!(
                 <div> 
-                    <div>{VNode::from_html_unchecked(AttrValue::from(to_html(&txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat())))}</div>
+                    <div>{find.render_lines(&txt)}</div>
                 </div>
             ));
         }
@@ -977,10 +1736,15 @@ fn response_part(prop:&ResponsePartProp) -> Html {
 #[derive(Properties, PartialEq)]
 struct MemoryPartProp {
     txt:String,
+    /// Memory items the block was made from
+    memory_ids:Vec<u64>
 }
 #[function_component(MemoryPartShow)]
 fn memory_part(prop:&MemoryPartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
     let should_show = use_state_eq(|| {false});
+    let shown = *should_show || find.matches(&prop.txt);
     let callback = {
         let should_show = should_show.clone();
         Callback::from(move |mouse_evt:MouseEvent| {
@@ -992,17 +1756,29 @@ fn memory_part(prop:&MemoryPartProp) -> Html {
             }
         })
     };
-    let name = if *should_show {
+    let name = if shown {
         format!("Automatic memory (click to hide)")
     }
     else {
         format!("Automatic memory (click to show)")
     };
-    if *should_show {
+    let jump_htmls = prop.memory_ids.iter().filter(|id| {db_state.db.memories.get_memories().contains_key(*id)}).map(|id| {
+        let db_state = db_state.clone();
+        let number = *id;
+        let jump_callback = Callback::from(move |mouse_evt:MouseEvent| {
+            db_state.dispatch(DatabaseAction::SetFocusedMemory(Some(number)));
+            db_state.dispatch(DatabaseAction::SetTab(8));
+        });
+        html!(
+            <button class="mainapp-button standard-padding-margin-corners" onclick={jump_callback}>{format!("Go to memory #{number}")}</button>
+        )
+    }).collect::<Html>();
+    if shown {
         html!(
             <div>
             <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
-            <div>{VNode::from_html_unchecked(AttrValue::from(to_html(prop.txt.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat().trim())))}</div>
+            {jump_htmls}
+            <div>{find.render_lines(&prop.txt)}</div>
             </div>
         )
     }
@@ -1010,6 +1786,7 @@ fn memory_part(prop:&MemoryPartProp) -> Html {
         html!(
             <div>
             <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
+            {jump_htmls}
             </div>
         )
     }
@@ -1022,6 +1799,7 @@ struct MediaPartProp {
 
 #[function_component(MediaPartShow)]
 fn media_part(prop:&MediaPartProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
     let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
     let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
     let media_data = use_state_eq(|| {Base64EncodedString::new(vec![])});
@@ -1059,7 +1837,7 @@ fn media_part(prop:&MediaPartProp) -> Html {
                     html!(
                         <div>
                             <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{name}</button>
-                            <>{VNode::from_html_unchecked(AttrValue::from(to_html(string.trim().lines().intersperse("\n\n").collect::<Vec<&str>>().concat().trim())))}</>
+                            <>{find.render_lines(&string)}</>
                         </div>
                     )
                 }
@@ -1092,4 +1870,18 @@ fn media_part(prop:&MediaPartProp) -> Html {
             </>
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caret_counts_utf16_units() {
+        assert_eq!(caret_byte_offset("abc", 2), 2);
+        // é is 2 bytes and 1 unit, 😀 is 4 bytes and 2 units
+        assert_eq!(caret_byte_offset("é😀x", 1), 2);
+        assert_eq!(caret_byte_offset("é😀x", 3), 6);
+        assert_eq!(caret_byte_offset("é😀x", 10), 7);
+    }
+}
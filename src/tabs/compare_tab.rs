@@ -0,0 +1,191 @@
+use proxima_backend::database::chats::ChatID;
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Html, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref};
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState};
+use crate::compare::{CompareSession, Promotion, send_to_columns};
+use crate::context_parts::part_key;
+use crate::ratings::is_ratings_part;
+use crate::tabs::chat_tab::{ChatUISettings, ContextPartShow};
+
+fn chat_name(db_state:&DatabaseState, chat_id:ChatID) -> String {
+    db_state.chat(chat_id).and_then(|chat| {chat.chat_title.clone()}).unwrap_or(format!("Chat {chat_id}"))
+}
+
+#[function_component(CompareTab)]
+pub fn compare_tab() -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let prompt_ref = use_node_ref();
+    let session = (*db_state.compare).clone();
+    let busy = session.is_busy(&db_state);
+
+    let config_htmls = db_state.db.configs.get_configs().iter().map(|(id, config)| {
+        let db_state = db_state.clone();
+        let chosen = session.has_config(*id);
+        let id = *id;
+        let toggle_callback = Callback::from(move |mouse_evt:MouseEvent| {
+            let mut session = (*db_state.compare).clone();
+            session.toggle_config(id);
+            db_state.dispatch(DatabaseAction::SetCompare(session));
+        });
+        html!(
+            <button class="mainapp-button standard-padding-margin-corners" onclick={toggle_callback}>{if chosen {format!("✓ {}", config.name)} else {config.name.clone()}}</button>
+        )
+    }).collect::<Html>();
+
+    let main_chat_callback = {
+        let db_state = db_state.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let mut session = (*db_state.compare).clone();
+            session.main_chat = if session.main_chat.is_some() {None} else {db_state.cursors.chosen_chat};
+            db_state.dispatch(DatabaseAction::SetCompare(session));
+        })
+    };
+    let new_comparison_callback = {
+        let db_state = db_state.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            // the same configurations are compared again in new chats
+            let mut session = CompareSession { main_chat: db_state.compare.main_chat, ..CompareSession::default() };
+            for column in &db_state.compare.columns {
+                session.toggle_config(column.config);
+            }
+            db_state.dispatch(DatabaseAction::SetCompare(session));
+        })
+    };
+    let send_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let prompt_ref = prompt_ref.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let prompt = prompt_ref.cast::<web_sys::HtmlInputElement>().unwrap();
+            let prompt_text = prompt.value();
+            if prompt_text.trim().is_empty() {
+                return;
+            }
+            if db_state.compare.columns.is_empty() {
+                alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Choose the configurations to compare first"))));
+                return;
+            }
+            prompt.set_value("");
+            let session = (*db_state.compare).clone();
+            let db_state = db_state.clone();
+            let proxima_state = proxima_state.clone();
+            let alerts_state = alerts_state.clone();
+            spawn_local(async move {
+                send_to_columns(&db_state, &proxima_state, &alerts_state, session, prompt_text).await;
+            });
+        })
+    };
+
+    let column_htmls = session.columns.iter().map(|column| {
+        html!(
+            <CompareColumnShow config={column.config} chat={column.chat} main_chat={session.main_chat} busy={busy}/>
+        )
+    }).collect::<Html>();
+
+    html!(
+        <div class="chat-part">
+            <div class="vertical-flex standard-padding-margin-corners first-level most-horizontal-space-no-flex max-height-of-container">
+                <div>
+                    <h1>{"Compare configurations"}</h1>
+                    <div class="horizontal-flex memory-ids">
+                        <p>{"Compared : "}</p>
+                        {config_htmls}
+                    </div>
+                    <div class="horizontal-flex memory-ids">
+                        <p>{match session.main_chat {
+                            Some(chat_id) => format!("Promoted answers are added to {}", chat_name(&db_state, chat_id)),
+                            None => "Promoted answers are opened in their own chat".to_string()
+                        }}</p>
+                        <button class="mainapp-button standard-padding-margin-corners" onclick={main_chat_callback}>{if session.main_chat.is_some() {"Open them in their own chat"} else {"Add them to the chosen chat"}}</button>
+                        <button class="mainapp-button standard-padding-margin-corners" onclick={new_comparison_callback}>{"New comparison"}</button>
+                    </div>
+                    <hr/>
+                </div>
+                <div class="horizontal-flex compare-columns list-holder">
+                    {column_htmls}
+                </div>
+                <div class="label-input-combo bottom-bar most-horizontal-space-no-flex third-level standard-padding-margin-corners">
+                    <textarea placeholder="Prompt sent to every compared configuration" ref={prompt_ref} class="standard-padding-margin-corners"/>
+                    <button class={if busy {"mainapp-unused-button standard-padding-margin-corners"} else {"mainapp-button standard-padding-margin-corners"}} disabled={busy} onclick={send_callback}>{format!("Send to {} configuration(s)", session.columns.len())}</button>
+                </div>
+            </div>
+        </div>
+    )
+}
+
+#[derive(Properties, PartialEq)]
+struct CompareColumnProp {
+    config:usize,
+    chat:Option<ChatID>,
+    main_chat:Option<ChatID>,
+    busy:bool
+}
+
+#[function_component(CompareColumnShow)]
+fn compare_column(prop:&CompareColumnProp) -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let config_name = db_state.db.configs.get_configs().get(&prop.config).map(|config| {config.name.clone()}).unwrap_or(format!("Configuration {}", prop.config));
+
+    let Some(chat) = prop.chat.and_then(|chat_id| {db_state.chat(chat_id)}) else {
+        return html!(
+            <div class="vertical-flex standard-padding-margin-corners third-level compare-column">
+                <h3>{config_name}</h3>
+                <p>{"Nothing sent yet"}</p>
+            </div>
+        )
+    };
+    let chat_id = chat.get_id();
+    let parts = db_state.shown_parts(chat_id, 0..db_state.part_count(chat_id).unwrap_or(0));
+    let ongoing = db_state.ongoing_chats.contains(&chat_id);
+    // only the answer to the latest prompt is compared
+    let answer_start = parts.iter().rposition(|(_, part)| {part.is_user()}).map(|index| {index + 1}).unwrap_or(0);
+    let ui_settings = ChatUISettings::for_state(&db_state);
+    let answer_htmls = parts.iter().skip(answer_start).filter(|(_, part)| {part.in_visible_position() && !is_ratings_part(part)}).map(|(i, part)| {
+        let i = *i;
+        html!(
+            <ContextPartShow context_part={part.clone()} context_part_index={i} chat_id={chat_id} deletable={false} ui_settings={ui_settings.clone()} focused={false} streaming={ongoing && i + 1 == parts.len()}/>
+        )
+    }).collect::<Html>();
+    let metrics = if ongoing {
+        db_state.live_metrics(chat_id).map(|metrics| {metrics.summary()})
+    }
+    else {
+        parts.last().and_then(|(_, last)| {db_state.stream_metrics.get(chat_id, &part_key(last))}).map(|metrics| {metrics.summary()})
+    };
+
+    let promote_callback = {
+        let db_state = db_state.clone();
+        let main_chat = prop.main_chat;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            db_state.dispatch(DatabaseAction::PromoteAnswer(Promotion { main_chat, column_chat: chat_id }));
+        })
+    };
+
+    html!(
+        <div class="vertical-flex standard-padding-margin-corners third-level compare-column">
+            <div class="chat-title-display">
+                <h3>{config_name}</h3>
+                <button class={if prop.busy {"mainapp-unused-button standard-padding-margin-corners"} else {"mainapp-button standard-padding-margin-corners"}} disabled={prop.busy} onclick={promote_callback}>{"Promote"}</button>
+            </div>
+            {
+                match metrics {
+                    Some(summary) => html!(<p class="stream-metrics">{summary}</p>),
+                    None => html!()
+                }
+            }
+            {
+                if ongoing && answer_start == parts.len() {
+                    html!(<p>{"Waiting on the AI to respond..."}</p>)
+                }
+                else {
+                    answer_htmls
+                }
+            }
+        </div>
+    )
+}
@@ -10,6 +10,9 @@ use yew::ContextProvider;
 
 use crate::app::{DatabaseAction, DatabaseState, ProximaState, make_ai_request, make_db_request};
 use crate::db_sync::get_delta_for_add;
+use crate::titles::auto_title_if_needed;
+use crate::stream_metrics::NO_CONFIG;
+use crate::templates::TemplatePanel;
 
 
 #[function_component(HomeTab)]
@@ -52,10 +55,12 @@ pub fn home_tab() -> Html {
                 }
 
                 db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+                db_state.dispatch(DatabaseAction::AddToOngoingChats { chat: local_id, config: NO_CONFIG.to_string(), tokenizer: db_state.tokenizer_settings.kind_for(None) });
 
                 let json_request = proxima_backend::web_payloads::AIPayload::new(proxima_state.auth_token.clone(), EndpointRequestVariant::RespondToFullPrompt { whole_context: starting_context, streaming: true, session_type: SessionType::Chat, chat_settings:None, chat_id:Some(local_id), access_mode:db_state.cursors.chosen_access_mode });
                 
                 let value = make_ai_request(json_request, proxima_state.chat_url.clone(), local_id).await;
+                db_state.dispatch(DatabaseAction::RemoveFromOngoingChats { chat: local_id });
 
                 match value {
                     Ok(response) => {
@@ -73,7 +78,7 @@ pub fn home_tab() -> Html {
                     },
                     Err(_) => ()
                 }
-
+                auto_title_if_needed(&db_state, &proxima_state, local_id).await;
 
             });
         })
@@ -113,6 +118,11 @@ pub fn home_tab() -> Html {
                 <button class="mainapp-button standard-padding-margin-corners" onclick={prompt_send_callback}>{"Send"}</button>
             </div>
         </div>
+        <hr/>
+        <div class="multi-input-container standard-padding-margin-corners second-level">
+            <h2>{"Start from a template"}</h2>
+            <TemplatePanel/>
+        </div>
         
         
     </div>  
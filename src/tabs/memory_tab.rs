@@ -0,0 +1,256 @@
+use std::collections::BTreeSet;
+
+use proxima_backend::database::memories::Memory;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlElement;
+use yew::virtual_dom::VNode;
+use yew::{AttrValue, Callback, Event, Html, InputEvent, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_effect_with, use_node_ref, use_state_eq};
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState};
+use crate::memories::{delete_memory, sorted_memories, update_memory, visible_in};
+use crate::search::fold_char;
+use crate::find_matches::highlight_in_html;
+
+fn folded(text:&str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+#[function_component(MemoryTab)]
+pub fn memory_tab() -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let query_ref = use_node_ref();
+    let query = use_state_eq(String::new);
+    let all_access_modes = use_state_eq(|| {false});
+
+    let query_callback = {
+        let query_ref = query_ref.clone();
+        let query = query.clone();
+        Callback::from(move |input_evt:InputEvent| {
+            query.set(query_ref.cast::<web_sys::HtmlInputElement>().unwrap().value());
+        })
+    };
+    let scope_callback = {
+        let all_access_modes = all_access_modes.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            all_access_modes.set(!*all_access_modes);
+        })
+    };
+
+    let folded_query = folded(query.trim());
+    let memories = sorted_memories(&db_state.db);
+    let shown = memories.iter().filter(|(_, memory)| {
+        (*all_access_modes || visible_in(memory, db_state.cursors.chosen_access_mode))
+        && (folded_query.is_empty() || folded(&memory.content).contains(&folded_query) || memory.tags.iter().any(|tag| {
+            db_state.db.tags.get_tags().get(tag).is_some_and(|tag| {folded(tag.get_name()).contains(&folded_query)})
+        }))
+    }).collect::<Vec<&(u64, &Memory)>>();
+    let memory_htmls = shown.iter().rev().map(|(id, memory)| {
+        html!(
+            <MemoryShow id={*id} memory={(*memory).clone()} query={query.trim().to_string()}/>
+        )
+    }).collect::<Html>();
+
+    html!(
+        <div class="chat-part">
+            <div class="vertical-flex standard-padding-margin-corners first-level most-horizontal-space-no-flex max-height-of-container">
+                <div>
+                    <h1>{"Memory"}</h1>
+                    <div class="label-input-combo">
+                        <input class="standard-padding-margin-corners" placeholder="Search memories by text or tag..." ref={query_ref} oninput={query_callback}/>
+                        <button class="mainapp-button standard-padding-margin-corners" onclick={scope_callback}>{if *all_access_modes {"Showing every access mode"} else {"Showing the current access mode"}}</button>
+                    </div>
+                    <p>{format!("{} of {} memory item(s)", shown.len(), memories.len())}</p>
+                    <hr/>
+                </div>
+                <div class="list-holder">
+                    {memory_htmls}
+                </div>
+            </div>
+        </div>
+    )
+}
+
+/// Saves a change of a memory, telling when the server did not take it
+fn save_change(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, id:u64, change:impl FnOnce(&mut Memory) + 'static) {
+    let db_state = db_state.clone();
+    let proxima_state = proxima_state.clone();
+    let alerts_state = alerts_state.clone();
+    spawn_local(async move {
+        if let Err(error) = update_memory(&db_state, &proxima_state, id, change).await {
+            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::App, AlertCategory::Database, format!("Could not save the memory : {error}"))));
+        }
+    });
+}
+
+#[derive(Properties, PartialEq)]
+struct MemoryShowProp {
+    id:u64,
+    memory:Memory,
+    query:String
+}
+
+#[function_component(MemoryShow)]
+fn memory_show(prop:&MemoryShowProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let editing = use_state_eq(|| {false});
+    let content_ref = use_node_ref();
+    let tag_ref = use_node_ref();
+    let access_mode_ref = use_node_ref();
+    let memory_ref = use_node_ref();
+    let focused = db_state.cursors.focused_memory == Some(prop.id);
+
+    use_effect_with(
+        focused,
+        {
+            let memory_ref = memory_ref.clone();
+            move |focused:&bool| {
+                if *focused && let Some(element) = memory_ref.cast::<HtmlElement>() {
+                    element.scroll_into_view();
+                }
+            }
+        }
+    );
+
+    let edit_callback = {
+        let editing = editing.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            editing.set(!*editing);
+        })
+    };
+    let save_content_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let editing = editing.clone();
+        let content_ref = content_ref.clone();
+        let id = prop.id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let content = content_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+            save_change(&db_state, &proxima_state, &alerts_state, id, move |memory| {memory.content = content;});
+            editing.set(false);
+        })
+    };
+    let remove_tag_callback = |tag:usize| {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let id = prop.id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            save_change(&db_state, &proxima_state, &alerts_state, id, move |memory| {memory.tags.remove(&tag);});
+        })
+    };
+    let remove_access_mode_callback = |access_mode:usize| {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let id = prop.id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            save_change(&db_state, &proxima_state, &alerts_state, id, move |memory| {memory.access_modes.remove(&access_mode);});
+        })
+    };
+    let add_tag_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let tag_ref = tag_ref.clone();
+        let id = prop.id;
+        Callback::from(move |evt:Event| {
+            if let Ok(tag) = tag_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse::<usize>() {
+                save_change(&db_state, &proxima_state, &alerts_state, id, move |memory| {memory.tags.insert(tag);});
+            }
+        })
+    };
+    let add_access_mode_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let access_mode_ref = access_mode_ref.clone();
+        let id = prop.id;
+        Callback::from(move |evt:Event| {
+            if let Ok(access_mode) = access_mode_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse::<usize>() {
+                save_change(&db_state, &proxima_state, &alerts_state, id, move |memory| {memory.access_modes.insert(access_mode);});
+            }
+        })
+    };
+    let delete_callback = {
+        let db_state = db_state.clone();
+        let proxima_state = proxima_state.clone();
+        let alerts_state = alerts_state.clone();
+        let id = prop.id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let db_state = db_state.clone();
+            let proxima_state = proxima_state.clone();
+            let alerts_state = alerts_state.clone();
+            spawn_local(async move {
+                if let Err(error) = delete_memory(&db_state, &proxima_state, id).await {
+                    alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::App, AlertCategory::Database, format!("Could not delete the memory : {error}"))));
+                }
+            });
+        })
+    };
+
+    let content = prop.memory.content.clone();
+    let content_html = if *editing {
+        html!(
+            <div class="label-input-combo">
+                <textarea class="standard-padding-margin-corners most-horizontal-space-no-flex" rows="4" ref={content_ref} value={content.clone()}/>
+                <button class="mainapp-button standard-padding-margin-corners" onclick={save_content_callback}>{"Save"}</button>
+            </div>
+        )
+    }
+    else {
+        VNode::from_html_unchecked(AttrValue::from(highlight_in_html(&markdown::to_html(&content), &prop.query)))
+    };
+
+    let id_list = |ids:BTreeSet<usize>, name_of:&dyn Fn(usize) -> Option<String>, remove:&dyn Fn(usize) -> Callback<MouseEvent>| {
+        ids.into_iter().map(|id| {
+            html!(
+                <button class="mainapp-button standard-padding-margin-corners" onclick={remove(id)}>{format!("{} ✕", name_of(id).unwrap_or(format!("#{id}")))}</button>
+            )
+        }).collect::<Html>()
+    };
+    let tag_name = |id:usize| {db_state.db.tags.get_tags().get(&id).map(|tag| {tag.get_name().clone()})};
+    let access_mode_name = |id:usize| {db_state.db.access_modes.get_modes().get(&id).map(|access_mode| {access_mode.get_name().clone()})};
+    let tags = prop.memory.tags.iter().cloned().collect::<BTreeSet<usize>>();
+    let access_modes = prop.memory.access_modes.iter().cloned().collect::<BTreeSet<usize>>();
+
+    html!(
+        <div class={if focused {"standard-padding-margin-corners third-level focused-part"} else {"standard-padding-margin-corners third-level"}} ref={memory_ref}>
+            <div class="chat-title-display">
+                <p>{format!("Memory #{}", prop.id)}</p>
+                <div>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={edit_callback}>{if *editing {"Cancel"} else {"Edit"}}</button>
+                    <button class="mainapp-button standard-padding-margin-corners" onclick={delete_callback}>{"Delete"}</button>
+                </div>
+            </div>
+            {content_html}
+            <div class="horizontal-flex memory-ids">
+                <p>{"Tags : "}</p>
+                {id_list(tags, &tag_name, &remove_tag_callback)}
+                <select class="standard-padding-margin-corners" ref={tag_ref} onchange={add_tag_callback}>
+                    <option value="none" selected=true>{"Add a tag..."}</option>
+                    {
+                        db_state.db.tags.get_tags().iter().map(|(id, tag)| {
+                            html!(<option value={id.to_string()}>{tag.get_name().clone()}</option>)
+                        }).collect::<Html>()
+                    }
+                </select>
+            </div>
+            <div class="horizontal-flex memory-ids">
+                <p>{"Access modes : "}</p>
+                {id_list(access_modes, &access_mode_name, &remove_access_mode_callback)}
+                <select class="standard-padding-margin-corners" ref={access_mode_ref} onchange={add_access_mode_callback}>
+                    <option value="none" selected=true>{"Add an access mode..."}</option>
+                    {
+                        db_state.db.access_modes.get_modes().iter().map(|(id, access_mode)| {
+                            html!(<option value={id.to_string()}>{access_mode.get_name().clone()}</option>)
+                        }).collect::<Html>()
+                    }
+                </select>
+            </div>
+        </div>
+    )
+}
@@ -3,4 +3,7 @@ pub mod home_tab;
 pub mod chat_tab;
 pub mod tags_tab;
 pub mod access_modes_tab;
-pub mod chat_configs_tab;
\ No newline at end of file
+pub mod chat_configs_tab;
+pub mod search_tab;
+pub mod memory_tab;
+pub mod compare_tab;
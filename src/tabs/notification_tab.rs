@@ -51,6 +51,12 @@ pub fn single_notification() -> Html {
 
     let specific = match my_notification.related_item.clone() {
         Some(item_id) => match item_id {
+            // templates are stored as chats, they are changed in the template panel and not opened
+            DatabaseItemID::Chat(chat_id) if db_state.chats.template(chat_id).is_some() => html!(
+                <div class="label-input-combo most-horizontal-space third-level chat-title-display align-right">
+                    <p class="standard-padding-margin-corners">{format!("Template \"{}\" updated", db_state.chats.template(chat_id).and_then(|template| {template.chat_title.clone()}).unwrap_or(chat_id.to_string()))}</p>
+                </div>
+            ),
             DatabaseItemID::Chat(chat_id) => {
                 let goto_callback = {
                     let db_state = db_state.clone();
@@ -61,7 +67,7 @@ pub fn single_notification() -> Html {
                         db_state.dispatch(DatabaseAction::SetChat(Some(chat_id)));
                     })
                 };
-                let chat_title = match db_state.db.chats.get_chats().get(&chat_id) {
+                let chat_title = match db_state.chat(chat_id) {
                     Some(chat) => match chat.chat_title.clone() {
                         Some(title) => format!("\"{title}\""),
                         None => format!("{}", chat_id),
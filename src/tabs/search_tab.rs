@@ -0,0 +1,116 @@
+use proxima_backend::database::chats::ChatID;
+use yew::{Callback, Html, InputEvent, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::app::{DatabaseAction, DatabaseState};
+use crate::search::{SearchResult, SearchTarget, SnippetSegment};
+
+const MAX_SEARCH_RESULTS:usize = 100;
+
+#[function_component(SearchTab)]
+pub fn search_tab() -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let query_ref = use_node_ref();
+    let query = use_state_eq(String::new);
+
+    let query_callback = {
+        let query_ref = query_ref.clone();
+        let query = query.clone();
+        Callback::from(move |input_evt:InputEvent| {
+            query.set(query_ref.cast::<web_sys::HtmlInputElement>().unwrap().value());
+        })
+    };
+
+    let results = db_state.search_index.search(&query, db_state.cursors.chosen_access_mode, MAX_SEARCH_RESULTS);
+    let result_htmls = results.iter().map(|result| {
+        html!(
+            <SearchResultShow result={result.clone()}/>
+        )
+    }).collect::<Html>();
+
+    html!(
+        <div class="chat-part">
+            <div class="vertical-flex standard-padding-margin-corners first-level most-horizontal-space-no-flex max-height-of-container">
+                <div>
+                    <h1>{"Search"}</h1>
+                    <div class="label-input-combo">
+                        <input class="standard-padding-margin-corners" placeholder="Search chats, titles, text files and notifications..." ref={query_ref} oninput={query_callback}/>
+                    </div>
+                    <p>
+                    {
+                        if query.trim().is_empty() {
+                            String::new()
+                        }
+                        else {
+                            format!("{} result(s)", results.len())
+                        }
+                    }
+                    </p>
+                    <hr/>
+                </div>
+                <div class="list-holder">
+                    {result_htmls}
+                </div>
+            </div>
+        </div>
+    )
+}
+
+#[derive(Properties, PartialEq)]
+struct SearchResultProp {
+    result:SearchResult
+}
+
+#[function_component(SearchResultShow)]
+fn search_result(prop:&SearchResultProp) -> Html {
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+
+    let chat_name = |chat_id:ChatID| {
+        match db_state.chat(chat_id).and_then(|chat| {chat.chat_title.clone()}) {
+            Some(title) => format!("\"{title}\""),
+            None => format!("Chat {chat_id}")
+        }
+    };
+    let (kind, location) = match &prop.result.target {
+        SearchTarget::ChatTitle(chat_id) => (format!("Title of {}", chat_name(*chat_id)), Some((*chat_id, None))),
+        SearchTarget::ChatPart { chat_id, part_index } => (format!("Message in {}", chat_name(*chat_id)), Some((*chat_id, Some(*part_index)))),
+        SearchTarget::MediaText(hash) => {
+            let file_name = db_state.db.media.get_media(hash).map(|media| {media.file_name.clone()}).unwrap_or(hash.clone());
+            (format!("File {file_name}"), prop.result.location.map(|(chat_id, part_index)| {(chat_id, Some(part_index))}))
+        },
+        SearchTarget::Notification(_) => (format!("Notification"), None)
+    };
+
+    let goto_callback = {
+        let db_state = db_state.clone();
+        let target = prop.result.target.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            match location {
+                Some((chat_id, part_index)) => {
+                    db_state.dispatch(DatabaseAction::SetTab(1));
+                    db_state.dispatch(DatabaseAction::SetChat(Some(chat_id)));
+                    db_state.dispatch(DatabaseAction::SetFocusedPart(part_index));
+                },
+                None => if let SearchTarget::Notification(_) = target {
+                    db_state.dispatch(DatabaseAction::SetTab(6));
+                }
+            }
+        })
+    };
+
+    let snippet = prop.result.snippet.iter().map(|segment| {
+        match segment {
+            SnippetSegment::Plain(text) => html!(<span>{text.clone()}</span>),
+            SnippetSegment::Match(text) => html!(<mark>{text.clone()}</mark>)
+        }
+    }).collect::<Html>();
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="chat-title-display">
+                <h3>{kind}</h3>
+                <button class="mainapp-button standard-padding-margin-corners align-right" onclick={goto_callback}>{"Go to"}</button>
+            </div>
+            <p>{snippet}</p>
+        </div>
+    )
+}
@@ -0,0 +1,639 @@
+use std::collections::{BTreeSet, HashMap};
+
+use proxima_backend::database::chats::{Chat, ChatID};
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_backend::database::{DatabaseItem, DatabaseItemID, DatabaseRequestVariant};
+use proxima_backend::web_payloads::DBPayload;
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Event, Html, InputEvent, MouseEvent, NodeRef, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::alerts::{AlertCategory, AlertData, AlertTab, Alerts, AlertsAction};
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, make_db_request, update_chat};
+use crate::chat_store::ChatStore;
+use crate::db_sync::get_delta_for_add;
+use crate::prompt_queue::{PromptSettings, request_response, starting_context_with_config};
+use crate::tabs::chat_tab::caret_byte_offset;
+
+/// First line of the only part of a chat holding a template, the backend has no item for templates so they
+/// are stored and synced as chats
+const TEMPLATE_MARKER:&str = "<proxima_prompt_template/>";
+
+pub fn is_template(chat:&Chat) -> bool {
+    template_body(chat).is_some()
+}
+
+/// Text that starts like a template without being one, such as imported messages, keeps its chat a chat
+pub fn without_template_marker(text:&str) -> &str {
+    text.strip_prefix(TEMPLATE_MARKER).map(|rest| {rest.strip_prefix('\n').unwrap_or(rest)}).unwrap_or(text)
+}
+
+fn template_body(chat:&Chat) -> Option<&str> {
+    match &chat.context.get_parts()[..] {
+        [part] if matches!(part.get_position(), ContextPosition::System) => match part.get_data().first() {
+            Some(ContextData::Text(text)) => text.strip_prefix(TEMPLATE_MARKER).map(|body| {body.strip_prefix('\n').unwrap_or(body)}),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// A reusable prompt with `{{variable}}` placeholders, with the configuration and tags of the chats started from it
+#[derive(Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub chat_id:ChatID,
+    pub name:String,
+    pub body:String,
+    pub config:Option<usize>,
+    pub tags:BTreeSet<usize>,
+    pub access_modes:BTreeSet<usize>
+}
+
+impl PromptTemplate {
+    pub fn from_chat(chat:&Chat) -> Option<Self> {
+        let body = template_body(chat)?.to_string();
+        Some(Self {
+            chat_id: chat.get_id(),
+            name: chat.chat_title.clone().unwrap_or(format!("Template {}", chat.get_id())),
+            body,
+            config: chat.config,
+            tags: chat.tags.iter().copied().collect(),
+            access_modes: chat.access_modes.iter().copied().collect()
+        })
+    }
+    pub fn variables(&self) -> Vec<String> {
+        template_variables(&self.body)
+    }
+}
+
+/// Templates usable in an access mode, by name
+pub fn templates_for(chats:&ChatStore, access_mode:usize) -> Vec<PromptTemplate> {
+    let mut templates = chats.templates().filter(|chat| {chat.access_modes.contains(&access_mode)}).filter_map(PromptTemplate::from_chat).collect::<Vec<PromptTemplate>>();
+    templates.sort_by(|template1, template2| {template1.name.to_lowercase().cmp(&template2.name.to_lowercase()).then(template1.chat_id.cmp(&template2.chat_id))});
+    templates
+}
+
+/// Placeholders found between `{{` and `}}`, and where they start and end in the text, braces written `\{{` are
+/// not one
+fn placeholders(body:&str) -> Vec<(usize, usize, &str)> {
+    let mut found = Vec::new();
+    let mut searched = 0;
+    while let Some(start) = body[searched..].find("{{").map(|start| {searched + start}) {
+        if body[..start].ends_with('\\') {
+            searched = start + 2;
+            continue;
+        }
+        let Some(end) = body[start + 2..].find("}}").map(|end| {start + 2 + end}) else {
+            break
+        };
+        let name = body[start + 2..end].trim();
+        // braces inside mean the first ones were not a placeholder, like code holding `{{{`
+        if name.is_empty() || name.contains('{') {
+            searched = start + 1;
+            continue;
+        }
+        found.push((start, end + 2, name));
+        searched = end + 2;
+    }
+    found
+}
+
+/// Names of the variables of a template, in the order they first appear
+pub fn template_variables(body:&str) -> Vec<String> {
+    let mut variables:Vec<String> = Vec::new();
+    for (_, _, name) in placeholders(body) {
+        if !variables.iter().any(|variable| {variable == name}) {
+            variables.push(name.to_string());
+        }
+    }
+    variables
+}
+
+/// Replaces every placeholder with its value, those without one are left as they are and escaped braces lose
+/// their backslash
+pub fn fill_template(body:&str, values:&HashMap<String, String>) -> String {
+    let mut filled = String::with_capacity(body.len());
+    let mut copied = 0;
+    for (start, end, name) in placeholders(body) {
+        if let Some(value) = values.get(name) {
+            filled += &unescape(&body[copied..start]);
+            filled += value;
+            copied = end;
+        }
+    }
+    filled += &unescape(&body[copied..]);
+    filled
+}
+
+fn unescape(text:&str) -> String {
+    text.replace("\\{{", "{{")
+}
+
+pub fn template_context(body:&str) -> WholeContext {
+    WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(format!("{TEMPLATE_MARKER}\n{body}"))], ContextPosition::System)])
+}
+
+/// Saves a new template when it has no chat yet, returns its chat
+pub async fn save_template(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, template:Option<ChatID>, name:String, body:String, config:Option<usize>, tags:BTreeSet<usize>, access_modes:BTreeSet<usize>) -> Result<ChatID, String> {
+    match template {
+        Some(chat_id) => {
+            let mut chat = db_state.chats.template(chat_id).ok_or("the template does not exist anymore".to_string())?.clone();
+            chat.chat_title = Some(name);
+            chat.context = template_context(&body);
+            chat.config = config;
+            chat.tags = tags.into_iter().collect();
+            chat.access_modes = access_modes.into_iter().collect();
+            let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Update(DatabaseItem::Chat(chat.clone())) };
+            make_db_request(json_request, proxima_state.chat_url.clone()).await.map_err(|_| {"the server did not save the template".to_string()})?;
+            db_state.dispatch(DatabaseAction::ApplyUpdates(vec![(DatabaseItemID::Chat(chat_id), DatabaseItem::Chat(chat))]));
+            Ok(chat_id)
+        },
+        None => {
+            let mut chat = db_state.db.chats.create_possible_chat(template_context(&body), None, proxima_state.device_id, None);
+            chat.chat_title = Some(name);
+            chat.config = config;
+            chat.tags = tags.into_iter().collect();
+            chat.access_modes = access_modes.into_iter().collect();
+            let (delta, new_id, new_item) = get_delta_for_add(
+                DatabaseItemID::Chat(chat.id),
+                DatabaseItem::Chat(chat),
+                async |request| {make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request }, proxima_state.chat_url.clone()).await.map(|response| {response.reply})}
+            ).await;
+            let chat_id = match new_id {
+                DatabaseItemID::Chat(id) => id,
+                _ => panic!("Wrong kind of ID after check, impossible")
+            };
+            db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+            Ok(chat_id)
+        }
+    }
+}
+
+pub async fn delete_template(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID) -> Result<(), String> {
+    let json_request = DBPayload { auth_key: proxima_state.auth_token.clone(), request: DatabaseRequestVariant::Remove(DatabaseItemID::Chat(chat_id)) };
+    make_db_request(json_request, proxima_state.chat_url.clone()).await.map_err(|_| {"the server did not delete the template".to_string()})?;
+    db_state.dispatch(DatabaseAction::RemoveItem(DatabaseItemID::Chat(chat_id)));
+    Ok(())
+}
+
+/// Starts a chat with the filled template as its first prompt, with the template's configuration and tags
+pub async fn start_from_template(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, alerts_state:&UseReducerHandle<Alerts>, template:PromptTemplate, prompt_text:String) {
+    let config = template.config.filter(|config| {db_state.db.configs.get_configs().contains_key(config)});
+    let settings = PromptSettings { config, access_mode: db_state.cursors.chosen_access_mode };
+    let (starting_context, config_opt) = match starting_context_with_config(db_state, prompt_text, config) {
+        Ok(started) => started,
+        Err(error) => {
+            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("Template not started, {error}"))));
+            return
+        }
+    };
+    let mut new_chat = db_state.db.chats.create_possible_chat(starting_context.clone(), None, proxima_state.device_id, config_opt.clone());
+    new_chat.access_modes.insert(settings.access_mode);
+    new_chat.config = config;
+    for tag in template.tags {
+        new_chat.tags.insert(tag);
+    }
+    let (delta, new_id, new_item) = get_delta_for_add(
+        DatabaseItemID::Chat(new_chat.id),
+        DatabaseItem::Chat(new_chat),
+        async |request| {make_db_request(DBPayload { auth_key: proxima_state.auth_token.clone(), request }, proxima_state.chat_url.clone()).await.map(|response| {response.reply})}
+    ).await;
+    let chat_id = match new_id {
+        DatabaseItemID::Chat(id) => id,
+        _ => panic!("Wrong kind of ID after check, impossible")
+    };
+    db_state.dispatch(DatabaseAction::AddItem(delta, new_id, new_item));
+    db_state.dispatch(DatabaseAction::ChangeUsedChatConfig(config));
+    db_state.dispatch(DatabaseAction::SetChat(Some(chat_id)));
+    db_state.dispatch(DatabaseAction::SetTab(1));
+    db_state.dispatch(DatabaseAction::StartSending(chat_id));
+    request_response(db_state, proxima_state, alerts_state, chat_id, starting_context, config_opt, settings).await;
+}
+
+/// Adds the template's tags to a chat that misses some of them
+async fn tag_chat(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID, tags:&BTreeSet<usize>) -> Result<(), String> {
+    match db_state.chat(chat_id) {
+        Some(chat) if !tags.iter().all(|tag| {chat.tags.contains(tag)}) => (),
+        _ => return Ok(())
+    }
+    update_chat(db_state, proxima_state, chat_id, |chat| {
+        for tag in tags {
+            chat.tags.insert(*tag);
+        }
+    }).await.map(|_| {()})
+}
+
+/// Template being written, what is typed stays in the inputs until it is saved
+#[derive(Clone, PartialEq)]
+struct TemplateEditor {
+    template:Option<ChatID>,
+    tags:BTreeSet<usize>,
+    access_modes:BTreeSet<usize>
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TemplatePanelProp {
+    /// Composer the filled template can be inserted into, templates can only start chats without one
+    #[prop_or_default]
+    pub prompt_ref:Option<NodeRef>
+}
+
+#[function_component(TemplatePanel)]
+pub fn template_panel(prop:&TemplatePanelProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let alerts_state = use_context::<UseReducerHandle<Alerts>>().expect("no ctx found");
+    let chosen = use_state_eq(|| {None::<ChatID>});
+    let values = use_state_eq(|| {HashMap::<String, String>::new()});
+    let editor = use_state_eq(|| {None::<TemplateEditor>});
+    let name_ref = use_node_ref();
+    let body_ref = use_node_ref();
+    let config_ref = use_node_ref();
+
+    let templates = templates_for(&db_state.chats, db_state.cursors.chosen_access_mode);
+    let template = chosen.and_then(|chat_id| {templates.iter().find(|template| {template.chat_id == chat_id})}).cloned();
+
+    let template_htmls = templates.iter().map(|listed| {
+        let chosen = chosen.clone();
+        let values = values.clone();
+        let editor = editor.clone();
+        let chat_id = listed.chat_id;
+        let choose_callback = Callback::from(move |mouse_evt:MouseEvent| {
+            chosen.set(Some(chat_id));
+            values.set(HashMap::new());
+            editor.set(None);
+        });
+        html!(
+            <button class={if Some(listed.chat_id) == *chosen {"chat-option chosen-chat text-left"} else {"chat-option text-left"}} onclick={choose_callback}>{listed.name.clone()}</button>
+        )
+    }).collect::<Html>();
+
+    let new_callback = {
+        let chosen = chosen.clone();
+        let editor = editor.clone();
+        let db_state = db_state.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            chosen.set(None);
+            editor.set(Some(TemplateEditor { template: None, tags: BTreeSet::new(), access_modes: BTreeSet::from([db_state.cursors.chosen_access_mode]) }));
+        })
+    };
+
+    let editor_html = match (*editor).clone() {
+        Some(editing) => {
+            let edited = editing.template.and_then(|chat_id| {templates.iter().find(|template| {template.chat_id == chat_id})});
+            let toggle_button = |id:usize, name:String, field_tags:bool| {
+                let editor = editor.clone();
+                let editing = editing.clone();
+                let set = if field_tags {&editing.tags} else {&editing.access_modes};
+                let label = if set.contains(&id) {format!("✓ {name}")} else {name};
+                let toggle_callback = Callback::from(move |mouse_evt:MouseEvent| {
+                    let mut editing = editing.clone();
+                    let set = if field_tags {&mut editing.tags} else {&mut editing.access_modes};
+                    if !set.remove(&id) {
+                        set.insert(id);
+                    }
+                    editor.set(Some(editing));
+                });
+                html!(<button class="mainapp-button standard-padding-margin-corners" onclick={toggle_callback}>{label}</button>)
+            };
+            let tag_htmls = db_state.db.tags.get_tags().iter().map(|(id, tag)| {toggle_button(*id, tag.get_name().clone(), true)}).collect::<Html>();
+            let access_mode_htmls = db_state.db.access_modes.get_modes().iter().map(|(id, access_mode)| {toggle_button(*id, access_mode.get_name().clone(), false)}).collect::<Html>();
+            let config_options = db_state.db.configs.get_configs().iter().map(|(id, config)| {
+                html!(<option value={format!("{id}")} selected={edited.is_some_and(|template| {template.config == Some(*id)})}>{config.name.clone()}</option>)
+            }).collect::<Html>();
+            let save_callback = {
+                let db_state = db_state.clone();
+                let proxima_state = proxima_state.clone();
+                let alerts_state = alerts_state.clone();
+                let chosen = chosen.clone();
+                let editor = editor.clone();
+                let editing = editing.clone();
+                let name_ref = name_ref.clone();
+                let body_ref = body_ref.clone();
+                let config_ref = config_ref.clone();
+                Callback::from(move |mouse_evt:MouseEvent| {
+                    let name = name_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().trim().to_string();
+                    let body = body_ref.cast::<web_sys::HtmlInputElement>().unwrap().value();
+                    let config = config_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse().ok();
+                    if name.is_empty() || body.trim().is_empty() {
+                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Frontend, format!("A template needs a name and a text"))));
+                        return;
+                    }
+                    let db_state = db_state.clone();
+                    let proxima_state = proxima_state.clone();
+                    let alerts_state = alerts_state.clone();
+                    let chosen = chosen.clone();
+                    let editor = editor.clone();
+                    let editing = editing.clone();
+                    spawn_local(async move {
+                        match save_template(&db_state, &proxima_state, editing.template, name, body, config, editing.tags, editing.access_modes).await {
+                            Ok(chat_id) => {
+                                chosen.set(Some(chat_id));
+                                editor.set(None);
+                            },
+                            Err(error) => alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Database, format!("Could not save the template : {error}"))))
+                        }
+                    });
+                })
+            };
+            let cancel_callback = {
+                let editor = editor.clone();
+                Callback::from(move |mouse_evt:MouseEvent| {
+                    editor.set(None);
+                })
+            };
+            html!(
+                <div class="vertical-flex standard-padding-margin-corners second-level">
+                    <div class="label-input-combo">
+                        <input class="standard-padding-margin-corners" placeholder="Template name..." ref={name_ref.clone()} value={edited.map(|template| {template.name.clone()}).unwrap_or_default()}/>
+                        <select class="standard-padding-margin-corners" ref={config_ref.clone()}>
+                            <option value="none" selected={edited.is_none_or(|template| {template.config.is_none()})}>{"No configuration"}</option>
+                            {config_options}
+                        </select>
+                    </div>
+                    <textarea class="standard-padding-margin-corners" placeholder="Prompt, with {{variables}} filled in before sending, \\{{ for braces kept as they are..." ref={body_ref.clone()} value={edited.map(|template| {template.body.clone()}).unwrap_or_default()}/>
+                    <div class="horizontal-flex memory-ids">
+                        <p>{"Tags : "}</p>
+                        {tag_htmls}
+                    </div>
+                    <div class="horizontal-flex memory-ids">
+                        <p>{"Access modes : "}</p>
+                        {access_mode_htmls}
+                    </div>
+                    <div class="horizontal-flex">
+                        <button class="mainapp-button standard-padding-margin-corners" onclick={save_callback}>{"Save template"}</button>
+                        <button class="mainapp-button standard-padding-margin-corners" onclick={cancel_callback}>{"Cancel"}</button>
+                    </div>
+                </div>
+            )
+        },
+        None => html!()
+    };
+
+    let form_html = match (&template, editor.is_some()) {
+        (Some(template), false) => {
+            let variables = template.variables();
+            let missing = variables.iter().filter(|variable| {values.get(*variable).is_none_or(|value| {value.trim().is_empty()})}).cloned().collect::<Vec<String>>();
+            let filled = fill_template(&template.body, &values);
+            let variable_htmls = variables.iter().map(|variable| {
+                let values = values.clone();
+                let name = variable.clone();
+                let input_callback = Callback::from(move |evt:InputEvent| {
+                    let mut new_values = (*values).clone();
+                    new_values.insert(name.clone(), evt.target_unchecked_into::<web_sys::HtmlInputElement>().value());
+                    values.set(new_values);
+                });
+                html!(
+                    <div class="label-input-combo">
+                        <p class="standard-padding-margin-corners">{format!("{variable} : ")}</p>
+                        <input class="standard-padding-margin-corners" value={values.get(variable).cloned().unwrap_or_default()} oninput={input_callback}/>
+                    </div>
+                )
+            }).collect::<Html>();
+            let (action_disabled, action_style) = if missing.is_empty() {
+                (false, "mainapp-button standard-padding-margin-corners")
+            }
+            else {
+                (true, "mainapp-unused-button standard-padding-margin-corners")
+            };
+            let insert_html = match &prop.prompt_ref {
+                Some(prompt_ref) => {
+                    let insert_callback = {
+                        let db_state = db_state.clone();
+                        let proxima_state = proxima_state.clone();
+                        let alerts_state = alerts_state.clone();
+                        let prompt_ref = prompt_ref.clone();
+                        let template = template.clone();
+                        let filled = filled.clone();
+                        Callback::from(move |mouse_evt:MouseEvent| {
+                            let input = prompt_ref.cast::<web_sys::HtmlInputElement>().unwrap();
+                            let text = input.value();
+                            let caret = input.selection_start().ok().flatten().map(|caret| {caret_byte_offset(&text, caret as usize)}).unwrap_or(text.len());
+                            let (before_caret, after_caret) = text.split_at(caret);
+                            input.set_value(&format!("{before_caret}{filled}{after_caret}"));
+                            // the composer saves its draft when it changes
+                            if let Ok(change) = Event::new("change") {
+                                let _ = input.dispatch_event(&change);
+                            }
+                            let _ = input.focus();
+                            if let Some(config) = template.config && db_state.db.configs.get_configs().contains_key(&config) {
+                                db_state.dispatch(DatabaseAction::ChangeUsedChatConfig(Some(config)));
+                            }
+                            if let Some(chat_id) = db_state.cursors.chosen_chat {
+                                let db_state = db_state.clone();
+                                let proxima_state = proxima_state.clone();
+                                let alerts_state = alerts_state.clone();
+                                let tags = template.tags.clone();
+                                spawn_local(async move {
+                                    if let Err(error) = tag_chat(&db_state, &proxima_state, chat_id, &tags).await {
+                                        alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Database, format!("Could not tag the chat : {error}"))));
+                                    }
+                                });
+                            }
+                        })
+                    };
+                    html!(<button class={action_style} disabled={action_disabled} onclick={insert_callback}>{"Insert into the prompt"}</button>)
+                },
+                None => html!()
+            };
+            let start_callback = {
+                let db_state = db_state.clone();
+                let proxima_state = proxima_state.clone();
+                let alerts_state = alerts_state.clone();
+                let template = template.clone();
+                let filled = filled.clone();
+                let values = values.clone();
+                Callback::from(move |mouse_evt:MouseEvent| {
+                    let db_state = db_state.clone();
+                    let proxima_state = proxima_state.clone();
+                    let alerts_state = alerts_state.clone();
+                    let template = template.clone();
+                    let filled = filled.clone();
+                    values.set(HashMap::new());
+                    spawn_local(async move {
+                        start_from_template(&db_state, &proxima_state, &alerts_state, template, filled).await;
+                    });
+                })
+            };
+            let edit_callback = {
+                let editor = editor.clone();
+                let template = template.clone();
+                Callback::from(move |mouse_evt:MouseEvent| {
+                    editor.set(Some(TemplateEditor { template: Some(template.chat_id), tags: template.tags.clone(), access_modes: template.access_modes.clone() }));
+                })
+            };
+            let delete_callback = {
+                let db_state = db_state.clone();
+                let proxima_state = proxima_state.clone();
+                let alerts_state = alerts_state.clone();
+                let chosen = chosen.clone();
+                let chat_id = template.chat_id;
+                Callback::from(move |mouse_evt:MouseEvent| {
+                    let db_state = db_state.clone();
+                    let proxima_state = proxima_state.clone();
+                    let alerts_state = alerts_state.clone();
+                    chosen.set(None);
+                    spawn_local(async move {
+                        if let Err(error) = delete_template(&db_state, &proxima_state, chat_id).await {
+                            alerts_state.dispatch(AlertsAction::AddAlert(AlertData::new(AlertTab::Chats, AlertCategory::Database, format!("Could not delete the template : {error}"))));
+                        }
+                    });
+                })
+            };
+            let bound = {
+                let mut bound = Vec::with_capacity(2);
+                if let Some(config) = template.config.and_then(|config| {db_state.db.configs.get_configs().get(&config)}) {
+                    bound.push(format!("configuration {}", config.name));
+                }
+                let tag_names = template.tags.iter().filter_map(|tag| {db_state.db.tags.get_tags().get(tag).map(|tag| {tag.get_name().clone()})}).collect::<Vec<String>>();
+                if !tag_names.is_empty() {
+                    bound.push(format!("tags {}", tag_names.join(", ")));
+                }
+                bound
+            };
+            html!(
+                <div class="vertical-flex standard-padding-margin-corners second-level">
+                    <div class="chat-title-display">
+                        <h3>{template.name.clone()}</h3>
+                        <div>
+                            <button class="mainapp-button standard-padding-margin-corners" onclick={edit_callback}>{"Edit"}</button>
+                            <button class="mainapp-button standard-padding-margin-corners" onclick={delete_callback}>{"Delete"}</button>
+                        </div>
+                    </div>
+                    {
+                        if bound.is_empty() {
+                            html!()
+                        }
+                        else {
+                            html!(<p class="stream-metrics">{format!("Uses {}", bound.join(" and "))}</p>)
+                        }
+                    }
+                    {variable_htmls}
+                    <p class="template-preview standard-padding-margin-corners third-level">{filled}</p>
+                    {
+                        if missing.is_empty() {
+                            html!()
+                        }
+                        else {
+                            html!(<p class="stream-metrics">{format!("Still to fill : {}", missing.join(", "))}</p>)
+                        }
+                    }
+                    <div class="horizontal-flex">
+                        {insert_html}
+                        <button class={action_style} disabled={action_disabled} onclick={start_callback}>{"Start a chat"}</button>
+                    </div>
+                </div>
+            )
+        },
+        _ => html!()
+    };
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="horizontal-flex memory-ids">
+                <p>{"Templates : "}</p>
+                {
+                    if templates.is_empty() {
+                        html!(<p>{"none in this access mode yet"}</p>)
+                    }
+                    else {
+                        template_htmls
+                    }
+                }
+                <button class="mainapp-button standard-padding-margin-corners" onclick={new_callback}>{"New template"}</button>
+            </div>
+            {editor_html}
+            {form_html}
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::ProxDatabase;
+
+    use super::*;
+
+    fn template_chat(name:&str, body:&str, access_mode:usize) -> Chat {
+        let db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        let mut chat = db.chats.create_possible_chat(template_context(body), None, 0, None);
+        chat.chat_title = Some(name.to_string());
+        chat.access_modes.insert(access_mode);
+        chat
+    }
+
+    fn values(pairs:&[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| {(name.to_string(), value.to_string())}).collect()
+    }
+
+    #[test]
+    fn templates_are_told_apart_from_chats() {
+        let template = template_chat("Review", "Review {{code}}", 0);
+        assert!(is_template(&template));
+        let from_chat = PromptTemplate::from_chat(&template).unwrap();
+        assert_eq!(from_chat.name, "Review");
+        assert_eq!(from_chat.body, "Review {{code}}");
+
+        let mut chat = template.clone();
+        chat.context = WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(String::from("Review {{code}}"))], ContextPosition::System)]);
+        assert!(!is_template(&chat));
+        // a template that got a prompt added is a chat again
+        let mut answered = template.clone();
+        answered.context.add_part(ContextPart::new(vec![ContextData::Text(String::from("hi"))], ContextPosition::User));
+        assert!(!is_template(&answered));
+    }
+
+    #[test]
+    fn the_marker_is_taken_off_text_that_is_not_a_template() {
+        let text = format!("{TEMPLATE_MARKER}\nReview {{{{code}}}}");
+        assert_eq!(without_template_marker(&text), "Review {{code}}");
+        assert_eq!(without_template_marker("Review {{code}}"), "Review {{code}}");
+        let mut chat = template_chat("Imported", "", 0);
+        chat.context = WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(without_template_marker(&text).to_string())], ContextPosition::System)]);
+        assert!(!is_template(&chat));
+    }
+
+    #[test]
+    fn templates_of_an_access_mode_sorted_by_name() {
+        let mut db = ProxDatabase::new_just_data(String::from("test"), String::from("test"));
+        for (name, access_mode) in [("summarize", 0), ("Code review", 0), ("Translate", 1)] {
+            let mut chat = template_chat(name, "{{text}}", access_mode);
+            chat.id = db.chats.latest_id;
+            db.chats.add_chat_raw(chat);
+        }
+        let chat = db.chats.create_possible_chat(WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(String::from("hi"))], ContextPosition::User)]), None, 0, None);
+        db.chats.add_chat_raw(chat);
+        let chats = ChatStore::take_from(&mut db);
+        let names = templates_for(&chats, 0).into_iter().map(|template| {template.name}).collect::<Vec<String>>();
+        assert_eq!(names, vec!["Code review", "summarize"]);
+    }
+
+    #[test]
+    fn variables_in_order_of_first_use() {
+        assert_eq!(template_variables("{{ lang }} to {{target}}, keep {{lang}} terms"), vec!["lang", "target"]);
+        assert!(template_variables("no variables, {{}} nor {{ }}").is_empty());
+        assert!(template_variables("unclosed {{name").is_empty());
+    }
+
+    #[test]
+    fn braces_around_braces_are_not_placeholders() {
+        assert_eq!(template_variables("fn main() {{{x}}}"), vec!["x"]);
+        assert_eq!(fill_template("{{{x}}}", &values(&[("x", "1")])), "{1}");
+    }
+
+    #[test]
+    fn fills_known_variables_only() {
+        let body = "Translate {{text}} into {{language}}";
+        assert_eq!(fill_template(body, &values(&[("text", "hello"), ("language", "French")])), "Translate hello into French");
+        assert_eq!(fill_template(body, &values(&[("text", "hello")])), "Translate hello into {{language}}");
+        // values are put in as they are, even when they look like placeholders
+        assert_eq!(fill_template(body, &values(&[("text", "{{language}}"), ("language", "French")])), "Translate {{language}} into French");
+    }
+
+    #[test]
+    fn escaped_braces_stay_literal() {
+        let body = "Explain \\{{name}} in {{lang}} templates";
+        assert_eq!(template_variables(body), vec!["lang"]);
+        assert_eq!(fill_template(body, &values(&[("lang", "Jinja"), ("name", "unused")])), "Explain {{name}} in Jinja templates");
+        assert_eq!(fill_template("\\{{a}} {{a}}", &HashMap::new()), "{{a}} {{a}}");
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+
+use proxima_backend::ai_interaction::endpoint_api::{EndpointRequestVariant, EndpointResponseVariant};
+use proxima_backend::database::chats::{Chat, ChatID, SessionType};
+use proxima_backend::database::context::{ContextData, ContextPart, ContextPosition, WholeContext};
+use proxima_backend::web_payloads::AIPayload;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Event, Html, MouseEvent, Properties, UseReducerHandle, function_component, html, use_context, use_node_ref, use_state_eq};
+
+use crate::app::{DatabaseAction, DatabaseState, ProximaState, fetch_chat, make_ai_request, save_chat_change, update_chat};
+use crate::local_store::MergeSaved;
+
+pub const TITLES_STORE:&str = "titles";
+const MAX_TITLE_CHARS:usize = 60;
+const MAX_EXCERPT_CHARS:usize = 1500;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct TitleSettings {
+    pub auto_title:bool,
+    pub titling_config:Option<usize>,
+    pub locked_chats:HashSet<ChatID>
+}
+
+impl Default for TitleSettings {
+    fn default() -> Self {
+        Self { auto_title: true, titling_config: None, locked_chats: HashSet::new() }
+    }
+}
+
+impl MergeSaved for TitleSettings {
+    /// Locks made before loading are kept with the saved ones, the saved choices of titling replace the defaults
+    fn merge_saved(&mut self, saved:Self) {
+        self.auto_title = saved.auto_title;
+        self.titling_config = saved.titling_config;
+        self.locked_chats.extend(saved.locked_chats);
+    }
+}
+
+fn excerpt(text:&str) -> String {
+    text.chars().take(MAX_EXCERPT_CHARS).collect()
+}
+
+fn title_context(chat:&Chat) -> Option<WholeContext> {
+    let parts = chat.context.get_parts();
+    let user = parts.iter().find(|part| {part.is_user()})?.data_to_single_text();
+    let ai = parts.iter().find(|part| {matches!(part.get_position(), ContextPosition::AI)})?.data_to_single_text();
+    let prompt = format!(
+        "Give a short title of at most 6 words for the following conversation. Reply with the title only, without quotes or punctuation at the end.\n\nUser :\n{}\n\nAssistant :\n{}",
+        excerpt(&user),
+        excerpt(&ai)
+    );
+    Some(WholeContext::new(vec![ContextPart::new(vec![ContextData::Text(prompt)], ContextPosition::User)]))
+}
+
+/// Keeps the first meaningful line of the answer, without thinking sections or surrounding quotes
+pub fn clean_title(answer:&str) -> Option<String> {
+    let without_thinking = match answer.rfind("</think>") {
+        Some(end) => &answer[end + "</think>".len()..],
+        None => answer
+    };
+    let undecorated = |text:&str| -> String {
+        text.trim().trim_matches(|character:char| {character == '"' || character == '\'' || character == '*' || character == '#' || character == '.'}).trim().to_string()
+    };
+    let line = without_thinking.lines().map(|line| {line.trim()}).find(|line| {!line.is_empty()})?;
+    // the prefix can be inside the markdown, like in "**Title:** ..."
+    let line = undecorated(line);
+    let line = ["Title:", "title:", "TITLE:"].iter().fold(line.as_str(), |line, prefix| {line.strip_prefix(prefix).unwrap_or(line)});
+    let title:String = undecorated(line).chars().take(MAX_TITLE_CHARS).collect();
+    if title.is_empty() {
+        None
+    }
+    else {
+        Some(title)
+    }
+}
+
+pub async fn generate_title(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat:&Chat) -> Option<String> {
+    let context = title_context(chat)?;
+    let config = db_state.title_settings.titling_config.and_then(|config| {db_state.db.configs.get_configs().get(&config).cloned()});
+    let request = AIPayload::new(proxima_state.auth_token.clone(), EndpointRequestVariant::RespondToFullPrompt { whole_context: context, streaming: false, session_type: SessionType::Chat, chat_settings: config, chat_id: None, access_mode: db_state.cursors.chosen_access_mode });
+    match make_ai_request(request, proxima_state.chat_url.clone(), chat.get_id()).await {
+        Ok(response) => match response.reply {
+            EndpointResponseVariant::Block(part) => clean_title(&part.data_to_single_text()),
+            _ => None
+        },
+        Err(_) => None
+    }
+}
+
+pub async fn set_chat_title(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID, title:Option<String>) -> Result<(), String> {
+    update_chat(db_state, proxima_state, chat_id, |chat| {chat.chat_title = title;}).await.map(|_| {()})
+}
+
+/// Called once a response is over, titles chats that still have none after their first exchange
+pub async fn auto_title_if_needed(db_state:&UseReducerHandle<DatabaseState>, proxima_state:&UseReducerHandle<ProximaState>, chat_id:ChatID) {
+    if !db_state.title_settings.auto_title || db_state.title_settings.locked_chats.contains(&chat_id) {
+        return
+    }
+    // the streamed reply is not in this handle's copy of the chat, the server's is complete
+    let Some(chat) = fetch_chat(proxima_state, chat_id).await else {
+        return
+    };
+    if chat.chat_title.is_some() {
+        return
+    }
+    // this runs while the chat is still being sent, and the title could have been set while it was generated
+    if let Some(title) = generate_title(db_state, proxima_state, &chat).await {
+        let _ = save_chat_change(db_state, proxima_state, chat_id, |chat| {
+            if chat.chat_title.is_none() {
+                chat.chat_title = Some(title);
+            }
+        }).await;
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TitlePanelProp {
+    pub chat_id:ChatID
+}
+
+#[function_component(TitlePanel)]
+pub fn title_panel(prop:&TitlePanelProp) -> Html {
+    let proxima_state = use_context::<UseReducerHandle<ProximaState>>().expect("no ctx found");
+    let db_state = use_context::<UseReducerHandle<DatabaseState>>().expect("no ctx found");
+    let title_ref = use_node_ref();
+    let auto_ref = use_node_ref();
+    let config_ref = use_node_ref();
+    let generating = use_state_eq(|| {false});
+    let status = use_state_eq(String::new);
+    let locked = db_state.title_settings.locked_chats.contains(&prop.chat_id);
+    let current_title = db_state.chat(prop.chat_id).and_then(|chat| {chat.chat_title.clone()}).unwrap_or_default();
+
+    let rename_callback = {
+        let proxima_state = proxima_state.clone();
+        let db_state = db_state.clone();
+        let title_ref = title_ref.clone();
+        let status = status.clone();
+        let chat_id = prop.chat_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            let title = title_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().trim().to_string();
+            let proxima_state = proxima_state.clone();
+            let db_state = db_state.clone();
+            let status = status.clone();
+            spawn_local(async move {
+                match set_chat_title(&db_state, &proxima_state, chat_id, if title.is_empty() {None} else {Some(title)}).await {
+                    Ok(()) => status.set(String::new()),
+                    Err(error) => status.set(format!("Could not rename, {error}"))
+                }
+            });
+        })
+    };
+    let retitle_callback = {
+        let proxima_state = proxima_state.clone();
+        let db_state = db_state.clone();
+        let title_ref = title_ref.clone();
+        let generating = generating.clone();
+        let status = status.clone();
+        let chat_id = prop.chat_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            if let Some(chat) = db_state.chat(chat_id).cloned() {
+                let proxima_state = proxima_state.clone();
+                let db_state = db_state.clone();
+                let title_ref = title_ref.clone();
+                let generating = generating.clone();
+                let status = status.clone();
+                generating.set(true);
+                spawn_local(async move {
+                    if let Some(title) = generate_title(&db_state, &proxima_state, &chat).await {
+                        if let Some(input) = title_ref.cast::<web_sys::HtmlInputElement>() {
+                            input.set_value(&title);
+                        }
+                        match set_chat_title(&db_state, &proxima_state, chat_id, Some(title)).await {
+                            Ok(()) => status.set(String::new()),
+                            Err(error) => status.set(format!("Could not rename, {error}"))
+                        }
+                    }
+                    generating.set(false);
+                });
+            }
+        })
+    };
+    let lock_callback = {
+        let db_state = db_state.clone();
+        let chat_id = prop.chat_id;
+        Callback::from(move |mouse_evt:MouseEvent| {
+            db_state.dispatch(DatabaseAction::SetTitleLock { chat: chat_id, locked: !locked });
+        })
+    };
+    let settings_callback = {
+        let db_state = db_state.clone();
+        let auto_ref = auto_ref.clone();
+        let config_ref = config_ref.clone();
+        Callback::from(move |evt:Event| {
+            db_state.dispatch(DatabaseAction::SetAutoTitle {
+                auto: auto_ref.cast::<web_sys::HtmlInputElement>().unwrap().checked(),
+                config: config_ref.cast::<web_sys::HtmlInputElement>().unwrap().value().parse().ok()
+            });
+        })
+    };
+
+    let config_options = db_state.db.configs.get_configs().iter().map(|(id, config)| {
+        html!(<option value={format!("{id}")} selected={db_state.title_settings.titling_config == Some(*id)}>{config.name.clone()}</option>)
+    }).collect::<Html>();
+    let (retitle_disabled, retitle_style) = if locked || *generating {
+        (true, "mainapp-unused-button standard-padding-margin-corners")
+    }
+    else {
+        (false, "mainapp-button standard-padding-margin-corners")
+    };
+
+    html!(
+        <div class="third-level standard-padding-margin-corners">
+            <div class="label-input-combo">
+                <input class="standard-padding-margin-corners" placeholder="Chat title..." ref={title_ref} value={current_title}/>
+                <button class="mainapp-button standard-padding-margin-corners" onclick={rename_callback}>{"Rename"}</button>
+                <button class={retitle_style} disabled={retitle_disabled} onclick={retitle_callback}>{if *generating {"Generating..."} else {"Re-title with AI"}}</button>
+                <button class="mainapp-button standard-padding-margin-corners" onclick={lock_callback}>{if locked {"Unlock title"} else {"Lock title"}}</button>
+            </div>
+            <div class="horizontal-flex">
+                <label class="standard-padding-margin-corners"><input type="checkbox" ref={auto_ref} checked={db_state.title_settings.auto_title} onchange={settings_callback.clone()}/>{"Title new chats automatically with"}</label>
+                <select class="standard-padding-margin-corners" ref={config_ref} onchange={settings_callback}>
+                    <option value="none" selected={db_state.title_settings.titling_config.is_none()}>{"No configuration"}</option>
+                    {config_options}
+                </select>
+            </div>
+            <p>{(*status).clone()}</p>
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_and_markdown_around_the_title_go() {
+        assert_eq!(clean_title("\"Rust lifetimes explained\""), Some(String::from("Rust lifetimes explained")));
+        assert_eq!(clean_title("'Sorting a list'."), Some(String::from("Sorting a list")));
+        assert_eq!(clean_title("## **Baking bread**"), Some(String::from("Baking bread")));
+    }
+
+    #[test]
+    fn title_prefixes_go() {
+        assert_eq!(clean_title("Title: Trip to Lisbon"), Some(String::from("Trip to Lisbon")));
+        assert_eq!(clean_title("title: \"Trip to Lisbon\""), Some(String::from("Trip to Lisbon")));
+        assert_eq!(clean_title("**Title:** Trip to Lisbon"), Some(String::from("Trip to Lisbon")));
+    }
+
+    #[test]
+    fn only_the_first_line_after_thinking_is_kept() {
+        assert_eq!(clean_title("<think>A title about cats\nmaybe</think>\n\nCat care basics\nThis chat is about cats."), Some(String::from("Cat care basics")));
+        assert_eq!(clean_title("\n\n  Weekly planning  \n"), Some(String::from("Weekly planning")));
+    }
+
+    #[test]
+    fn long_titles_are_cut() {
+        let title = clean_title(&"word ".repeat(40)).unwrap();
+        assert_eq!(title.chars().count(), MAX_TITLE_CHARS);
+        // characters are counted, not bytes
+        assert_eq!(clean_title(&"é".repeat(100)).unwrap().chars().count(), MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn empty_answers_give_no_title() {
+        assert_eq!(clean_title(""), None);
+        assert_eq!(clean_title("<think>still thinking</think>\n  \n"), None);
+        assert_eq!(clean_title("\"\"..."), None);
+        assert_eq!(clean_title("Title:"), None);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use proxima_backend::{ai_interaction::endpoint_api::EndpointResponseVariant, database::chats::ChatID};
+
+pub const GAP_TIMEOUT_MILLIS:i64 = 1500;
+
+pub struct TokenReorderBuffer {
+    next_token_id:u64,
+    pending:BTreeMap<u64, EndpointResponseVariant>,
+    gap_since:Option<DateTime<Utc>>,
+    pub skipped_tokens:bool,
+}
+
+impl TokenReorderBuffer {
+    pub fn new() -> Self {
+        Self { next_token_id: 0, pending: BTreeMap::new(), gap_since: None, skipped_tokens:false }
+    }
+    /// Takes a token as it arrives and returns every token that can now be applied, in order
+    pub fn receive(&mut self, token_id:u64, event:EndpointResponseVariant, now:DateTime<Utc>) -> Vec<(u64, EndpointResponseVariant)> {
+        if token_id == 0 && self.next_token_id > 0 && matches!(event, EndpointResponseVariant::StartStream(_, _)) {
+            // token ids restart at 0 for every request made for this chat
+            *self = Self::new();
+        }
+        if token_id < self.next_token_id {
+            return Vec::new()
+        }
+        self.pending.insert(token_id, event);
+        let ready = self.take_in_sequence();
+        if self.pending.is_empty() {
+            self.gap_since = None;
+        }
+        else if self.gap_since.is_none() {
+            self.gap_since = Some(now);
+        }
+        ready
+    }
+    fn take_in_sequence(&mut self) -> Vec<(u64, EndpointResponseVariant)> {
+        let mut ready = Vec::with_capacity(self.pending.len());
+        while let Some(event) = self.pending.remove(&self.next_token_id) {
+            ready.push((self.next_token_id, event));
+            self.next_token_id += 1;
+        }
+        ready
+    }
+    /// When the current gap stops being waited on
+    pub fn gap_deadline(&self) -> Option<DateTime<Utc>> {
+        self.gap_since.map(|since| {since + TimeDelta::milliseconds(GAP_TIMEOUT_MILLIS)})
+    }
+    pub fn gap_timed_out(&self, now:DateTime<Utc>) -> bool {
+        self.gap_deadline().is_some_and(|deadline| {now > deadline})
+    }
+    /// Gives up on the missing tokens and returns everything that was waiting behind them
+    pub fn skip_gap(&mut self) -> Vec<(u64, EndpointResponseVariant)> {
+        let mut ready = Vec::with_capacity(self.pending.len());
+        while let Some((token_id, _)) = self.pending.first_key_value() {
+            self.next_token_id = *token_id;
+            ready.append(&mut self.take_in_sequence());
+        }
+        self.gap_since = None;
+        self.skipped_tokens = true;
+        ready
+    }
+}
+
+pub struct ChatTokenBuffers {
+    buffers:HashMap<ChatID, TokenReorderBuffer>
+}
+
+impl ChatTokenBuffers {
+    pub fn new() -> Self {
+        Self { buffers: HashMap::with_capacity(16) }
+    }
+    pub fn receive(&mut self, chat_id:ChatID, token_id:u64, event:EndpointResponseVariant, now:DateTime<Utc>) -> Vec<(u64, EndpointResponseVariant)> {
+        self.buffers.entry(chat_id).or_insert_with(TokenReorderBuffer::new).receive(token_id, event, now)
+    }
+    /// The earliest time a gap of any chat times out
+    pub fn next_gap_deadline(&self) -> Option<DateTime<Utc>> {
+        self.buffers.values().filter_map(|buffer| {buffer.gap_deadline()}).min()
+    }
+    /// Returns the chats whose gap was not filled in time, with the tokens that were waiting behind it
+    pub fn skip_timed_out_gaps(&mut self, now:DateTime<Utc>) -> Vec<(ChatID, Vec<(u64, EndpointResponseVariant)>)> {
+        self.buffers.iter_mut().filter(|(_, buffer)| {buffer.gap_timed_out(now)}).map(|(chat_id, buffer)| {
+            (*chat_id, buffer.skip_gap())
+        }).collect()
+    }
+    /// Whether tokens were lost for this chat, resetting the flag
+    pub fn take_skipped(&mut self, chat_id:ChatID) -> bool {
+        self.buffers.get_mut(&chat_id).is_some_and(|buffer| {std::mem::replace(&mut buffer.skipped_tokens, false)})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proxima_backend::database::context::{ContextData, ContextPosition};
+
+    use super::*;
+
+    fn start() -> EndpointResponseVariant {
+        EndpointResponseVariant::StartStream(ContextData::Text(String::new()), ContextPosition::AI)
+    }
+
+    fn token() -> EndpointResponseVariant {
+        EndpointResponseVariant::ContinueStream(ContextData::Text(String::from("a")), ContextPosition::AI)
+    }
+
+    fn ids(ready:&[(u64, EndpointResponseVariant)]) -> Vec<u64> {
+        ready.iter().map(|(token_id, _)| {*token_id}).collect()
+    }
+
+    fn at(millis:i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    #[test]
+    fn tokens_in_order_go_through() {
+        let mut buffer = TokenReorderBuffer::new();
+        assert_eq!(ids(&buffer.receive(0, start(), at(0))), vec![0]);
+        assert_eq!(ids(&buffer.receive(1, token(), at(1))), vec![1]);
+        assert_eq!(buffer.gap_deadline(), None);
+    }
+
+    #[test]
+    fn tokens_wait_behind_a_gap() {
+        let mut buffer = TokenReorderBuffer::new();
+        buffer.receive(0, start(), at(0));
+        assert!(buffer.receive(2, token(), at(10)).is_empty());
+        assert!(buffer.receive(3, token(), at(20)).is_empty());
+        assert_eq!(buffer.gap_deadline(), Some(at(10 + GAP_TIMEOUT_MILLIS)));
+        assert_eq!(ids(&buffer.receive(1, token(), at(30))), vec![1, 2, 3]);
+        assert_eq!(buffer.gap_deadline(), None);
+    }
+
+    #[test]
+    fn old_and_repeated_tokens_are_dropped() {
+        let mut buffer = TokenReorderBuffer::new();
+        buffer.receive(0, start(), at(0));
+        buffer.receive(1, token(), at(0));
+        assert!(buffer.receive(1, token(), at(0)).is_empty());
+        assert!(buffer.receive(0, token(), at(0)).is_empty());
+    }
+
+    #[test]
+    fn a_new_stream_starts_over() {
+        let mut buffer = TokenReorderBuffer::new();
+        buffer.receive(0, start(), at(0));
+        buffer.receive(1, token(), at(0));
+        assert_eq!(ids(&buffer.receive(0, start(), at(0))), vec![0]);
+        assert_eq!(ids(&buffer.receive(1, token(), at(0))), vec![1]);
+    }
+
+    #[test]
+    fn timed_out_gaps_are_skipped() {
+        let mut buffer = TokenReorderBuffer::new();
+        buffer.receive(0, start(), at(0));
+        buffer.receive(2, token(), at(100));
+        buffer.receive(5, token(), at(200));
+        assert!(!buffer.gap_timed_out(at(100 + GAP_TIMEOUT_MILLIS)));
+        assert!(buffer.gap_timed_out(at(101 + GAP_TIMEOUT_MILLIS)));
+        assert_eq!(ids(&buffer.skip_gap()), vec![2, 5]);
+        assert!(buffer.skipped_tokens);
+        assert_eq!(buffer.gap_deadline(), None);
+        assert_eq!(ids(&buffer.receive(6, token(), at(300))), vec![6]);
+    }
+
+    #[test]
+    fn each_chat_has_its_own_gap() {
+        let mut buffers = ChatTokenBuffers::new();
+        buffers.receive(1, 0, start(), at(0));
+        buffers.receive(1, 2, token(), at(0));
+        buffers.receive(2, 0, start(), at(500));
+        buffers.receive(2, 2, token(), at(500));
+        assert_eq!(buffers.next_gap_deadline(), Some(at(GAP_TIMEOUT_MILLIS)));
+
+        // the second chat keeps streaming, the first one's gap still times out
+        assert_eq!(ids(&buffers.receive(2, 1, token(), at(GAP_TIMEOUT_MILLIS + 1))), vec![1, 2]);
+        let skipped = buffers.skip_timed_out_gaps(at(GAP_TIMEOUT_MILLIS + 1));
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, 1);
+        assert_eq!(ids(&skipped[0].1), vec![2]);
+        assert_eq!(buffers.next_gap_deadline(), None);
+        assert!(buffers.take_skipped(1));
+        assert!(!buffers.take_skipped(1));
+        assert!(!buffers.take_skipped(2));
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use proxima_backend::database::configuration::{ChatConfiguration, ChatSetting};
+use proxima_backend::database::context::ContextPart;
+use serde::{Deserialize, Serialize};
+
+use crate::local_store::MergeSaved;
+
+pub const TOKENIZERS_STORE:&str = "tokenizers";
+
+/// None of the tokenizers here know the vocabulary of the model, so every count is an estimate
+/// and has to be shown as one
+pub trait Tokenizer {
+    fn count(&self, text:&str) -> usize;
+}
+
+/// About 4 characters per token, what most English text gives with BPE tokenizers
+pub struct CharRatioTokenizer {
+    chars_per_token:f64
+}
+
+impl Tokenizer for CharRatioTokenizer {
+    fn count(&self, text:&str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Splits text the way GPT-style pre-tokenizers do (words, numbers, punctuation, whitespace),
+/// then counts long words as several tokens and every CJK character as its own token
+pub struct PreTokenizer {
+    max_word_chars:usize,
+    max_digits:usize
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum CharClass {
+    Letter,
+    Digit,
+    Space,
+    Wide,
+    Other
+}
+
+fn char_class(character:char) -> CharClass {
+    if matches!(character as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF) {
+        CharClass::Wide
+    }
+    else if character.is_alphabetic() || character == '\'' {
+        CharClass::Letter
+    }
+    else if character.is_numeric() {
+        CharClass::Digit
+    }
+    else if character.is_whitespace() {
+        CharClass::Space
+    }
+    else {
+        CharClass::Other
+    }
+}
+
+impl Tokenizer for PreTokenizer {
+    fn count(&self, text:&str) -> usize {
+        let mut tokens = 0;
+        let mut run_class = None;
+        let mut run_length = 0;
+        let close_run = |class:Option<CharClass>, length:usize| -> usize {
+            match class {
+                Some(CharClass::Letter) => length.div_ceil(self.max_word_chars),
+                Some(CharClass::Digit) => length.div_ceil(self.max_digits),
+                // a single space sticks to the next word, longer runs of whitespace are their own tokens
+                Some(CharClass::Space) => if length > 1 {length.div_ceil(4)} else {0},
+                Some(CharClass::Wide) | Some(CharClass::Other) => length,
+                None => 0
+            }
+        };
+        for character in text.chars() {
+            let class = char_class(character);
+            if Some(class) == run_class && !matches!(class, CharClass::Wide) {
+                run_length += 1;
+            }
+            else {
+                tokens += close_run(run_class, run_length);
+                run_class = Some(class);
+                run_length = 1;
+            }
+        }
+        tokens + close_run(run_class, run_length)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub enum TokenizerKind {
+    #[default]
+    CharRatio,
+    PreTokenized,
+    CodeHeavy
+}
+
+impl TokenizerKind {
+    pub fn all() -> Vec<Self> {
+        vec![Self::CharRatio, Self::PreTokenized, Self::CodeHeavy]
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CharRatio => "Estimate : 4 chars per token",
+            Self::PreTokenized => "Estimate : word based (GPT-style)",
+            Self::CodeHeavy => "Estimate : code heavy, 3 chars per token"
+        }
+    }
+    pub fn tokenizer(&self) -> Box<dyn Tokenizer> {
+        match self {
+            Self::CharRatio => Box::new(CharRatioTokenizer { chars_per_token: 4.0 }),
+            Self::PreTokenized => Box::new(PreTokenizer { max_word_chars: 6, max_digits: 3 }),
+            Self::CodeHeavy => Box::new(CharRatioTokenizer { chars_per_token: 3.0 })
+        }
+    }
+}
+
+/// Kept on this device, chat configurations have no field for it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TokenizerSettings {
+    pub per_config:HashMap<usize, TokenizerKind>
+}
+
+impl TokenizerSettings {
+    pub fn kind_for(&self, config:Option<usize>) -> TokenizerKind {
+        config.and_then(|config| {self.per_config.get(&config).copied()}).unwrap_or_default()
+    }
+}
+
+impl MergeSaved for TokenizerSettings {
+    /// Configs given a tokenizer before loading keep it
+    fn merge_saved(&mut self, saved:Self) {
+        for (config, kind) in saved.per_config {
+            self.per_config.entry(config).or_insert(kind);
+        }
+    }
+}
+
+/// How counts are written anywhere they are shown
+pub fn format_estimate(tokens:usize) -> String {
+    format!("~{tokens}")
+}
+
+pub fn count_part(tokenizer:&dyn Tokenizer, part:&ContextPart) -> usize {
+    tokenizer.count(&part.data_to_single_text())
+}
+
+pub fn count_parts(tokenizer:&dyn Tokenizer, parts:&[ContextPart]) -> usize {
+    parts.iter().map(|part| {count_part(tokenizer, part)}).sum()
+}
+
+pub fn max_context_length(config:Option<&ChatConfiguration>) -> Option<usize> {
+    config?.raw_settings.iter().find_map(|setting| {
+        match setting {
+            ChatSetting::MaxContextLength(length) => Some(*length as usize),
+            _ => None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_based() -> Box<dyn Tokenizer> {
+        TokenizerKind::PreTokenized.tokenizer()
+    }
+
+    #[test]
+    fn prose_is_about_a_token_per_word() {
+        let sentence = "The quick brown fox jumps over the lazy dog.";
+        // nine words and the full stop, the spaces stick to the words
+        assert_eq!(word_based().count(sentence), 10);
+        assert_eq!(TokenizerKind::CharRatio.tokenizer().count(sentence), 11);
+        // long words are split, like BPE does with rare words
+        assert_eq!(word_based().count("internationalization"), 4);
+    }
+
+    #[test]
+    fn code_counts_its_symbols_and_indentation() {
+        let code = "fn main() {\n    let total = 1234567;\n}";
+        let words = word_based().count(code);
+        // every bracket and symbol is its own token, so code costs more per character than prose
+        assert!(words > word_based().count("fn main let total"));
+        assert_eq!(word_based().count("    "), 1);
+        assert_eq!(word_based().count("1234567"), 3);
+        assert!(TokenizerKind::CodeHeavy.tokenizer().count(code) > TokenizerKind::CharRatio.tokenizer().count(code));
+    }
+
+    #[test]
+    fn every_cjk_character_is_a_token() {
+        assert_eq!(word_based().count("你好世界"), 4);
+        assert_eq!(word_based().count("こんにちは"), 5);
+        assert_eq!(word_based().count("안녕 하세요"), 5);
+        // the character ratio estimate is far too low for these
+        assert_eq!(TokenizerKind::CharRatio.tokenizer().count("你好世界"), 1);
+    }
+
+    #[test]
+    fn empty_text_has_no_tokens() {
+        for kind in TokenizerKind::all() {
+            assert_eq!(kind.tokenizer().count(""), 0);
+        }
+    }
+
+    #[test]
+    fn configs_without_a_tokenizer_use_the_default() {
+        let mut settings = TokenizerSettings::default();
+        settings.per_config.insert(2, TokenizerKind::CodeHeavy);
+        assert_eq!(settings.kind_for(Some(2)), TokenizerKind::CodeHeavy);
+        assert_eq!(settings.kind_for(Some(3)), TokenizerKind::CharRatio);
+        assert_eq!(settings.kind_for(None), TokenizerKind::CharRatio);
+        let mut saved = TokenizerSettings::default();
+        saved.per_config.insert(2, TokenizerKind::PreTokenized);
+        saved.per_config.insert(5, TokenizerKind::PreTokenized);
+        settings.merge_saved(saved);
+        assert_eq!(settings.kind_for(Some(2)), TokenizerKind::CodeHeavy);
+        assert_eq!(settings.kind_for(Some(5)), TokenizerKind::PreTokenized);
+    }
+}
@@ -0,0 +1,821 @@
+use std::rc::Rc;
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use proxima_backend::ai_interaction::tools::ProximaTool;
+use serde_json::Value;
+use wasm_bindgen_futures::spawn_local;
+use yew::{Callback, Html, MouseEvent, Properties, function_component, html, use_context, use_state_eq};
+
+use crate::code_blocks::CodeBlock;
+use crate::files::{is_web_link, open_url};
+use crate::html_parsing::{Element, HtmlNode, TagKind, parse_tags};
+use crate::stream_metrics::{TagTimes, format_millis};
+use crate::tabs::chat_tab::FindInChat;
+
+/// Tool named in a `<tool>` tag, names are compared without case, spaces or underscores
+pub fn tool_from_name(name:&str) -> Option<ProximaTool> {
+    let normalized = name.chars().filter(|character| {!character.is_whitespace() && *character != '_' && *character != '-'}).collect::<String>().to_lowercase();
+    match normalized.as_str() {
+        "calculator" | "calc" => Some(ProximaTool::Calculator),
+        "localmemory" => Some(ProximaTool::LocalMemory),
+        "web" | "websearch" | "search" => Some(ProximaTool::Web),
+        "python" => Some(ProximaTool::Python),
+        "agent" => Some(ProximaTool::Agent),
+        "rng" | "random" => Some(ProximaTool::Rng),
+        "memory" => Some(ProximaTool::Memory),
+        "jobs" | "job" => Some(ProximaTool::Jobs),
+        "time" | "currenttime" => Some(ProximaTool::Time),
+        _ => None
+    }
+}
+
+/// A tool call found in a chat, with the date of the part it was made in
+#[derive(Clone, PartialEq)]
+pub struct CallInfo {
+    pub tool_name:String,
+    pub txt:String,
+    pub date:Option<DateTime<Utc>>
+}
+
+impl CallInfo {
+    pub fn from_element(call:&Element, date:Option<DateTime<Utc>>) -> Self {
+        Self { tool_name: call.children.first_of_kind(TagKind::Tool).map(|tool| {tool.text().trim().to_string()}).unwrap_or_default(), txt: call.content.clone(), date }
+    }
+}
+
+/// Where an element parsed from a text lies in it, looked for from `from` since elements come in order : the
+/// offsets of its opening tag, of its content and of the end of its content
+pub fn element_span(text:&str, from:usize, element:&Element) -> Option<(usize, usize, usize)> {
+    let start = from + text.get(from..)?.find(&format!("<{}", element.name))?;
+    let content_start = start + text[start..].find('>')? + 1;
+    Some((start, content_start, content_start + element.content.len()))
+}
+
+/// Tag times of the part a text comes from, with where the text starts in the part
+#[derive(Clone, PartialEq)]
+pub struct TextTiming {
+    pub times:Rc<TagTimes>,
+    pub offset:usize
+}
+
+impl TextTiming {
+    /// When the text at this offset of the timed text arrived
+    pub fn at(&self, offset:usize) -> Option<DateTime<Utc>> {
+        self.times.at(self.offset + offset)
+    }
+    /// Timing of the text found at this offset of the timed text
+    pub fn shifted(&self, offset:usize) -> Self {
+        Self { times: self.times.clone(), offset: self.offset + offset }
+    }
+}
+
+/// Sub-agents are shown inside each other up to this depth, deeper threads are shown as text
+const MAX_AGENT_DEPTH:usize = 8;
+
+/// What a model wrote inside a call or an outputs tag, split into its sub tags and the text around them
+#[derive(Clone, PartialEq, Default)]
+pub struct ToolText {
+    pub fields:Vec<(String, String)>,
+    pub text:String
+}
+
+impl ToolText {
+    pub fn parse(txt:&str) -> Self {
+        let parsed = parse_tags(txt);
+        let mut fields = Vec::new();
+        let mut text = String::new();
+        for child in &parsed.children {
+            match child {
+                HtmlNode::Element(element) if element.kind != TagKind::Tool => fields.push((element.name.clone(), element.content.trim().to_string())),
+                HtmlNode::Element(_) => (),
+                HtmlNode::Text(txt) => text.push_str(txt)
+            }
+        }
+        text.push_str(&parsed.pending);
+        Self { fields, text: text.trim().to_string() }
+    }
+    /// The main content, the only sub tag when there is one and no text around it
+    pub fn body(&self) -> String {
+        if self.text.is_empty() && self.fields.len() == 1 {
+            self.fields[0].1.clone()
+        }
+        else if self.text.is_empty() {
+            self.fields.iter().map(|(_, content)| {content.as_str()}).collect::<Vec<&str>>().join("\n")
+        }
+        else {
+            self.text.clone()
+        }
+    }
+    pub fn field(&self, names:&[&str]) -> Option<String> {
+        self.fields.iter().find(|(name, _)| {names.contains(&name.to_lowercase().as_str())}).map(|(_, content)| {content.clone()})
+    }
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_str(self.body().trim()).ok()
+    }
+    /// Everything as one JSON value, for tools without a dedicated view
+    pub fn to_json(&self) -> Value {
+        if let Some(value) = self.json() {
+            return value
+        }
+        let mut object = serde_json::Map::new();
+        for (name, content) in &self.fields {
+            object.insert(name.clone(), serde_json::from_str(content).unwrap_or(Value::String(content.clone())));
+        }
+        if !self.text.is_empty() {
+            object.insert("text".to_string(), Value::String(self.text.clone()));
+        }
+        Value::Object(object)
+    }
+}
+
+fn json_string(value:&Value, keys:&[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        match value.get(*key)? {
+            Value::String(string) => Some(string.clone()),
+            Value::Null => None,
+            other => Some(other.to_string())
+        }
+    })
+}
+
+/// Items of a JSON array, or of the first array found in a JSON object
+fn json_items(value:&Value) -> Option<Vec<Value>> {
+    match value {
+        Value::Array(items) => Some(items.clone()),
+        Value::Object(object) => object.values().find_map(|value| {value.as_array().cloned()}),
+        _ => None
+    }
+}
+
+fn query_of(find:&FindInChat) -> Option<String> {
+    find.query().map(|query| {query.to_string()})
+}
+
+fn pretty_json(find:&FindInChat, value:&Value) -> Html {
+    html!(
+        <CodeBlock code={serde_json::to_string_pretty(value).unwrap_or(value.to_string())} language={"json".to_string()} highlighted={query_of(find)}/>
+    )
+}
+
+fn python_code(find:&FindInChat, code:&str) -> Html {
+    if code.contains("```") {
+        find.render_lines(code)
+    }
+    else {
+        html!(
+            <CodeBlock code={code.trim_matches('\n').to_string()} language={"python".to_string()} highlighted={query_of(find)}/>
+        )
+    }
+}
+
+struct WebResult {
+    title:String,
+    link:Option<String>,
+    snippet:String
+}
+
+fn first_link(text:&str) -> Option<String> {
+    let start = text.find("http://").or(text.find("https://"))?;
+    let link = text[start..].split(|character:char| {character.is_whitespace() || matches!(character, ')' | '>' | '"' | ']')}).next()?;
+    Some(link.to_string())
+}
+
+fn web_results(output:&ToolText) -> Vec<WebResult> {
+    if let Some(items) = output.json().as_ref().and_then(json_items) {
+        return items.iter().map(|item| {
+            WebResult {
+                title: json_string(item, &["title", "name"]).unwrap_or("Untitled result".to_string()),
+                link: json_string(item, &["url", "link", "href"]).filter(|link| {is_web_link(link)}),
+                snippet: json_string(item, &["snippet", "description", "content", "text"]).unwrap_or_default()
+            }
+        }).collect()
+    }
+    // one result per paragraph otherwise, titled by its first line that is not only a link
+    output.body().split("\n\n").filter(|block| {!block.trim().is_empty()}).map(|block| {
+        let link = first_link(block);
+        let mut lines = block.lines().map(|line| {line.trim()}).filter(|line| {!line.is_empty() && Some(line.trim_start_matches("URL:").trim().to_string()) != link});
+        let title = lines.next().unwrap_or("Result").trim_start_matches("Title:").trim().to_string();
+        let snippet = lines.collect::<Vec<&str>>().join(" ");
+        WebResult { title, link, snippet }
+    }).collect()
+}
+
+/// Sections of a Python output, taken from stdout/stderr tags, JSON fields or "stderr:" headers
+fn python_streams(output:&ToolText) -> (String, String) {
+    if output.field(&["stdout", "stderr"]).is_some() {
+        return (output.field(&["stdout"]).unwrap_or_default(), output.field(&["stderr"]).unwrap_or_default())
+    }
+    if let Some(value) = output.json() && (value.get("stdout").is_some() || value.get("stderr").is_some()) {
+        return (json_string(&value, &["stdout"]).unwrap_or_default(), json_string(&value, &["stderr"]).unwrap_or_default())
+    }
+    let body = output.body();
+    let lower = body.to_ascii_lowercase();
+    match lower.find("stderr:") {
+        Some(start) => (body[..start].trim().trim_start_matches("stdout:").trim_start_matches("STDOUT:").trim().to_string(), body[start + "stderr:".len()..].trim().to_string()),
+        None => if lower.contains("traceback (most recent call last)") {
+            (String::new(), body)
+        }
+        else {
+            (body.trim_start_matches("stdout:").trim().to_string(), String::new())
+        }
+    }
+}
+
+fn memory_items(text:&ToolText) -> Vec<String> {
+    if let Some(items) = text.json().as_ref().and_then(json_items) {
+        return items.iter().map(|item| {
+            match item {
+                Value::String(string) => string.clone(),
+                other => json_string(other, &["content", "text", "memory", "value"]).unwrap_or(other.to_string())
+            }
+        }).collect()
+    }
+    let body = text.body();
+    let listed = body.lines().map(|line| {line.trim()}).filter(|line| {!line.is_empty()}).collect::<Vec<&str>>();
+    if listed.len() > 1 && listed.iter().all(|line| {line.starts_with("- ") || line.starts_with("* ") || line.split_once(". ").is_some_and(|(number, _)| {number.parse::<usize>().is_ok()})}) {
+        listed.iter().map(|line| {
+            line.trim_start_matches("- ").trim_start_matches("* ").split_once(". ").filter(|(number, _)| {number.parse::<usize>().is_ok()}).map(|(_, item)| {item}).unwrap_or(line.trim_start_matches("- ").trim_start_matches("* ")).to_string()
+        }).collect()
+    }
+    else if body.is_empty() {
+        Vec::new()
+    }
+    else {
+        vec![body]
+    }
+}
+
+const TIME_FORMATS:&[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+fn parse_time(text:&str) -> Option<String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text).or(DateTime::parse_from_rfc2822(text)) {
+        return Some(date.with_timezone(&Local).format("%a %d %b %Y, %H:%M:%S").to_string())
+    }
+    TIME_FORMATS.iter().find_map(|format| {
+        NaiveDateTime::parse_from_str(text, format).ok().map(|date| {date.format("%a %d %b %Y, %H:%M:%S").to_string()})
+    })
+}
+
+/// A date found in a time output, shown in local time when it has a timezone
+fn compact_time(text:&str) -> Option<String> {
+    let text = text.trim();
+    parse_time(text).or_else(|| {
+        // a date somewhere in a sentence, tried with the lengths the formats above can have
+        text.char_indices().filter(|(_, character)| {character.is_ascii_digit()}).find_map(|(start, _)| {
+            [35, 32, 29, 26, 25, 23, 20, 19, 16].iter().find_map(|length| {
+                text.get(start..start + length).and_then(parse_time)
+            })
+        })
+    })
+}
+
+struct JobStatus {
+    name:String,
+    status:String
+}
+
+fn job_statuses(output:&ToolText) -> Vec<JobStatus> {
+    if let Some(value) = output.json() {
+        let items = json_items(&value).unwrap_or(vec![value]);
+        return items.iter().map(|item| {
+            JobStatus {
+                name: json_string(item, &["name", "job", "id", "title"]).unwrap_or("Job".to_string()),
+                status: json_string(item, &["status", "state", "result"]).unwrap_or_default()
+            }
+        }).collect()
+    }
+    output.body().lines().map(|line| {line.trim().trim_start_matches("- ")}).filter(|line| {!line.is_empty()}).map(|line| {
+        match line.split_once(':') {
+            Some((name, status)) => JobStatus { name: name.trim().to_string(), status: status.trim().to_string() },
+            None => JobStatus { name: line.to_string(), status: String::new() }
+        }
+    }).collect()
+}
+
+fn status_class(status:&str) -> &'static str {
+    let status = status.to_lowercase();
+    if ["done", "finished", "complete", "success", "succeeded", "ok"].iter().any(|word| {status.contains(word)}) {
+        "job-status job-done"
+    }
+    else if ["fail", "error", "cancel"].iter().any(|word| {status.contains(word)}) {
+        "job-status job-failed"
+    }
+    else {
+        "job-status"
+    }
+}
+
+fn fields_html(find:&FindInChat, call:&ToolText) -> Html {
+    call.fields.iter().map(|(name, content)| {
+        html!(<div class="tool-field"><b>{format!("{name} : ")}</b>{find.render_markdown(content)}</div>)
+    }).collect::<Html>()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ToolCallViewProp {
+    pub tool_name:String,
+    pub txt:String
+}
+
+#[function_component(ToolCallView)]
+pub fn tool_call_view(prop:&ToolCallViewProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
+    let call = ToolText::parse(&prop.txt);
+    match tool_from_name(&prop.tool_name) {
+        Some(ProximaTool::Calculator) => html!(
+            <p class="tool-view"><code>{call.body()}</code>{" = ..."}</p>
+        ),
+        Some(ProximaTool::Python) => python_code(&find, &call.body()),
+        Some(ProximaTool::Web) => html!(
+            <p class="tool-view">{"Searching : "}<b>{call.body()}</b></p>
+        ),
+        Some(ProximaTool::Time) => html!(
+            <p class="tool-view">{"Asking for the time"}</p>
+        ),
+        Some(ProximaTool::Agent) => html!(
+            <div class="tool-view"><b>{"Task given to the sub-agent : "}</b>{find.render_lines(&agent_prompt(&call))}</div>
+        ),
+        Some(ProximaTool::Memory) | Some(ProximaTool::LocalMemory) | Some(ProximaTool::Jobs) if !call.fields.is_empty() => html!(
+            <div class="tool-view">{fields_html(&find, &call)}</div>
+        ),
+        Some(ProximaTool::Memory) | Some(ProximaTool::LocalMemory) | Some(ProximaTool::Jobs) => html!(
+            <div class="tool-view">{find.render_lines(&call.body())}</div>
+        ),
+        _ => pretty_json(&find, &call.to_json())
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ToolOutputViewProp {
+    /// Call these outputs answer, when it could be found
+    pub call:Option<CallInfo>,
+    /// Date of the part holding the outputs
+    pub date:Option<DateTime<Utc>>,
+    pub txt:String,
+    #[prop_or_default]
+    pub depth:usize,
+    /// Tag times of the part holding the outputs, starting at their content
+    #[prop_or_default]
+    pub timing:Option<TextTiming>
+}
+
+#[function_component(ToolOutputView)]
+pub fn tool_output_view(prop:&ToolOutputViewProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
+    let output = ToolText::parse(&prop.txt);
+    // outputs sometimes name their tool themselves
+    let tool_name = parse_tags(&prop.txt).first_of_kind(TagKind::Tool).map(|tool| {tool.text().trim().to_string()}).or(prop.call.as_ref().map(|call| {call.tool_name.clone()})).unwrap_or_default();
+    match tool_from_name(&tool_name) {
+        Some(ProximaTool::Agent) if prop.depth < MAX_AGENT_DEPTH => html!(
+            <AgentThreadView call={prop.call.clone()} txt={prop.txt.clone()} finished_at={prop.date} depth={prop.depth} timing={prop.timing.clone()}/>
+        ),
+        Some(ProximaTool::Calculator) => {
+            let expression = prop.call.as_ref().map(|call| {ToolText::parse(&call.txt).body()}).unwrap_or("?".to_string());
+            html!(
+                <p class="tool-view"><code>{expression}</code>{" = "}<b>{output.body()}</b></p>
+            )
+        },
+        Some(ProximaTool::Web) => {
+            let results = web_results(&output);
+            if results.is_empty() {
+                return find.render_lines(&output.body())
+            }
+            html!(
+                <div class="tool-view">
+                {
+                    results.into_iter().map(|result| {
+                        html!(
+                            <div class="web-result standard-padding-margin-corners">
+                                {
+                                    match &result.link {
+                                        Some(link) => {
+                                            let url = link.clone();
+                                            let onclick = Callback::from(move |event:MouseEvent| {
+                                                event.prevent_default();
+                                                let url = url.clone();
+                                                spawn_local(async move {
+                                                    let _ = open_url(url).await;
+                                                });
+                                            });
+                                            html!(<a href={link.clone()} {onclick}><b>{result.title.clone()}</b></a>)
+                                        },
+                                        None => html!(<b>{result.title.clone()}</b>)
+                                    }
+                                }
+                                {
+                                    match &result.link {
+                                        Some(link) => html!(<p class="web-result-link">{link.clone()}</p>),
+                                        None => html!()
+                                    }
+                                }
+                                <div>{find.render_markdown(&result.snippet)}</div>
+                            </div>
+                        )
+                    }).collect::<Html>()
+                }
+                </div>
+            )
+        },
+        Some(ProximaTool::Python) => {
+            let (stdout, stderr) = python_streams(&output);
+            html!(
+                <div class="tool-view">
+                    <p>{"stdout"}</p>
+                    <pre class="python-stdout">{if stdout.is_empty() {"(nothing)".to_string()} else {stdout}}</pre>
+                    {
+                        if stderr.is_empty() {
+                            html!()
+                        }
+                        else {
+                            html!(
+                                <>
+                                <p>{"stderr"}</p>
+                                <pre class="python-stderr">{stderr}</pre>
+                                </>
+                            )
+                        }
+                    }
+                </div>
+            )
+        },
+        Some(ProximaTool::Memory) | Some(ProximaTool::LocalMemory) => {
+            let items = memory_items(&output);
+            html!(
+                <div class="tool-view">
+                    <p>{format!("{} memory item(s)", items.len())}</p>
+                    <ul>
+                    {
+                        items.into_iter().map(|item| {
+                            html!(<li class="memory-item">{find.render_markdown(&item)}</li>)
+                        }).collect::<Html>()
+                    }
+                    </ul>
+                </div>
+            )
+        },
+        Some(ProximaTool::Time) => html!(
+            <p class="tool-view">{"🕒 "}{compact_time(&output.body()).unwrap_or(output.body())}</p>
+        ),
+        Some(ProximaTool::Jobs) => html!(
+            <div class="tool-view">
+            {
+                job_statuses(&output).into_iter().map(|job| {
+                    html!(
+                        <div class="horizontal-flex">
+                            <span class={status_class(&job.status)}>{if job.status.is_empty() {"?".to_string()} else {job.status.clone()}}</span>
+                            <span>{job.name}</span>
+                        </div>
+                    )
+                }).collect::<Html>()
+            }
+            </div>
+        ),
+        _ => if output.json().is_some() || !output.fields.is_empty() {
+            pretty_json(&find, &output.to_json())
+        }
+        else {
+            find.render_lines(&output.body())
+        }
+    }
+}
+
+fn agent_prompt(call:&ToolText) -> String {
+    call.field(&["prompt", "task", "instructions", "query", "input", "inputs"]).unwrap_or(call.body())
+}
+
+#[derive(Properties, PartialEq)]
+struct CollapsibleProp {
+    title:String,
+    #[prop_or_default]
+    open:bool,
+    #[prop_or_default]
+    children:Html
+}
+
+#[function_component(Collapsible)]
+fn collapsible(prop:&CollapsibleProp) -> Html {
+    let should_show = use_state_eq(|| {prop.open});
+    let callback = {
+        let should_show = should_show.clone();
+        Callback::from(move |mouse_evt:MouseEvent| {
+            should_show.set(!*should_show);
+        })
+    };
+    html!(
+        <div>
+            <button class="mainapp-button standard-padding-margin-corners" onclick={callback}>{format!("{} (click to {})", prop.title, if *should_show {"hide"} else {"show"})}</button>
+            {
+                if *should_show {
+                    prop.children.clone()
+                }
+                else {
+                    html!()
+                }
+            }
+        </div>
+    )
+}
+
+/// Outputs found in a sub-agent's work, with where their content starts and ends in it
+struct AgentOutputs {
+    txt:String,
+    content_start:usize,
+    end:usize
+}
+
+/// One step of a sub-agent's work, in the order it was done
+enum AgentStep {
+    Thought(String),
+    Call { call:Element, start:usize, outputs:Option<AgentOutputs> },
+    Outputs(AgentOutputs),
+    Message(String)
+}
+
+/// Splits what a sub-agent sent back into its steps and its final answer
+fn agent_steps(txt:&str) -> (Vec<AgentStep>, Option<String>) {
+    let parsed = parse_tags(txt);
+    let mut steps:Vec<AgentStep> = Vec::with_capacity(parsed.children.len());
+    let mut answer = None;
+    let mut cursor = 0;
+    for child in parsed.children {
+        match child {
+            HtmlNode::Element(element) => {
+                let (start, content_start, end) = element_span(txt, cursor, &element).unwrap_or((cursor, cursor, cursor));
+                cursor = end;
+                match element.kind {
+                    TagKind::Think => steps.push(AgentStep::Thought(element.content)),
+                    TagKind::Call => steps.push(AgentStep::Call { call: element, start, outputs: None }),
+                    TagKind::Outputs => {
+                        let found = AgentOutputs { txt: element.content, content_start, end };
+                        // calls made one after the other get their outputs in the order they were made
+                        let waiting = steps.iter().rev().take_while(|step| {matches!(step, AgentStep::Call { outputs: None, .. })}).count();
+                        match steps.len().checked_sub(waiting).filter(|_| {waiting > 0}).and_then(|first| {steps.get_mut(first)}) {
+                            Some(AgentStep::Call { outputs, .. }) => *outputs = Some(found),
+                            _ => steps.push(AgentStep::Outputs(found))
+                        }
+                    },
+                    TagKind::Response => answer = Some(element.content),
+                    TagKind::Tool => (),
+                    _ => steps.push(AgentStep::Message(format!("<{}>{}</{}>", element.name, element.content, element.name)))
+                }
+            },
+            HtmlNode::Text(text) => if text.trim().len() > 0 {
+                steps.push(AgentStep::Message(text));
+            }
+        }
+    }
+    if !parsed.pending.trim().is_empty() {
+        steps.push(AgentStep::Message(parsed.pending));
+    }
+    // without a response tag the answer is what the sub-agent wrote after its last call
+    if answer.is_none() && matches!(steps.last(), Some(AgentStep::Message(_))) && let Some(AgentStep::Message(message)) = steps.pop() {
+        answer = Some(message);
+    }
+    (steps, answer)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct AgentThreadViewProp {
+    pub call:Option<CallInfo>,
+    /// Everything the sub-agent sent back
+    pub txt:String,
+    pub finished_at:Option<DateTime<Utc>>,
+    pub depth:usize,
+    /// Tag times of the part holding the sub-agent's work, starting at `txt`, which time its steps
+    #[prop_or_default]
+    pub timing:Option<TextTiming>
+}
+
+#[function_component(AgentThreadView)]
+pub fn agent_thread_view(prop:&AgentThreadViewProp) -> Html {
+    let find = use_context::<FindInChat>().unwrap_or_default();
+    let (steps, answer) = agent_steps(&prop.txt);
+    let prompt = prop.call.as_ref().map(|call| {agent_prompt(&ToolText::parse(&call.txt))});
+    let started_at = prop.call.as_ref().and_then(|call| {call.date});
+    let timing = match (started_at, prop.finished_at) {
+        (Some(start), Some(end)) if end > start => format!("took {}, started {}", format_millis(end.signed_duration_since(start).num_milliseconds()), start.with_timezone(&Local).format("%H:%M:%S")),
+        (Some(start), _) => format!("started {}", start.with_timezone(&Local).format("%H:%M:%S")),
+        (None, Some(end)) => format!("finished {}", end.with_timezone(&Local).format("%H:%M:%S")),
+        (None, None) => "no timing".to_string()
+    };
+    let calls = steps.iter().filter(|step| {matches!(step, AgentStep::Call { .. })}).count();
+    let title = format!("Sub-agent thread, depth {} - {} step(s), {} tool call(s), {}", prop.depth + 1, steps.len(), calls, timing);
+    let time_at = |offset:usize| {prop.timing.as_ref().and_then(|timing| {timing.at(offset)})};
+    let timing_of = |outputs:&AgentOutputs| {prop.timing.as_ref().map(|timing| {timing.shifted(outputs.content_start)})};
+
+    let step_htmls = steps.into_iter().enumerate().map(|(i, step)| {
+        match step {
+            AgentStep::Thought(thought) => html!(
+                <Collapsible title={format!("{}. Thought process", i + 1)}>
+                    <div>{find.render_lines(&thought)}</div>
+                </Collapsible>
+            ),
+            AgentStep::Call { call, start, outputs } => {
+                let info = CallInfo::from_element(&call, time_at(start));
+                let is_agent = matches!(tool_from_name(&info.tool_name), Some(ProximaTool::Agent));
+                match outputs {
+                    // nested sub-agents are opened directly, their own thread is already collapsible
+                    Some(outputs) if is_agent => html!(
+                        <ToolOutputView call={Some(info)} date={time_at(outputs.end)} timing={timing_of(&outputs)} txt={outputs.txt.clone()} depth={prop.depth + 1}/>
+                    ),
+                    outputs => html!(
+                        <Collapsible title={format!("{}. Tool call : {}{}", i + 1, info.tool_name, if outputs.is_none() {" (no outputs)"} else {""})}>
+                            <ToolCallView tool_name={info.tool_name.clone()} txt={info.txt.clone()}/>
+                            {
+                                match outputs {
+                                    Some(outputs) => html!(<ToolOutputView call={Some(info.clone())} date={time_at(outputs.end)} timing={timing_of(&outputs)} txt={outputs.txt.clone()} depth={prop.depth + 1}/>),
+                                    None => html!()
+                                }
+                            }
+                        </Collapsible>
+                    )
+                }
+            },
+            AgentStep::Outputs(outputs) => html!(
+                <Collapsible title={format!("{}. Tool outputs", i + 1)}>
+                    <ToolOutputView call={None::<CallInfo>} date={time_at(outputs.end)} timing={timing_of(&outputs)} txt={outputs.txt.clone()} depth={prop.depth + 1}/>
+                </Collapsible>
+            ),
+            AgentStep::Message(message) => html!(
+                <div class="agent-message">{find.render_lines(&message)}</div>
+            )
+        }
+    }).collect::<Html>();
+
+    html!(
+        <div class="agent-thread">
+            <Collapsible title={title} open={prop.depth == 0 || find.matches(&prop.txt)}>
+                {
+                    match prompt {
+                        Some(prompt) => html!(<div class="agent-prompt"><b>{"Prompt"}</b>{find.render_lines(&prompt)}</div>),
+                        None => html!()
+                    }
+                }
+                <div class="agent-steps">{step_htmls}</div>
+                <div class="agent-answer">
+                    <b>{"Final answer"}</b>
+                    {
+                        match answer {
+                            Some(answer) => find.render_lines(&answer),
+                            None => html!(<p>{"(no answer yet)"}</p>)
+                        }
+                    }
+                </div>
+            </Collapsible>
+        </div>
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn streams(txt:&str) -> (String, String) {
+        python_streams(&ToolText::parse(txt))
+    }
+
+    #[test]
+    fn tool_text_splits_sub_tags_from_text() {
+        let call = ToolText::parse("<tool>python</tool><code>print(1)</code>");
+        assert_eq!(call.fields, vec![(String::from("code"), String::from("print(1)"))]);
+        assert_eq!(call.body(), "print(1)");
+        let call = ToolText::parse("<tool>web</tool> rust lifetimes ");
+        assert!(call.fields.is_empty());
+        assert_eq!(call.body(), "rust lifetimes");
+        let call = ToolText::parse("<action>add</action><content>{\"a\":1}</content>");
+        assert_eq!(call.field(&["ACTION", "action"]), Some(String::from("add")));
+        assert_eq!(call.to_json(), serde_json::json!({"action": "add", "content": {"a": 1}}));
+    }
+
+    #[test]
+    fn web_results_are_read_from_json() {
+        let output = ToolText::parse(r#"{"results": [{"title": "Rust", "url": "https://www.rust-lang.org", "snippet": "A language"}, {"name": "Bad", "link": "javascript:alert(1)", "description": "Not a page"}]}"#);
+        let results = web_results(&output);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Rust");
+        assert_eq!(results[0].link.as_deref(), Some("https://www.rust-lang.org"));
+        assert_eq!(results[0].snippet, "A language");
+        // links that are not web pages are not shown as links
+        assert_eq!(results[1].title, "Bad");
+        assert_eq!(results[1].link, None);
+    }
+
+    #[test]
+    fn web_results_are_read_from_paragraphs() {
+        let output = ToolText::parse("Rust\nhttps://www.rust-lang.org\nA language\n\nTitle: Docs\nURL: https://doc.rust-lang.org\nThe docs\nof Rust");
+        let results = web_results(&output);
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].title.as_str(), results[0].link.as_deref(), results[0].snippet.as_str()), ("Rust", Some("https://www.rust-lang.org"), "A language"));
+        assert_eq!((results[1].title.as_str(), results[1].link.as_deref(), results[1].snippet.as_str()), ("Docs", Some("https://doc.rust-lang.org"), "The docs of Rust"));
+        assert_eq!(first_link("see (https://example.com/page) here"), Some(String::from("https://example.com/page")));
+    }
+
+    #[test]
+    fn python_streams_are_split() {
+        assert_eq!(streams("<stdout>4</stdout><stderr>warning</stderr>"), (String::from("4"), String::from("warning")));
+        assert_eq!(streams(r#"{"stdout": "4\n", "stderr": null}"#), (String::from("4\n"), String::new()));
+        assert_eq!(streams("stdout: 4\nSTDERR: oops"), (String::from("4"), String::from("oops")));
+        assert_eq!(streams("4"), (String::from("4"), String::new()));
+    }
+
+    #[test]
+    fn tracebacks_go_to_stderr() {
+        let traceback = "Traceback (most recent call last):\n  File \"<stdin>\", line 1\nZeroDivisionError: division by zero";
+        assert_eq!(streams(traceback), (String::new(), traceback.to_string()));
+    }
+
+    #[test]
+    fn memory_items_are_listed() {
+        assert_eq!(memory_items(&ToolText::parse(r#"["likes tea", {"content": "lives in Lyon"}]"#)), vec!["likes tea", "lives in Lyon"]);
+        assert_eq!(memory_items(&ToolText::parse("- likes tea\n- lives in Lyon")), vec!["likes tea", "lives in Lyon"]);
+        assert_eq!(memory_items(&ToolText::parse("1. likes tea\n2. lives in Lyon")), vec!["likes tea", "lives in Lyon"]);
+        assert_eq!(memory_items(&ToolText::parse("nothing found")), vec!["nothing found"]);
+        assert!(memory_items(&ToolText::parse("")).is_empty());
+    }
+
+    #[test]
+    fn job_statuses_are_read() {
+        let jobs = job_statuses(&ToolText::parse("- build: done\n- deploy: failed\n- cleanup"));
+        assert_eq!(jobs.iter().map(|job| {(job.name.as_str(), job.status.as_str())}).collect::<Vec<(&str, &str)>>(), vec![("build", "done"), ("deploy", "failed"), ("cleanup", "")]);
+        assert_eq!(status_class("Succeeded"), "job-status job-done");
+        assert_eq!(status_class("cancelled"), "job-status job-failed");
+        assert_eq!(status_class("running"), "job-status");
+    }
+
+    #[test]
+    fn times_are_found_in_sentences() {
+        assert_eq!(compact_time("It is 2024-05-01 13:45:00 now"), Some(String::from("Wed 01 May 2024, 13:45:00")));
+        assert_eq!(compact_time("no date here"), None);
+    }
+
+    #[test]
+    fn tool_names_are_normalized() {
+        assert!(matches!(tool_from_name("Web_Search"), Some(ProximaTool::Web)));
+        assert!(matches!(tool_from_name(" local memory "), Some(ProximaTool::LocalMemory)));
+        assert!(tool_from_name("shell").is_none());
+    }
+
+    fn kinds(steps:&[AgentStep]) -> Vec<String> {
+        steps.iter().map(|step| {
+            match step {
+                AgentStep::Thought(thought) => format!("thought {thought}"),
+                AgentStep::Call { call, outputs, .. } => format!("call {} -> {}", call.content, outputs.as_ref().map(|outputs| {outputs.txt.as_str()}).unwrap_or("nothing")),
+                AgentStep::Outputs(outputs) => format!("outputs {}", outputs.txt),
+                AgentStep::Message(message) => format!("message {}", message.trim())
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn agent_steps_pair_calls_with_their_outputs() {
+        let (steps, answer) = agent_steps("<think>plan</think><call>a</call><outputs>ra</outputs>Looks good<response>done</response>");
+        assert_eq!(kinds(&steps), vec!["thought plan", "call a -> ra", "message Looks good"]);
+        assert_eq!(answer, Some(String::from("done")));
+    }
+
+    #[test]
+    fn interleaved_calls_get_their_outputs_in_order() {
+        let (steps, answer) = agent_steps("<call>a</call><call>b</call><outputs>ra</outputs><outputs>rb</outputs><outputs>extra</outputs>");
+        assert_eq!(kinds(&steps), vec!["call a -> ra", "call b -> rb", "outputs extra"]);
+        assert_eq!(answer, None);
+        let (steps, _) = agent_steps("<call>a</call><think>wait</think><call>b</call><outputs>rb</outputs>");
+        assert_eq!(kinds(&steps), vec!["call a -> nothing", "thought wait", "call b -> rb"]);
+    }
+
+    #[test]
+    fn nested_agents_stay_in_their_outputs() {
+        let inner = "<call><tool>web</tool>q</call><outputs>r</outputs><response>inner answer</response>";
+        let txt = format!("<call><tool>agent</tool>task</call><outputs>{inner}</outputs><response>outer answer</response>");
+        let (steps, answer) = agent_steps(&txt);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(answer, Some(String::from("outer answer")));
+        let Some(AgentStep::Call { start, outputs: Some(outputs), .. }) = steps.first() else {
+            panic!("the call lost its outputs")
+        };
+        assert_eq!(*start, 0);
+        assert_eq!(outputs.txt, inner);
+        // the span points at the outputs in the text, which is what times the nested steps
+        assert_eq!(&txt[outputs.content_start..outputs.end], inner);
+        let (inner_steps, inner_answer) = agent_steps(&outputs.txt);
+        assert_eq!(inner_steps.len(), 1);
+        assert_eq!(inner_answer, Some(String::from("inner answer")));
+    }
+
+    #[test]
+    fn the_last_message_is_the_answer_without_a_response_tag() {
+        let (steps, answer) = agent_steps("<call>a</call><outputs>ra</outputs>\n\nThe answer is 4");
+        assert_eq!(kinds(&steps), vec!["call a -> ra"]);
+        assert_eq!(answer, Some(String::from("\n\nThe answer is 4")));
+    }
+
+    #[test]
+    fn text_timing_is_shifted_into_the_part() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let later = DateTime::from_timestamp(1_700_000_010, 0).unwrap();
+        let timing = TextTiming { times: Rc::new(TagTimes(vec![(0, start), (30, later)])), offset: 10 };
+        assert_eq!(timing.at(5), Some(start));
+        assert_eq!(timing.at(20), Some(later));
+        assert_eq!(timing.shifted(15).at(4), Some(start));
+        assert_eq!(timing.shifted(15).at(5), Some(later));
+    }
+}